@@ -18,22 +18,72 @@
 //! request with the `RequestAuth` field in order to get the `ApplicationName` of the application
 //! sending the request. The `ApplicationName` string is used to namespace the keys of all
 //! applications with their name.
+//! Some authenticators do not trust the `RequestAuth` field alone and also rely on the metadata of
+//! the connection the request was received on, as given by the listener.
 
 pub mod simple_authenticator;
+pub mod unix_peer_credentials_authenticator;
 
+use crate::front::listener::ConnectionMetadata;
 use parsec_interface::requests::request::RequestAuth;
-use parsec_interface::requests::Result;
+use parsec_interface::requests::{AuthType, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Copy, Clone)]
+pub enum AuthenticatorType {
+    Simple,
+    UnixPeerCredentials,
+}
+
+impl AuthenticatorType {
+    pub fn to_auth_type(&self) -> AuthType {
+        match self {
+            AuthenticatorType::Simple => AuthType::Simple,
+            AuthenticatorType::UnixPeerCredentials => AuthType::UnixPeerCredentials,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AuthenticatorConfig {
+    pub authenticator_type: AuthenticatorType,
+    pub allowed_uids: Option<Vec<u32>>,
+}
+
+/// Decides what to do with requests sent without authentication (`AuthType::NoAuth`).
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum NoAuthPolicy {
+    /// Dispatch unauthenticated requests to any provider. Operations needing an application name
+    /// will still fail.
+    Allow,
+    /// Only dispatch unauthenticated requests to the Core Provider.
+    CoreOnly,
+    /// Never dispatch unauthenticated requests.
+    Reject,
+}
+
+impl Default for NoAuthPolicy {
+    fn default() -> Self {
+        NoAuthPolicy::Allow
+    }
+}
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct ApplicationName(String);
 
 pub trait Authenticate {
-    /// Authenticates a `RequestAuth` payload and returns the `ApplicationName` if successfull.
+    /// Authenticates a `RequestAuth` payload and returns the `ApplicationName` if successfull. The
+    /// metadata of the connection on which the request was received is also given, if the
+    /// listener could get it.
     ///
     /// # Errors
     ///
     /// If the authentification fails, returns a `ResponseStatus::AuthenticationError`.
-    fn authenticate(&self, auth: &RequestAuth) -> Result<ApplicationName>;
+    fn authenticate(
+        &self,
+        auth: &RequestAuth,
+        meta: Option<ConnectionMetadata>,
+    ) -> Result<ApplicationName>;
 }
 
 impl ApplicationName {
@@ -20,6 +20,7 @@
 
 use super::ApplicationName;
 use super::Authenticate;
+use crate::front::listener::ConnectionMetadata;
 use parsec_interface::requests::request::RequestAuth;
 use parsec_interface::requests::{ResponseStatus, Result};
 use std::str;
@@ -27,7 +28,11 @@ use std::str;
 pub struct SimpleAuthenticator;
 
 impl Authenticate for SimpleAuthenticator {
-    fn authenticate(&self, auth: &RequestAuth) -> Result<ApplicationName> {
+    fn authenticate(
+        &self,
+        auth: &RequestAuth,
+        _meta: Option<ConnectionMetadata>,
+    ) -> Result<ApplicationName> {
         if auth.is_empty() {
             Ok(ApplicationName(String::from("root")))
         } else {
@@ -53,7 +58,7 @@ mod test {
         let req_auth = RequestAuth::from_bytes(app_name.clone().into_bytes());
 
         let auth_name = authenticator
-            .authenticate(&req_auth)
+            .authenticate(&req_auth, None)
             .expect("Failed to authenticate");
 
         assert_eq!(auth_name.get_name(), app_name);
@@ -64,7 +69,7 @@ mod test {
     fn failed_authentication() {
         let authenticator = SimpleAuthenticator {};
         authenticator
-            .authenticate(&RequestAuth::from_bytes(vec![0xff; 5]))
+            .authenticate(&RequestAuth::from_bytes(vec![0xff; 5]), None)
             .expect("Failed to authenticate");
     }
 
@@ -72,7 +77,7 @@ mod test {
     fn auth_root() {
         let authenticator = SimpleAuthenticator {};
         let auth_name = authenticator
-            .authenticate(&RequestAuth::from_bytes(Vec::new()))
+            .authenticate(&RequestAuth::from_bytes(Vec::new()), None)
             .expect("Failed to authenticate");
 
         assert_eq!(auth_name.get_name(), "root");
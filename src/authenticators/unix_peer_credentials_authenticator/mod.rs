@@ -0,0 +1,185 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Unix peer credentials authenticator
+//!
+//! The `UnixPeerCredentialsAuthenticator` uses the credentials of the peer process, as verified by
+//! the kernel on the Unix domain socket the request was received on, to authenticate it. The
+//! `ApplicationName` is the decimal representation of the UID of the peer process.
+//!
+//! The `RequestAuth` field can either be empty or contain the UID the client thinks it runs as,
+//! encoded as a native-endian 32 bits unsigned integer. In the latter case, it has to match the
+//! UID given by the kernel. The client can not claim another identity in any case.
+//! The authenticator can be restricted to a list of allowed UIDs, other peers will fail to
+//! authenticate.
+
+use super::ApplicationName;
+use super::Authenticate;
+use crate::front::listener::ConnectionMetadata;
+use log::{error, info};
+use parsec_interface::requests::request::RequestAuth;
+use parsec_interface::requests::{ResponseStatus, Result};
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+pub struct UnixPeerCredentialsAuthenticator {
+    // If set, only peers with one of those UIDs can be authenticated.
+    allowed_uids: Option<HashSet<u32>>,
+}
+
+impl UnixPeerCredentialsAuthenticator {
+    pub fn new(allowed_uids: Option<Vec<u32>>) -> UnixPeerCredentialsAuthenticator {
+        UnixPeerCredentialsAuthenticator {
+            allowed_uids: allowed_uids.map(|uids| uids.into_iter().collect()),
+        }
+    }
+}
+
+impl Authenticate for UnixPeerCredentialsAuthenticator {
+    fn authenticate(
+        &self,
+        auth: &RequestAuth,
+        meta: Option<ConnectionMetadata>,
+    ) -> Result<ApplicationName> {
+        let (uid, gid, pid) = match meta {
+            Some(ConnectionMetadata::UnixPeerCredentials { uid, gid, pid }) => (uid, gid, pid),
+            None => {
+                error!("Peer credentials authentication needs the credentials of the connection.");
+                return Err(ResponseStatus::AuthenticationError);
+            }
+        };
+
+        if !auth.is_empty() {
+            let claimed_uid: [u8; 4] = match auth.bytes().try_into() {
+                Ok(claimed_uid) => claimed_uid,
+                Err(_) => {
+                    error!("The authentication field should contain a 32 bits UID.");
+                    return Err(ResponseStatus::AuthenticationError);
+                }
+            };
+            let claimed_uid = u32::from_ne_bytes(claimed_uid);
+            if claimed_uid != uid {
+                error!(
+                    "Claimed UID ({}) does not match the peer UID ({}).",
+                    claimed_uid, uid
+                );
+                return Err(ResponseStatus::AuthenticationError);
+            }
+        }
+
+        if let Some(allowed_uids) = &self.allowed_uids {
+            if !allowed_uids.contains(&uid) {
+                error!("Peer UID ({}) is not in the list of allowed UIDs.", uid);
+                return Err(ResponseStatus::AuthenticationError);
+            }
+        }
+
+        info!(
+            "Authenticated peer with UID {}, GID {} and PID {:?}.",
+            uid, gid, pid
+        );
+
+        Ok(ApplicationName(uid.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Authenticate;
+    use super::UnixPeerCredentialsAuthenticator;
+    use crate::front::listener::ConnectionMetadata;
+    use parsec_interface::requests::request::RequestAuth;
+    use parsec_interface::requests::ResponseStatus;
+
+    fn peer_credentials(uid: u32) -> Option<ConnectionMetadata> {
+        Some(ConnectionMetadata::UnixPeerCredentials {
+            uid,
+            gid: 100,
+            pid: Some(4242),
+        })
+    }
+
+    #[test]
+    fn successful_authentication() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(None);
+        let req_auth = RequestAuth::from_bytes(1000u32.to_ne_bytes().to_vec());
+
+        let auth_name = authenticator
+            .authenticate(&req_auth, peer_credentials(1000))
+            .expect("Failed to authenticate");
+
+        assert_eq!(auth_name.get_name(), "1000");
+    }
+
+    #[test]
+    fn empty_auth_uses_peer_uid() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(None);
+
+        let auth_name = authenticator
+            .authenticate(&RequestAuth::from_bytes(Vec::new()), peer_credentials(0))
+            .expect("Failed to authenticate");
+
+        assert_eq!(auth_name.get_name(), "0");
+    }
+
+    #[test]
+    fn wrong_uid() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(None);
+        let req_auth = RequestAuth::from_bytes(0u32.to_ne_bytes().to_vec());
+
+        let status = authenticator
+            .authenticate(&req_auth, peer_credentials(1000))
+            .expect_err("Impersonating another UID should fail");
+
+        assert_eq!(status, ResponseStatus::AuthenticationError);
+    }
+
+    #[test]
+    fn malformed_auth() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(None);
+        let req_auth = RequestAuth::from_bytes(vec![0xff; 5]);
+
+        let status = authenticator
+            .authenticate(&req_auth, peer_credentials(1000))
+            .expect_err("Authentication should have failed");
+
+        assert_eq!(status, ResponseStatus::AuthenticationError);
+    }
+
+    #[test]
+    fn allowed_uids() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(Some(vec![1000, 1001]));
+
+        let auth_name = authenticator
+            .authenticate(&RequestAuth::from_bytes(Vec::new()), peer_credentials(1001))
+            .expect("Failed to authenticate");
+        assert_eq!(auth_name.get_name(), "1001");
+
+        let status = authenticator
+            .authenticate(&RequestAuth::from_bytes(Vec::new()), peer_credentials(0))
+            .expect_err("UID should not have been allowed");
+        assert_eq!(status, ResponseStatus::AuthenticationError);
+    }
+
+    #[test]
+    fn no_connection_metadata() {
+        let authenticator = UnixPeerCredentialsAuthenticator::new(None);
+
+        let status = authenticator
+            .authenticate(&RequestAuth::from_bytes(Vec::new()), None)
+            .expect_err("Authentication should have failed");
+
+        assert_eq!(status, ResponseStatus::AuthenticationError);
+    }
+}
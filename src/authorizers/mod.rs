@@ -0,0 +1,48 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Authorizers
+//!
+//! Once a request has been authenticated, authorizers decide if the `ApplicationName` is allowed
+//! to perform the operation. The decision is based on the provider the request is sent to, the
+//! opcode of the operation and, for operations on keys, the name of the key.
+//! Authorizers need to implement the `Authorize` trait.
+
+pub mod policy_file_authorizer;
+
+use crate::authenticators::ApplicationName;
+use parsec_interface::requests::{Opcode, ProviderID, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct AuthorizationConfig {
+    pub policy_file_path: String,
+}
+
+pub trait Authorize {
+    /// Checks if the application is allowed to perform the operation described by the provider
+    /// ID, the opcode and the key name, if the operation is about a key. The application name is
+    /// `None` for requests sent without authentication.
+    ///
+    /// # Errors
+    ///
+    /// If the operation is not allowed, returns `ResponseStatus::PsaErrorNotPermitted`.
+    fn authorize(
+        &self,
+        app_name: Option<&ApplicationName>,
+        provider_id: ProviderID,
+        opcode: Opcode,
+        key_name: Option<&str>,
+    ) -> Result<()>;
+}
@@ -0,0 +1,411 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! An authorizer reading its access control policy from a TOML file
+//!
+//! The policy is a list of rules allowing an application to perform operations. A request is
+//! allowed if at least one rule matches it. A rule matches if:
+//! - its application name is the one of the request
+//! - the provider of the request is in its list of providers, if there is one
+//! - the opcode of the request is in its list of opcodes, if there is one
+//! - the key name of the request matches one of its key name patterns, if there are any and the
+//! operation is about a key. Patterns can contain `*` wildcards matching any sequence of
+//! characters.
+//!
+//! Applications for which no rule exist, and requests sent without authentication, are governed by
+//! the default policy. Requests to the Core Provider are always allowed as they do not involve
+//! keys.
+//!
+//! ```toml
+//! default_policy = "Deny"
+//!
+//! [[rule]]
+//! application = "1000"
+//! providers = ["MbedProvider"]
+//!
+//! [[rule]]
+//! application = "1000"
+//! providers = ["Pkcs11Provider"]
+//! opcodes = ["AsymSign", "AsymVerify", "ExportPublicKey"]
+//! key_names = ["device-*"]
+//! ```
+
+use super::Authorize;
+use crate::authenticators::ApplicationName;
+use crate::providers::ProviderType;
+use log::warn;
+use parsec_interface::requests::{Opcode, ProviderID, ResponseStatus, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum DefaultPolicy {
+    Allow,
+    Deny,
+}
+
+#[derive(Deserialize, Debug)]
+struct Rule {
+    application: String,
+    providers: Option<Vec<ProviderType>>,
+    opcodes: Option<Vec<String>>,
+    key_names: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug)]
+struct PolicyFile {
+    default_policy: DefaultPolicy,
+    #[serde(default)]
+    rule: Vec<Rule>,
+}
+
+/// A rule from the policy file, with its opcode names converted.
+struct CompiledRule {
+    application: String,
+    providers: Option<Vec<ProviderID>>,
+    opcodes: Option<Vec<Opcode>>,
+    key_names: Option<Vec<String>>,
+}
+
+pub struct PolicyFileAuthorizer {
+    default_policy: DefaultPolicy,
+    rules: Vec<CompiledRule>,
+}
+
+/// Converts the name of an opcode, as written in the policy file, to its value.
+fn opcode_from_name(name: &str) -> Option<Opcode> {
+    match name {
+        "Ping" => Some(Opcode::Ping),
+        "ListProviders" => Some(Opcode::ListProviders),
+        "ListOpcodes" => Some(Opcode::ListOpcodes),
+        "ListKeys" => Some(Opcode::ListKeys),
+        "CreateKey" => Some(Opcode::CreateKey),
+        "ImportKey" => Some(Opcode::ImportKey),
+        "ExportPublicKey" => Some(Opcode::ExportPublicKey),
+        "DestroyKey" => Some(Opcode::DestroyKey),
+        "AsymSign" => Some(Opcode::AsymSign),
+        "AsymVerify" => Some(Opcode::AsymVerify),
+        "GrantKey" => Some(Opcode::GrantKey),
+        "RevokeKey" => Some(Opcode::RevokeKey),
+        "AsymmetricEncrypt" => Some(Opcode::AsymmetricEncrypt),
+        "AsymmetricDecrypt" => Some(Opcode::AsymmetricDecrypt),
+        "AeadEncrypt" => Some(Opcode::AeadEncrypt),
+        "AeadDecrypt" => Some(Opcode::AeadDecrypt),
+        "MacCompute" => Some(Opcode::MacCompute),
+        "MacVerify" => Some(Opcode::MacVerify),
+        "HashCompute" => Some(Opcode::HashCompute),
+        "HashCompare" => Some(Opcode::HashCompare),
+        "AsymSignMessage" => Some(Opcode::AsymSignMessage),
+        "AsymVerifyMessage" => Some(Opcode::AsymVerifyMessage),
+        "RawKeyAgreement" => Some(Opcode::RawKeyAgreement),
+        "KeyAgreementDeriveKey" => Some(Opcode::KeyAgreementDeriveKey),
+        "KeyDerivationOutputKey" => Some(Opcode::KeyDerivationOutputKey),
+        "KeyDerivationOutputBytes" => Some(Opcode::KeyDerivationOutputBytes),
+        "GenerateRandom" => Some(Opcode::GenerateRandom),
+        _ => None,
+    }
+}
+
+/// Checks if the name matches the pattern, where `*` matches any sequence of characters.
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    // There is always at least one part, even for an empty pattern.
+    let first = parts.next().unwrap_or("");
+    if !name.starts_with(first) {
+        return false;
+    }
+    let mut rest = &name[first.len()..];
+    let parts: Vec<&str> = parts.collect();
+    match parts.split_last() {
+        // No wildcard in the pattern.
+        None => rest.is_empty(),
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(index) => rest = &rest[index + part.len()..],
+                    None => return false,
+                }
+            }
+            rest.ends_with(last)
+        }
+    }
+}
+
+impl CompiledRule {
+    fn matches(
+        &self,
+        app_name: &ApplicationName,
+        provider_id: ProviderID,
+        opcode: Opcode,
+        key_name: Option<&str>,
+    ) -> bool {
+        if self.application != app_name.get_name() {
+            return false;
+        }
+        if let Some(providers) = &self.providers {
+            if !providers.contains(&provider_id) {
+                return false;
+            }
+        }
+        if let Some(opcodes) = &self.opcodes {
+            if !opcodes.contains(&opcode) {
+                return false;
+            }
+        }
+        match (&self.key_names, key_name) {
+            (Some(patterns), Some(key_name)) => patterns
+                .iter()
+                .any(|pattern| matches_pattern(pattern, key_name)),
+            _ => true,
+        }
+    }
+}
+
+impl PolicyFileAuthorizer {
+    /// Creates an authorizer from the content of a policy file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error as a String if the policy could not be parsed or contains unknown
+    /// opcodes.
+    pub fn from_policy(policy: &str) -> std::result::Result<PolicyFileAuthorizer, String> {
+        let policy_file: PolicyFile = toml::from_str(policy).map_err(|err| err.to_string())?;
+
+        let mut rules = Vec::new();
+        for rule in policy_file.rule {
+            let opcodes = match rule.opcodes {
+                Some(names) => {
+                    let mut opcodes = Vec::new();
+                    for name in names {
+                        opcodes.push(
+                            opcode_from_name(&name)
+                                .ok_or_else(|| format!("Unknown opcode \"{}\".", name))?,
+                        );
+                    }
+                    Some(opcodes)
+                }
+                None => None,
+            };
+            rules.push(CompiledRule {
+                application: rule.application,
+                providers: rule
+                    .providers
+                    .map(|providers| providers.iter().map(ProviderType::to_provider_id).collect()),
+                opcodes,
+                key_names: rule.key_names,
+            });
+        }
+
+        Ok(PolicyFileAuthorizer {
+            default_policy: policy_file.default_policy,
+            rules,
+        })
+    }
+}
+
+impl Authorize for PolicyFileAuthorizer {
+    fn authorize(
+        &self,
+        app_name: Option<&ApplicationName>,
+        provider_id: ProviderID,
+        opcode: Opcode,
+        key_name: Option<&str>,
+    ) -> Result<()> {
+        if provider_id == ProviderID::CoreProvider {
+            return Ok(());
+        }
+
+        let allowed = match app_name {
+            Some(app_name)
+                if self
+                    .rules
+                    .iter()
+                    .any(|rule| rule.application == app_name.get_name()) =>
+            {
+                self.rules
+                    .iter()
+                    .any(|rule| rule.matches(app_name, provider_id, opcode, key_name))
+            }
+            _ => self.default_policy == DefaultPolicy::Allow,
+        };
+
+        if allowed {
+            Ok(())
+        } else {
+            match app_name {
+                Some(app_name) => warn!(
+                    "Access denied: application \"{}\", provider {}, opcode {:?}, key name {:?}.",
+                    app_name, provider_id, opcode, key_name
+                ),
+                None => warn!(
+                    "Access denied: unauthenticated request, provider {}, opcode {:?}.",
+                    provider_id, opcode
+                ),
+            }
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Authorize;
+    use super::{matches_pattern, PolicyFileAuthorizer};
+    use crate::authenticators::ApplicationName;
+    use parsec_interface::requests::{Opcode, ProviderID, ResponseStatus};
+
+    const POLICY: &str = r#"
+        default_policy = "Deny"
+
+        [[rule]]
+        application = "service-a"
+        providers = ["MbedProvider"]
+
+        [[rule]]
+        application = "service-a"
+        providers = ["Pkcs11Provider"]
+        opcodes = ["AsymSign", "AsymVerify"]
+        key_names = ["device-*"]
+    "#;
+
+    fn app(name: &str) -> ApplicationName {
+        ApplicationName::new(name.to_string())
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("key", "key"));
+        assert!(!matches_pattern("key", "key2"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("device-*", "device-1"));
+        assert!(!matches_pattern("device-*", "my-device-1"));
+        assert!(matches_pattern("*-signing-*", "a-signing-key"));
+        assert!(!matches_pattern("*-signing-*", "a-verify-key"));
+        assert!(matches_pattern("a*b*c", "abc"));
+        assert!(!matches_pattern("a*b*c", "acb"));
+    }
+
+    #[test]
+    fn allowed_by_rules() {
+        let authorizer = PolicyFileAuthorizer::from_policy(POLICY).unwrap();
+        let app_name = app("service-a");
+
+        authorizer
+            .authorize(
+                Some(&app_name),
+                ProviderID::MbedProvider,
+                Opcode::CreateKey,
+                Some("key"),
+            )
+            .expect("Any Mbed operation should be allowed");
+        authorizer
+            .authorize(
+                Some(&app_name),
+                ProviderID::Pkcs11Provider,
+                Opcode::AsymSign,
+                Some("device-1"),
+            )
+            .expect("Signing with a device key should be allowed");
+    }
+
+    #[test]
+    fn denied_by_rules() {
+        let authorizer = PolicyFileAuthorizer::from_policy(POLICY).unwrap();
+        let app_name = app("service-a");
+
+        assert_eq!(
+            authorizer.authorize(
+                Some(&app_name),
+                ProviderID::Pkcs11Provider,
+                Opcode::CreateKey,
+                Some("device-1"),
+            ),
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        );
+        assert_eq!(
+            authorizer.authorize(
+                Some(&app_name),
+                ProviderID::Pkcs11Provider,
+                Opcode::AsymSign,
+                Some("other-key"),
+            ),
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        );
+    }
+
+    #[test]
+    fn default_policy() {
+        let authorizer = PolicyFileAuthorizer::from_policy(POLICY).unwrap();
+        assert_eq!(
+            authorizer.authorize(
+                Some(&app("service-b")),
+                ProviderID::MbedProvider,
+                Opcode::CreateKey,
+                Some("key"),
+            ),
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        );
+        authorizer
+            .authorize(
+                Some(&app("service-b")),
+                ProviderID::CoreProvider,
+                Opcode::ListProviders,
+                None,
+            )
+            .expect("Core operations should always be allowed");
+
+        let authorizer = PolicyFileAuthorizer::from_policy("default_policy = \"Allow\"").unwrap();
+        authorizer
+            .authorize(
+                Some(&app("service-b")),
+                ProviderID::Pkcs11Provider,
+                Opcode::CreateKey,
+                Some("key"),
+            )
+            .expect("Applications without rules should be allowed");
+    }
+
+    #[test]
+    fn unauthenticated_requests() {
+        let authorizer = PolicyFileAuthorizer::from_policy(POLICY).unwrap();
+        assert_eq!(
+            authorizer.authorize(
+                None,
+                ProviderID::Pkcs11Provider,
+                Opcode::GenerateRandom,
+                None
+            ),
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        );
+        authorizer
+            .authorize(None, ProviderID::CoreProvider, Opcode::Ping, None)
+            .expect("Core operations should always be allowed");
+
+        let authorizer = PolicyFileAuthorizer::from_policy("default_policy = \"Allow\"").unwrap();
+        authorizer
+            .authorize(None, ProviderID::Pkcs11Provider, Opcode::HashCompute, None)
+            .expect("Unauthenticated requests should follow the default policy");
+    }
+
+    #[test]
+    fn unknown_opcode() {
+        let policy = r#"
+            default_policy = "Deny"
+
+            [[rule]]
+            application = "service-a"
+            opcodes = ["FlyToTheMoon"]
+        "#;
+        assert!(PolicyFileAuthorizer::from_policy(policy).is_err());
+    }
+}
@@ -13,13 +13,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use crate::authenticators::ApplicationName;
+use crate::authorizers::Authorize;
 use crate::providers::Provide;
+use log::error;
 use parsec_interface::operations::Convert;
 use parsec_interface::operations::{NativeOperation, NativeResult};
 use parsec_interface::requests::{
     request::RequestHeader, Request, Response, ResponseStatus, Result,
 };
 use parsec_interface::requests::{BodyType, ProviderID};
+use std::sync::Arc;
+
+/// Default maximum number of random bytes that can be generated in a single request.
+pub const DEFAULT_MAX_RANDOM_SIZE: usize = 4096;
 
 /// Component responsible for unmarshalling requests, passing the operation
 /// to the provider and marshalling the result.
@@ -27,14 +33,52 @@ use parsec_interface::requests::{BodyType, ProviderID};
 /// It also provides assessment capabilities, letting the dispatcher know if
 /// it can process a request.
 pub struct BackEndHandler {
-    // Send and Sync are required for Arc<FrontEndHandler> to be Send.
-    provider: Box<dyn Provide + Send + Sync>,
+    // Send and Sync are required for Arc<FrontEndHandler> to be Send. The provider is shared with
+    // the Core Provider when it forwards operations to it.
+    provider: Arc<dyn Provide + Send + Sync>,
     converter: Box<dyn Convert + Send + Sync>,
+    // Shared by all backend handlers. No authorization is done if it is not set.
+    authorizer: Option<Arc<dyn Authorize + Send + Sync>>,
     provider_id: ProviderID,
     content_type: BodyType,
     accept_type: BodyType,
     version_min: u8,
     version_maj: u8,
+    max_random_size: usize,
+}
+
+/// Returns the names of the keys the operation is about. Operations creating a key from another
+/// one are about both keys.
+fn operation_key_names(operation: &NativeOperation) -> Vec<&str> {
+    match operation {
+        NativeOperation::CreateKey(op) => vec![&op.key_name],
+        NativeOperation::ImportKey(op) => vec![&op.key_name],
+        NativeOperation::ExportPublicKey(op) => vec![&op.key_name],
+        NativeOperation::DestroyKey(op) => vec![&op.key_name],
+        NativeOperation::AsymSign(op) => vec![&op.key_name],
+        NativeOperation::AsymVerify(op) => vec![&op.key_name],
+        NativeOperation::GrantKey(op) => vec![&op.key_name],
+        NativeOperation::RevokeKey(op) => vec![&op.key_name],
+        NativeOperation::AsymmetricEncrypt(op) => vec![&op.key_name],
+        NativeOperation::AsymmetricDecrypt(op) => vec![&op.key_name],
+        NativeOperation::AeadEncrypt(op) => vec![&op.key_name],
+        NativeOperation::AeadDecrypt(op) => vec![&op.key_name],
+        NativeOperation::MacCompute(op) => vec![&op.key_name],
+        NativeOperation::MacVerify(op) => vec![&op.key_name],
+        NativeOperation::AsymSignMessage(op) => vec![&op.key_name],
+        NativeOperation::AsymVerifyMessage(op) => vec![&op.key_name],
+        NativeOperation::RawKeyAgreement(op) => vec![&op.private_key_name],
+        NativeOperation::KeyAgreementDeriveKey(op) => vec![&op.private_key_name, &op.key_name],
+        NativeOperation::KeyDerivationOutputKey(op) => vec![&op.base_key_name, &op.key_name],
+        NativeOperation::KeyDerivationOutputBytes(op) => vec![&op.base_key_name],
+        NativeOperation::ListProviders(_)
+        | NativeOperation::ListOpcodes(_)
+        | NativeOperation::ListKeys(_)
+        | NativeOperation::HashCompute(_)
+        | NativeOperation::HashCompare(_)
+        | NativeOperation::GenerateRandom(_)
+        | NativeOperation::Ping(_) => Vec::new(),
+    }
 }
 
 impl BackEndHandler {
@@ -79,8 +123,8 @@ impl BackEndHandler {
         }
     }
 
-    /// Unmarshall the request body, pass the operation to the provider and marshall
-    /// the result back.
+    /// Unmarshall the request body, check that the application is authorized to perform the
+    /// operation, pass the operation to the provider and marshall the result back.
     ///
     /// If any of the steps fails, a response containing an appropriate status code is
     /// returned.
@@ -97,7 +141,50 @@ impl BackEndHandler {
             };
         }
 
-        match unwrap_or_else_return!(self.converter.body_to_operation(request.body, opcode)) {
+        let operation =
+            unwrap_or_else_return!(self.converter.body_to_operation(request.body, opcode));
+
+        // Operations forwarded to another provider, for example by the Core Provider, need the same
+        // rights as when sent to that provider directly.
+        if let Some(provider_id) = self.provider.forwarded_to(opcode) {
+            if app_name.is_none() {
+                error!(
+                    "Unauthenticated requests can not be forwarded to provider {}.",
+                    provider_id
+                );
+                return Response::from_request_header(header, ResponseStatus::NotAuthenticated);
+            }
+            if let Some(authorizer) = &self.authorizer {
+                unwrap_or_else_return!(authorizer.authorize(
+                    app_name.as_ref(),
+                    provider_id,
+                    opcode,
+                    None,
+                ));
+            }
+        }
+
+        if let Some(authorizer) = &self.authorizer {
+            let key_names = operation_key_names(&operation);
+            if key_names.is_empty() {
+                unwrap_or_else_return!(authorizer.authorize(
+                    app_name.as_ref(),
+                    self.provider_id,
+                    opcode,
+                    None,
+                ));
+            }
+            for key_name in key_names {
+                unwrap_or_else_return!(authorizer.authorize(
+                    app_name.as_ref(),
+                    self.provider_id,
+                    opcode,
+                    Some(key_name),
+                ));
+            }
+        }
+
+        match operation {
             NativeOperation::ListProviders(op_list_providers) => {
                 let result =
                     unwrap_or_else_return!(self.provider.list_providers(op_list_providers));
@@ -107,6 +194,23 @@ impl BackEndHandler {
                 let result = unwrap_or_else_return!(self.provider.list_opcodes(op_list_opcodes));
                 self.result_to_response(NativeResult::ListOpcodes(result), header)
             }
+            NativeOperation::ListKeys(op_list_keys) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let mut result =
+                    unwrap_or_else_return!(self.provider.list_keys(app_name.clone(), op_list_keys));
+                // The Core Provider lists the keys of all the providers: only keep those of the
+                // providers the application is allowed to list the keys of.
+                if let Some(authorizer) = &self.authorizer {
+                    result.keys.retain(|key| {
+                        key.provider_id == self.provider_id
+                            || authorizer
+                                .authorize(Some(&app_name), key.provider_id, opcode, None)
+                                .is_ok()
+                    });
+                }
+                self.result_to_response(NativeResult::ListKeys(result), header)
+            }
             NativeOperation::Ping(op_ping) => {
                 let result = unwrap_or_else_return!(self.provider.ping(op_ping));
                 self.result_to_response(NativeResult::Ping(result), header)
@@ -154,19 +258,150 @@ impl BackEndHandler {
                     unwrap_or_else_return!(self.provider.asym_verify(app_name, op_asym_verify));
                 self.result_to_response(NativeResult::AsymVerify(result), header)
             }
+            NativeOperation::GrantKey(op_grant_key) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.grant_key(app_name, op_grant_key));
+                self.result_to_response(NativeResult::GrantKey(result), header)
+            }
+            NativeOperation::RevokeKey(op_revoke_key) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.revoke_key(app_name, op_revoke_key));
+                self.result_to_response(NativeResult::RevokeKey(result), header)
+            }
+            NativeOperation::AsymmetricEncrypt(op_asymmetric_encrypt) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .asym_encrypt(app_name, op_asymmetric_encrypt));
+                self.result_to_response(NativeResult::AsymmetricEncrypt(result), header)
+            }
+            NativeOperation::AsymmetricDecrypt(op_asymmetric_decrypt) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .asym_decrypt(app_name, op_asymmetric_decrypt));
+                self.result_to_response(NativeResult::AsymmetricDecrypt(result), header)
+            }
+            NativeOperation::AeadEncrypt(op_aead_encrypt) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.aead_encrypt(app_name, op_aead_encrypt));
+                self.result_to_response(NativeResult::AeadEncrypt(result), header)
+            }
+            NativeOperation::AeadDecrypt(op_aead_decrypt) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.aead_decrypt(app_name, op_aead_decrypt));
+                self.result_to_response(NativeResult::AeadDecrypt(result), header)
+            }
+            NativeOperation::MacCompute(op_mac_compute) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.mac_compute(app_name, op_mac_compute));
+                self.result_to_response(NativeResult::MacCompute(result), header)
+            }
+            NativeOperation::MacVerify(op_mac_verify) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result =
+                    unwrap_or_else_return!(self.provider.mac_verify(app_name, op_mac_verify));
+                self.result_to_response(NativeResult::MacVerify(result), header)
+            }
+            NativeOperation::HashCompute(op_hash_compute) => {
+                let result = unwrap_or_else_return!(self.provider.hash_compute(op_hash_compute));
+                self.result_to_response(NativeResult::HashCompute(result), header)
+            }
+            NativeOperation::HashCompare(op_hash_compare) => {
+                let result = unwrap_or_else_return!(self.provider.hash_compare(op_hash_compare));
+                self.result_to_response(NativeResult::HashCompare(result), header)
+            }
+            NativeOperation::AsymSignMessage(op_asym_sign_message) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .asym_sign_message(app_name, op_asym_sign_message));
+                self.result_to_response(NativeResult::AsymSignMessage(result), header)
+            }
+            NativeOperation::AsymVerifyMessage(op_asym_verify_message) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .asym_verify_message(app_name, op_asym_verify_message));
+                self.result_to_response(NativeResult::AsymVerifyMessage(result), header)
+            }
+            NativeOperation::RawKeyAgreement(op_raw_key_agreement) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .raw_key_agreement(app_name, op_raw_key_agreement));
+                self.result_to_response(NativeResult::RawKeyAgreement(result), header)
+            }
+            NativeOperation::KeyAgreementDeriveKey(op_key_agreement_derive_key) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .key_agreement_derive_key(app_name, op_key_agreement_derive_key));
+                self.result_to_response(NativeResult::KeyAgreementDeriveKey(result), header)
+            }
+            NativeOperation::KeyDerivationOutputKey(op_key_derivation_output_key) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .key_derivation_output_key(app_name, op_key_derivation_output_key));
+                self.result_to_response(NativeResult::KeyDerivationOutputKey(result), header)
+            }
+            NativeOperation::KeyDerivationOutputBytes(op_key_derivation_output_bytes) => {
+                let app_name =
+                    unwrap_or_else_return!(app_name.ok_or(ResponseStatus::NotAuthenticated));
+                let result = unwrap_or_else_return!(self
+                    .provider
+                    .key_derivation_output_bytes(app_name, op_key_derivation_output_bytes));
+                self.result_to_response(NativeResult::KeyDerivationOutputBytes(result), header)
+            }
+            NativeOperation::GenerateRandom(op_generate_random) => {
+                if op_generate_random.size > self.max_random_size {
+                    error!(
+                        "Requested {} random bytes, more than the maximum of {}.",
+                        op_generate_random.size, self.max_random_size
+                    );
+                    return Response::from_request_header(
+                        header,
+                        ResponseStatus::PsaErrorInvalidArgument,
+                    );
+                }
+                let result =
+                    unwrap_or_else_return!(self.provider.generate_random(op_generate_random));
+                self.result_to_response(NativeResult::GenerateRandom(result), header)
+            }
         }
     }
 }
 
 #[derive(Default)]
 pub struct BackEndHandlerBuilder {
-    provider: Option<Box<dyn Provide + Send + Sync>>,
+    provider: Option<Arc<dyn Provide + Send + Sync>>,
     converter: Option<Box<dyn Convert + Send + Sync>>,
+    authorizer: Option<Arc<dyn Authorize + Send + Sync>>,
     provider_id: Option<ProviderID>,
     content_type: Option<BodyType>,
     accept_type: Option<BodyType>,
     version_min: Option<u8>,
     version_maj: Option<u8>,
+    max_random_size: Option<usize>,
 }
 
 impl BackEndHandlerBuilder {
@@ -174,15 +409,17 @@ impl BackEndHandlerBuilder {
         BackEndHandlerBuilder {
             provider: None,
             converter: None,
+            authorizer: None,
             provider_id: None,
             content_type: None,
             accept_type: None,
             version_min: None,
             version_maj: None,
+            max_random_size: None,
         }
     }
 
-    pub fn with_provider(mut self, provider: Box<dyn Provide + Send + Sync>) -> Self {
+    pub fn with_provider(mut self, provider: Arc<dyn Provide + Send + Sync>) -> Self {
         self.provider = Some(provider);
         self
     }
@@ -192,6 +429,11 @@ impl BackEndHandlerBuilder {
         self
     }
 
+    pub fn with_authorizer(mut self, authorizer: Arc<dyn Authorize + Send + Sync>) -> Self {
+        self.authorizer = Some(authorizer);
+        self
+    }
+
     pub fn with_provider_id(mut self, provider_id: ProviderID) -> Self {
         self.provider_id = Some(provider_id);
         self
@@ -213,15 +455,22 @@ impl BackEndHandlerBuilder {
         self
     }
 
+    pub fn with_max_random_size(mut self, max_random_size: usize) -> Self {
+        self.max_random_size = Some(max_random_size);
+        self
+    }
+
     pub fn build(self) -> BackEndHandler {
         BackEndHandler {
             provider: self.provider.expect("Provider missing"),
             converter: self.converter.expect("Converter missing"),
+            authorizer: self.authorizer,
             provider_id: self.provider_id.expect("Provider ID missing"),
             content_type: self.content_type.expect("Content type missing"),
             accept_type: self.accept_type.expect("Accept type missing"),
             version_min: self.version_min.expect("Version min missing"),
             version_maj: self.version_maj.expect("Version maj missing"),
+            max_random_size: self.max_random_size.unwrap_or(DEFAULT_MAX_RANDOM_SIZE),
         }
     }
 }
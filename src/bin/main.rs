@@ -61,10 +61,10 @@ fn main() -> Result<(), Error> {
             break;
         }
 
-        if let Some(stream) = listener.accept() {
+        if let Some(connection) = listener.accept() {
             let front_end_handler = front_end_handler.clone();
             threadpool.execute(move || {
-                front_end_handler.handle_request(stream);
+                front_end_handler.handle_request(connection);
             });
         } else {
             ::std::thread::sleep(Duration::from_millis(
@@ -13,13 +13,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::listener;
+use listener::Connection;
+use listener::ConnectionMetadata;
 use listener::Listen;
-use listener::ReadWrite;
 use log::error;
 use std::fs;
-use std::io::ErrorKind;
-use std::os::unix::io::FromRawFd;
-use std::os::unix::net::UnixListener;
+use std::io::{Error, ErrorKind};
+#[cfg(target_os = "linux")]
+use std::mem;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::Path;
 use std::time::Duration;
 
@@ -78,12 +81,79 @@ impl DomainSocketListener {
     }
 }
 
+/// Gets the credentials of the process at the other end of the stream, as verified by the kernel
+/// with the `SO_PEERCRED` socket option.
+///
+/// # Errors
+///
+/// Returns the OS error if the `getsockopt` call failed.
+#[cfg(target_os = "linux")]
+pub fn peer_credentials(stream: &UnixStream) -> std::io::Result<ConnectionMetadata> {
+    let mut ucred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut ucred_size = mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut ucred as *mut libc::ucred as *mut libc::c_void,
+            &mut ucred_size,
+        )
+    };
+
+    if ret != 0 {
+        Err(Error::last_os_error())
+    } else if ucred_size as usize != mem::size_of::<libc::ucred>() {
+        Err(Error::new(
+            ErrorKind::Other,
+            "Unexpected size of the peer credentials structure.",
+        ))
+    } else {
+        Ok(ConnectionMetadata::UnixPeerCredentials {
+            uid: ucred.uid,
+            gid: ucred.gid,
+            // A PID of 0 means that the information is not available, for example if the peer
+            // is in a different PID namespace.
+            pid: if ucred.pid > 0 { Some(ucred.pid) } else { None },
+        })
+    }
+}
+
+/// Gets the credentials of the process at the other end of the stream, as verified by the kernel
+/// with `getpeereid`. The PID of the peer is not available.
+///
+/// # Errors
+///
+/// Returns the OS error if the `getpeereid` call failed.
+#[cfg(not(target_os = "linux"))]
+pub fn peer_credentials(stream: &UnixStream) -> std::io::Result<ConnectionMetadata> {
+    let mut uid: libc::uid_t = 0;
+    let mut gid: libc::gid_t = 0;
+
+    let ret = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+
+    if ret != 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(ConnectionMetadata::UnixPeerCredentials {
+            uid,
+            gid,
+            pid: None,
+        })
+    }
+}
+
 impl Listen for DomainSocketListener {
     fn set_timeout(&mut self, duration: Duration) {
         self.timeout = duration;
     }
 
-    fn accept(&self) -> Option<Box<dyn ReadWrite + Send>> {
+    fn accept(&self) -> Option<Connection> {
         let stream_result = self.listener.accept();
         match stream_result {
             Ok((stream, _)) => {
@@ -97,7 +167,19 @@ impl Listen for DomainSocketListener {
                     error!("Failed to set stream as blocking ({})", err);
                     None
                 } else {
-                    Some(Box::from(stream))
+                    // Failing to get the peer credentials only matters for authenticators
+                    // relying on them, the connection is still handed out.
+                    let metadata = match peer_credentials(&stream) {
+                        Ok(metadata) => Some(metadata),
+                        Err(err) => {
+                            error!("Failed to get the peer credentials ({})", err);
+                            None
+                        }
+                    };
+                    Some(Connection {
+                        stream: Box::from(stream),
+                        metadata,
+                    })
                 }
             }
             Err(err) => {
@@ -132,3 +214,30 @@ impl DomainSocketListenerBuilder {
         DomainSocketListener::new(timeout)
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::peer_credentials;
+    use super::ConnectionMetadata;
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn peer_credentials_of_own_process() {
+        let (sock_a, _sock_b) = UnixStream::pair().unwrap();
+
+        let metadata = peer_credentials(&sock_a).expect("Failed to get peer credentials");
+
+        assert_eq!(
+            metadata,
+            ConnectionMetadata::UnixPeerCredentials {
+                uid: unsafe { libc::getuid() },
+                gid: unsafe { libc::getgid() },
+                pid: if cfg!(target_os = "linux") {
+                    Some(std::process::id() as i32)
+                } else {
+                    None
+                },
+            }
+        );
+    }
+}
@@ -12,14 +12,14 @@
 // WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use crate::authenticators::Authenticate;
+use crate::authenticators::{Authenticate, NoAuthPolicy};
 use crate::back::dispatcher::Dispatcher;
-use log::{error, info};
+use crate::front::listener::Connection;
+use log::{error, info, warn};
 use parsec_interface::requests::AuthType;
-use parsec_interface::requests::ResponseStatus;
+use parsec_interface::requests::{ProviderID, ResponseStatus};
 use parsec_interface::requests::{Request, Response};
 use std::collections::HashMap;
-use std::io::{Read, Write};
 
 /// Service component that serializes requests and deserializes responses
 /// from/to the stream provided by the listener.
@@ -29,38 +29,51 @@ pub struct FrontEndHandler {
     dispatcher: Dispatcher,
     // Send and Sync are required for Arc<FrontEndHandler> to be Send.
     authenticators: HashMap<AuthType, Box<dyn Authenticate + Send + Sync>>,
+    no_auth_policy: NoAuthPolicy,
 }
 
 impl FrontEndHandler {
     /// Handle new connections on the underlying IPC mechanism.
     ///
     /// Unmarshalls a request from the stream, passes it to the dispatcher and marshalls
-    /// the response back onto the stream.
+    /// the response back onto the stream. The connection metadata is given to the authenticator.
     ///
     /// If an error occurs during (un)marshalling, no operation will be performed and the
     /// method will return.
-    pub fn handle_request<T: Read + Write>(&self, mut stream: T) {
+    pub fn handle_request(&self, mut connection: Connection) {
         // Read bytes from stream
         // De-Serialise bytes into a request
-        let request = match Request::read_from_stream(&mut stream) {
+        let request = match Request::read_from_stream(&mut connection.stream) {
             Ok(request) => request,
             Err(status) => {
                 error!("Failed to read request; status: {}", status);
 
                 let response = Response::from_status(status);
-                if let Err(status) = response.write_to_stream(&mut stream) {
+                if let Err(status) = response.write_to_stream(&mut connection.stream) {
                     error!("Failed to write response; status: {}", status);
                 }
                 return;
             }
         };
-        // Check if the request was sent without authentication
+        // Check if the request was sent without authentication and apply the policy for those
         let response = if AuthType::NoAuth == request.header.auth_type {
-            self.dispatcher.dispatch_request(request, None)
+            match self.no_auth_policy {
+                NoAuthPolicy::Allow => self.dispatcher.dispatch_request(request, None),
+                NoAuthPolicy::CoreOnly if request.header.provider == ProviderID::CoreProvider => {
+                    self.dispatcher.dispatch_request(request, None)
+                }
+                _ => {
+                    warn!(
+                        "Unauthenticated request rejected by the {:?} policy.",
+                        self.no_auth_policy
+                    );
+                    Response::from_request_header(request.header, ResponseStatus::NotAuthenticated)
+                }
+            }
         // Otherwise find an authenticator that is capable to authenticate the request
         } else if let Some(authenticator) = self.authenticators.get(&request.header.auth_type) {
             // Authenticate the request
-            match authenticator.authenticate(&request.auth) {
+            match authenticator.authenticate(&request.auth, connection.metadata) {
                 // Send the request to the dispatcher
                 // Get a response back
                 Ok(app_name) => self.dispatcher.dispatch_request(request, Some(app_name)),
@@ -75,7 +88,7 @@ impl FrontEndHandler {
 
         // Serialise the responso into bytes
         // Write bytes to stream
-        match response.write_to_stream(&mut stream) {
+        match response.write_to_stream(&mut connection.stream) {
             Ok(_) => info!("Request handled successfully"),
             Err(err) => error!("Failed to send response; error: {}", err),
         }
@@ -86,6 +99,7 @@ impl FrontEndHandler {
 pub struct FrontEndHandlerBuilder {
     dispatcher: Option<Dispatcher>,
     authenticators: Option<HashMap<AuthType, Box<dyn Authenticate + Send + Sync>>>,
+    no_auth_policy: Option<NoAuthPolicy>,
 }
 
 impl FrontEndHandlerBuilder {
@@ -93,6 +107,7 @@ impl FrontEndHandlerBuilder {
         FrontEndHandlerBuilder {
             dispatcher: None,
             authenticators: None,
+            no_auth_policy: None,
         }
     }
 
@@ -120,10 +135,16 @@ impl FrontEndHandlerBuilder {
         self
     }
 
+    pub fn with_no_auth_policy(mut self, no_auth_policy: NoAuthPolicy) -> Self {
+        self.no_auth_policy = Some(no_auth_policy);
+        self
+    }
+
     pub fn build(self) -> FrontEndHandler {
         FrontEndHandler {
             dispatcher: self.dispatcher.expect("Dispatcher missing"),
             authenticators: self.authenticators.expect("Authenticators missing"),
+            no_auth_policy: self.no_auth_policy.unwrap_or_default(),
         }
     }
 }
@@ -21,6 +21,25 @@ pub trait ReadWrite: std::io::Read + std::io::Write {}
 // Automatically implements ReadWrite for all types that implement Read and Write.
 impl<T: std::io::Read + std::io::Write> ReadWrite for T {}
 
+/// Specifies metadata associated with a connection, if any.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConnectionMetadata {
+    /// Credentials of the peer process of a Unix domain socket, as reported by the kernel.
+    UnixPeerCredentials {
+        uid: u32,
+        gid: u32,
+        pid: Option<i32>,
+    },
+}
+
+/// Represents a connection to a single client. Contains the stream used for communication with
+/// the client and the metadata associated with the connection, if the listener could get any.
+pub struct Connection {
+    // `Send` is needed because the connection is moved to a thread.
+    pub stream: Box<dyn ReadWrite + Send>,
+    pub metadata: Option<ConnectionMetadata>,
+}
+
 #[derive(Deserialize, Debug)]
 pub enum ListenerType {
     DomainSocket,
@@ -36,17 +55,16 @@ pub trait Listen {
     /// Set the timeout on read and write calls on any stream returned by this listener.
     fn set_timeout(&mut self, duration: Duration);
 
-    /// Non-blocking call that gets the next client connection and returns a stream
-    /// (a Read and Write trait object). Requests are read from the stream and responses are written
-    /// to it. Streams returned by this method should have a timeout period as set by the
-    /// `set_timeout` method.
+    /// Non-blocking call that gets the next client connection and returns it as a `Connection`,
+    /// holding a stream (a Read and Write trait object) and the metadata of the connection.
+    /// Requests are read from the stream and responses are written to it. Streams returned by
+    /// this method should have a timeout period as set by the `set_timeout` method.
     /// If no connections are present, return `None`.
     /// If there are any errors in establishing the connection other than the missing
     /// initialization, the implementation should log them and return `None`.
-    /// `Send` is needed because the stream is moved to a thread.
     ///
     /// # Panics
     ///
     /// If the listener has not been initialised before, with the `init` method.
-    fn accept(&self) -> Option<Box<dyn ReadWrite + Send>>;
+    fn accept(&self) -> Option<Connection>;
 }
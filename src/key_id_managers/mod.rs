@@ -17,9 +17,12 @@
 //! This module declares a `ManageKeyIDs` trait to help providers to store in a persistent manner
 //! the mapping between the name and the IDs of the keys they manage. Different implementors might
 //! store this mapping using different means but it has to be persistent.
+//! The same store keeps the grants given by key owners to other applications, allowing them to use
+//! a key for some usages only.
 
 use crate::authenticators::ApplicationName;
-use parsec_interface::requests::ProviderID;
+use log::{error, info};
+use parsec_interface::requests::{ProviderID, ResponseStatus, Result as ParsecResult};
 use serde::Deserialize;
 use std::fmt;
 
@@ -70,6 +73,71 @@ impl KeyTriple {
     pub fn belongs_to_provider(&self, provider_id: ProviderID) -> bool {
         self.provider_id == provider_id
     }
+
+    /// Returns the name of the application this triple belongs to.
+    pub fn app_name(&self) -> &ApplicationName {
+        &self.app_name
+    }
+
+    /// Returns the ID of the provider this triple belongs to.
+    pub fn provider_id(&self) -> ProviderID {
+        self.provider_id
+    }
+
+    /// Returns the name of the key.
+    pub fn key_name(&self) -> &str {
+        &self.key_name
+    }
+}
+
+/// Usage of a key by an application, checked against grants when the application is not the
+/// owner of the key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyUsage {
+    Sign,
+    Verify,
+    Export,
+}
+
+/// Access given by the owner of a key to another application. The grantee refers to the key with
+/// the same key name and provider as the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyGrant {
+    owner: ApplicationName,
+    permit_sign: bool,
+    permit_verify: bool,
+    permit_export: bool,
+}
+
+impl KeyGrant {
+    /// Creates a new grant from the owner of the key and the usages permitted.
+    pub fn new(
+        owner: ApplicationName,
+        permit_sign: bool,
+        permit_verify: bool,
+        permit_export: bool,
+    ) -> KeyGrant {
+        KeyGrant {
+            owner,
+            permit_sign,
+            permit_verify,
+            permit_export,
+        }
+    }
+
+    /// Returns the name of the application owning the key.
+    pub fn owner(&self) -> &ApplicationName {
+        &self.owner
+    }
+
+    /// Checks if the grant permits the usage.
+    pub fn permits(&self, usage: KeyUsage) -> bool {
+        match usage {
+            KeyUsage::Sign => self.permit_sign,
+            KeyUsage::Verify => self.permit_verify,
+            KeyUsage::Export => self.permit_export,
+        }
+    }
 }
 
 pub trait ManageKeyIDs {
@@ -111,4 +179,213 @@ pub trait ManageKeyIDs {
     ///
     /// Returns an error as a String if there was a problem accessing the Key ID Manager.
     fn exists(&self, key_triple: &KeyTriple) -> Result<bool, String>;
+
+    /// Returns a reference to the grant given to the application of this key triple or `None` if
+    /// it does not exist. The key triple is the one of the grantee.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error as a String if there was a problem accessing the Key ID Manager.
+    fn get_grant(&self, key_triple: &KeyTriple) -> Result<Option<&KeyGrant>, String>;
+
+    /// Returns a Vec of reference to the key triples of the grantees of the key owned under this
+    /// key triple.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error as a String if there was a problem accessing the Key ID Manager.
+    fn get_grantees(&self, key_triple: &KeyTriple) -> Result<Vec<&KeyTriple>, String>;
+
+    /// Inserts a new grant for the grantee key triple. If a grant already exists, overwrite it
+    /// and returns the old one. Otherwise returns `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error as a String if there was a problem accessing the Key ID Manager.
+    fn insert_grant(
+        &mut self,
+        key_triple: KeyTriple,
+        grant: KeyGrant,
+    ) -> Result<Option<KeyGrant>, String>;
+
+    /// Removes the grant of the grantee key triple and returns it. Does nothing and returns
+    /// `None` if the grant does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error as a String if there was a problem accessing the Key ID Manager.
+    fn remove_grant(&mut self, key_triple: &KeyTriple) -> Result<Option<KeyGrant>, String>;
+}
+
+fn key_id_manager_error(string: String) -> ResponseStatus {
+    error!("Key ID Manager error: {}", string);
+    ResponseStatus::KeyIDManagerError
+}
+
+/// Returns the key triple under which the key referred to by the application is stored. It is the
+/// key triple given if the application owns the key, or the one of the owner if the application
+/// was granted the usage of the key.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::KeyDoesNotExist` if the key neither exists nor was granted and
+/// `ResponseStatus::PsaErrorNotPermitted` if the grant does not permit this usage.
+pub fn resolve_key_triple(
+    key_triple: &KeyTriple,
+    usage: KeyUsage,
+    store_handle: &dyn ManageKeyIDs,
+) -> ParsecResult<KeyTriple> {
+    if store_handle
+        .exists(key_triple)
+        .map_err(key_id_manager_error)?
+    {
+        return Ok(key_triple.clone());
+    }
+
+    match store_handle
+        .get_grant(key_triple)
+        .map_err(key_id_manager_error)?
+    {
+        Some(grant) if grant.permits(usage) => Ok(KeyTriple::new(
+            grant.owner().clone(),
+            key_triple.provider_id,
+            key_triple.key_name.clone(),
+        )),
+        Some(_) => {
+            error!(
+                "The grant of key {} does not permit {:?}.",
+                key_triple, usage
+            );
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        }
+        None => Err(ResponseStatus::KeyDoesNotExist),
+    }
+}
+
+/// Checks if the key name is already used by the application, either for one of its own keys or
+/// for a key it was granted.
+pub fn key_name_in_use(
+    key_triple: &KeyTriple,
+    store_handle: &dyn ManageKeyIDs,
+) -> ParsecResult<bool> {
+    Ok(store_handle
+        .exists(key_triple)
+        .map_err(key_id_manager_error)?
+        || store_handle
+            .get_grant(key_triple)
+            .map_err(key_id_manager_error)?
+            .is_some())
+}
+
+/// Grants the usages to the grantee on the key owned under the key triple.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::KeyDoesNotExist` if the owner does not have this key and
+/// `ResponseStatus::KeyAlreadyExists` if the grantee already uses this key name for one of its own
+/// keys.
+pub fn grant_key(
+    owner_triple: &KeyTriple,
+    grantee: ApplicationName,
+    permit_sign: bool,
+    permit_verify: bool,
+    permit_export: bool,
+    store_handle: &mut dyn ManageKeyIDs,
+) -> ParsecResult<()> {
+    if !store_handle
+        .exists(owner_triple)
+        .map_err(key_id_manager_error)?
+    {
+        return Err(ResponseStatus::KeyDoesNotExist);
+    }
+    let grantee_triple = KeyTriple::new(
+        grantee,
+        owner_triple.provider_id,
+        owner_triple.key_name.clone(),
+    );
+    if store_handle
+        .exists(&grantee_triple)
+        .map_err(key_id_manager_error)?
+    {
+        return Err(ResponseStatus::KeyAlreadyExists);
+    }
+    if let Some(grant) = store_handle
+        .get_grant(&grantee_triple)
+        .map_err(key_id_manager_error)?
+    {
+        if grant.owner() != owner_triple.app_name() {
+            // Another application already shares a key with the same name.
+            return Err(ResponseStatus::KeyAlreadyExists);
+        }
+    }
+
+    info!(
+        "Granting key {} to \"{}\".",
+        owner_triple, grantee_triple.app_name
+    );
+    let grant = KeyGrant::new(
+        owner_triple.app_name.clone(),
+        permit_sign,
+        permit_verify,
+        permit_export,
+    );
+    store_handle
+        .insert_grant(grantee_triple, grant)
+        .map_err(key_id_manager_error)?;
+
+    Ok(())
+}
+
+/// Revokes the grant given to the grantee on the key owned under the key triple.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::KeyDoesNotExist` if no such grant exists.
+pub fn revoke_key(
+    owner_triple: &KeyTriple,
+    grantee: ApplicationName,
+    store_handle: &mut dyn ManageKeyIDs,
+) -> ParsecResult<()> {
+    let grantee_triple = KeyTriple::new(
+        grantee,
+        owner_triple.provider_id,
+        owner_triple.key_name.clone(),
+    );
+    match store_handle
+        .get_grant(&grantee_triple)
+        .map_err(key_id_manager_error)?
+    {
+        Some(grant) if grant.owner() == owner_triple.app_name() => {
+            info!(
+                "Revoking key {} from \"{}\".",
+                owner_triple, grantee_triple.app_name
+            );
+            store_handle
+                .remove_grant(&grantee_triple)
+                .map_err(key_id_manager_error)?;
+            Ok(())
+        }
+        _ => Err(ResponseStatus::KeyDoesNotExist),
+    }
+}
+
+/// Removes all the grants given on the key owned under the key triple. Used when the key is
+/// destroyed.
+pub fn remove_grants(
+    owner_triple: &KeyTriple,
+    store_handle: &mut dyn ManageKeyIDs,
+) -> ParsecResult<()> {
+    let grantees: Vec<KeyTriple> = store_handle
+        .get_grantees(owner_triple)
+        .map_err(key_id_manager_error)?
+        .into_iter()
+        .cloned()
+        .collect();
+    for grantee_triple in grantees.iter() {
+        store_handle
+            .remove_grant(grantee_triple)
+            .map_err(key_id_manager_error)?;
+    }
+
+    Ok(())
 }
@@ -24,7 +24,8 @@
 //! example, for operating systems having a limit of 255 characters for filenames (Unix systems),
 //! names will be limited to 188 bytes of UTF-8 characters.
 //! For security reasons, only the PARSEC service should have the ability to modify these files.
-use super::{KeyTriple, ManageKeyIDs};
+//! Key grants are stored the same way, under a separate directory.
+use super::{KeyGrant, KeyTriple, ManageKeyIDs};
 use crate::authenticators::ApplicationName;
 use log::{error, info};
 use parsec_interface::requests::ProviderID;
@@ -37,10 +38,20 @@ use std::io::{Error, ErrorKind, Read, Write};
 use std::path::PathBuf;
 
 pub const DEFAULT_MAPPINGS_PATH: &str = "./mappings";
+/// Name of the directory, inside the mappings directory, where the grants are stored. It can not
+/// collide with an application name directory as a dot is not part of the base64 alphabet used.
+const GRANTS_DIR_NAME: &str = ".grants";
+
+// Flags of the first byte of a grant file.
+const GRANT_PERMIT_SIGN: u8 = 0x1;
+const GRANT_PERMIT_VERIFY: u8 = 0x2;
+const GRANT_PERMIT_EXPORT: u8 = 0x4;
 
 pub struct OnDiskKeyIDManager {
     /// Internal mapping, used for non-modifying operations.
     key_store: HashMap<KeyTriple, Vec<u8>>,
+    /// Internal grants, indexed by the key triple of the grantee.
+    grants: HashMap<KeyTriple, KeyGrant>,
     /// Folder where all the key triple to key ID mappings are saved. This folder will be created
     /// if it does already exist.
     mappings_dir_path: PathBuf,
@@ -134,6 +145,91 @@ fn os_str_to_provider_id(os_str: &OsStr) -> std::io::Result<ProviderID> {
     }
 }
 
+/// Serializes a grant to the content of its file: one byte of usage flags followed by the UTF-8
+/// name of the owner.
+fn grant_to_bytes(grant: &KeyGrant) -> Vec<u8> {
+    let mut flags = 0;
+    if grant.permit_sign {
+        flags |= GRANT_PERMIT_SIGN;
+    }
+    if grant.permit_verify {
+        flags |= GRANT_PERMIT_VERIFY;
+    }
+    if grant.permit_export {
+        flags |= GRANT_PERMIT_EXPORT;
+    }
+    let mut bytes = vec![flags];
+    bytes.extend_from_slice(grant.owner.get_name().as_bytes());
+
+    bytes
+}
+
+/// Deserializes a grant from the content of its file.
+///
+/// # Errors
+///
+/// Returns an error as a string if the file is empty or the owner name is not valid UTF-8.
+fn bytes_to_grant(bytes: &[u8]) -> Result<KeyGrant, String> {
+    match bytes.split_first() {
+        Some((flags, owner)) => match String::from_utf8(owner.to_vec()) {
+            Ok(owner) => Ok(KeyGrant::new(
+                ApplicationName::new(owner),
+                flags & GRANT_PERMIT_SIGN != 0,
+                flags & GRANT_PERMIT_VERIFY != 0,
+                flags & GRANT_PERMIT_EXPORT != 0,
+            )),
+            Err(error) => Err(error.to_string()),
+        },
+        None => Err(String::from("Grant file is empty.")),
+    }
+}
+
+/// Reads all the files of a mappings-like directory tree and returns the key triples with the
+/// content of the files. The directory named `GRANTS_DIR_NAME` is skipped.
+///
+/// # Errors
+///
+/// Returns an std::io error if the function failed reading the files.
+fn read_triple_files(dir_path: &PathBuf) -> std::io::Result<Vec<(KeyTriple, Vec<u8>)>> {
+    let mut triple_files = Vec::new();
+
+    for app_name_dir_path in list_dirs(dir_path)?.iter() {
+        if app_name_dir_path.file_name() == Some(OsStr::new(GRANTS_DIR_NAME)) {
+            continue;
+        }
+        for provider_dir_path in list_dirs(&app_name_dir_path)?.iter() {
+            for key_name_file_path in list_files(&provider_dir_path)?.iter() {
+                info!("Found mapping file: {:?}.", key_name_file_path);
+                let mut content = Vec::new();
+                let mut file = File::open(&key_name_file_path)?;
+                file.read_to_end(&mut content)?;
+                match base64_data_triple_to_key_triple(
+                    os_str_to_u8_ref(app_name_dir_path.file_name().expect(
+                        "The application name directory path should contain a final component.",
+                    ))?,
+                    os_str_to_provider_id(
+                        provider_dir_path.file_name().expect(
+                            "The provider directory path should contain a final component.",
+                        ),
+                    )?,
+                    os_str_to_u8_ref(
+                        key_name_file_path.file_name().expect(
+                            "The key name directory path should contain a final component.",
+                        ),
+                    )?,
+                ) {
+                    Ok(key_triple) => triple_files.push((key_triple, content)),
+                    Err(string) => {
+                        error!("Failed to convert the mapping path found to an UTF-8 string (error: {}).", string);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(triple_files)
+}
+
 /// Lists all the directory paths in the given directory path.
 fn list_dirs(path: &PathBuf) -> std::io::Result<Vec<PathBuf>> {
     // read_dir returning an iterator over Result<DirEntry>, there is first a conversion to a path
@@ -186,34 +282,27 @@ impl OnDiskKeyIDManager {
     /// Returns an std::io error if the function failed reading the mapping files.
     fn new(mappings_dir_path: PathBuf) -> std::io::Result<OnDiskKeyIDManager> {
         let mut key_store = HashMap::new();
+        let mut grants = HashMap::new();
 
         // Will ignore if the mappings directory already exists.
         fs::create_dir_all(&mappings_dir_path)?;
 
-        for app_name_dir_path in list_dirs(&mappings_dir_path)?.iter() {
-            for provider_dir_path in list_dirs(&app_name_dir_path)?.iter() {
-                for key_name_file_path in list_files(&provider_dir_path)?.iter() {
-                    info!("Found mapping file: {:?}.", key_name_file_path);
-                    let mut key_id = Vec::new();
-                    let mut key_id_file = File::open(&key_name_file_path)?;
-                    key_id_file.read_to_end(&mut key_id)?;
-                    match base64_data_triple_to_key_triple(
-                        os_str_to_u8_ref(app_name_dir_path.file_name().expect(
-                            "The application name directory path should contain a final component.",
-                        ))?,
-                        os_str_to_provider_id(provider_dir_path.file_name().expect(
-                            "The provider directory path should contain a final component.",
-                        ))?,
-                        os_str_to_u8_ref(key_name_file_path.file_name().expect(
-                            "The key name directory path should contain a final component.",
-                        ))?,
-                    ) {
-                        Ok(key_triple) => {
-                            key_store.insert(key_triple, key_id);
-                        }
-                        Err(string) => {
-                            error!("Failed to convert the mapping path found to an UTF-8 string (error: {}).", string);
-                        }
+        for (key_triple, key_id) in read_triple_files(&mappings_dir_path)? {
+            key_store.insert(key_triple, key_id);
+        }
+
+        let grants_dir_path = mappings_dir_path.join(GRANTS_DIR_NAME);
+        if grants_dir_path.is_dir() {
+            for (key_triple, grant) in read_triple_files(&grants_dir_path)? {
+                match bytes_to_grant(&grant) {
+                    Ok(grant) => {
+                        grants.insert(key_triple, grant);
+                    }
+                    Err(string) => {
+                        error!(
+                            "Failed to read the grant of {} (error: {}).",
+                            key_triple, string
+                        );
                     }
                 }
             }
@@ -221,6 +310,7 @@ impl OnDiskKeyIDManager {
 
         Ok(OnDiskKeyIDManager {
             key_store,
+            grants,
             mappings_dir_path,
         })
     }
@@ -229,35 +319,64 @@ impl OnDiskKeyIDManager {
     /// The filename will be `mappings/[APP_NAME]/[PROVIDER_NAME]/[KEY_NAME]` under the same path as the
     /// on-disk manager. It will contain the Key ID data.
     fn save_mapping(&self, key_triple: &KeyTriple, key_id: &[u8]) -> std::io::Result<()> {
-        // Create the directories with base64 names.
-        let (app_name, prov, key_name) = key_triple_to_base64_filenames(key_triple);
-        let provider_dir_path = self.mappings_dir_path.join(app_name).join(prov);
-        let key_name_file_path = provider_dir_path.join(key_name);
-        // Will ignore if they already exist.
-        fs::create_dir_all(&provider_dir_path)?;
-
-        if key_name_file_path.exists() {
-            fs::remove_file(&key_name_file_path)?;
-        }
-
-        let mut mapping_file = fs::File::create(&key_name_file_path)?;
-        mapping_file.write_all(key_id)
+        save_triple_file(&self.mappings_dir_path, key_triple, key_id)
     }
 
     /// Removes the mapping file.
     /// Will do nothing if the mapping file does not exist.
     fn delete_mapping(&self, key_triple: &KeyTriple) -> std::io::Result<()> {
-        let (app_name, prov, key_name) = key_triple_to_base64_filenames(key_triple);
-        let key_name_file_path = self
-            .mappings_dir_path
-            .join(app_name)
-            .join(prov)
-            .join(key_name);
-        if key_name_file_path.exists() {
-            fs::remove_file(key_name_file_path)
-        } else {
-            Ok(())
-        }
+        delete_triple_file(&self.mappings_dir_path, key_triple)
+    }
+
+    /// Saves the grant in its own file.
+    /// The filename will be `mappings/.grants/[GRANTEE_NAME]/[PROVIDER_NAME]/[KEY_NAME]`. It will
+    /// contain the usage flags and the name of the owner.
+    fn save_grant(&self, key_triple: &KeyTriple, grant: &KeyGrant) -> std::io::Result<()> {
+        save_triple_file(
+            &self.mappings_dir_path.join(GRANTS_DIR_NAME),
+            key_triple,
+            &grant_to_bytes(grant),
+        )
+    }
+
+    /// Removes the grant file.
+    /// Will do nothing if the grant file does not exist.
+    fn delete_grant(&self, key_triple: &KeyTriple) -> std::io::Result<()> {
+        delete_triple_file(&self.mappings_dir_path.join(GRANTS_DIR_NAME), key_triple)
+    }
+}
+
+/// Writes the content in the file of the key triple under the given directory, replacing it if it
+/// exists.
+fn save_triple_file(
+    dir_path: &PathBuf,
+    key_triple: &KeyTriple,
+    content: &[u8],
+) -> std::io::Result<()> {
+    // Create the directories with base64 names.
+    let (app_name, prov, key_name) = key_triple_to_base64_filenames(key_triple);
+    let provider_dir_path = dir_path.join(app_name).join(prov);
+    let key_name_file_path = provider_dir_path.join(key_name);
+    // Will ignore if they already exist.
+    fs::create_dir_all(&provider_dir_path)?;
+
+    if key_name_file_path.exists() {
+        fs::remove_file(&key_name_file_path)?;
+    }
+
+    let mut file = fs::File::create(&key_name_file_path)?;
+    file.write_all(content)
+}
+
+/// Removes the file of the key triple under the given directory.
+/// Will do nothing if the file does not exist.
+fn delete_triple_file(dir_path: &PathBuf, key_triple: &KeyTriple) -> std::io::Result<()> {
+    let (app_name, prov, key_name) = key_triple_to_base64_filenames(key_triple);
+    let key_name_file_path = dir_path.join(app_name).join(prov).join(key_name);
+    if key_name_file_path.exists() {
+        fs::remove_file(key_name_file_path)
+    } else {
+        Ok(())
     }
 }
 
@@ -305,6 +424,43 @@ impl ManageKeyIDs for OnDiskKeyIDManager {
     fn exists(&self, key_triple: &KeyTriple) -> Result<bool, String> {
         Ok(self.key_store.contains_key(key_triple))
     }
+
+    fn get_grant(&self, key_triple: &KeyTriple) -> Result<Option<&KeyGrant>, String> {
+        Ok(self.grants.get(key_triple))
+    }
+
+    fn get_grantees(&self, key_triple: &KeyTriple) -> Result<Vec<&KeyTriple>, String> {
+        Ok(self
+            .grants
+            .iter()
+            .filter(|(grantee_triple, grant)| {
+                grant.owner == key_triple.app_name
+                    && grantee_triple.provider_id == key_triple.provider_id
+                    && grantee_triple.key_name == key_triple.key_name
+            })
+            .map(|(grantee_triple, _)| grantee_triple)
+            .collect())
+    }
+
+    fn insert_grant(
+        &mut self,
+        key_triple: KeyTriple,
+        grant: KeyGrant,
+    ) -> Result<Option<KeyGrant>, String> {
+        if let Err(err) = self.save_grant(&key_triple, &grant) {
+            Err(err.to_string())
+        } else {
+            Ok(self.grants.insert(key_triple, grant))
+        }
+    }
+
+    fn remove_grant(&mut self, key_triple: &KeyTriple) -> Result<Option<KeyGrant>, String> {
+        if let Err(err) = self.delete_grant(key_triple) {
+            Err(err.to_string())
+        } else {
+            Ok(self.grants.remove(key_triple))
+        }
+    }
 }
 
 #[derive(Default)]
@@ -336,7 +492,7 @@ impl OnDiskKeyIDManagerBuilder {
 
 #[cfg(test)]
 mod test {
-    use super::super::{KeyTriple, ManageKeyIDs};
+    use super::super::{KeyGrant, KeyTriple, ManageKeyIDs};
     use super::OnDiskKeyIDManager;
     use crate::authenticators::ApplicationName;
     use parsec_interface::requests::ProviderID;
@@ -523,6 +679,78 @@ mod test {
         fs::remove_dir_all(path).unwrap();
     }
 
+    #[test]
+    fn insert_get_remove_grant() {
+        let path = PathBuf::from(env!("OUT_DIR").to_owned() + "/insert_get_remove_grant_mappings");
+        let mut manager = OnDiskKeyIDManager::new(path.clone()).unwrap();
+
+        let owner_triple = new_key_triple("insert_get_remove_grant".to_string());
+        let grantee_triple = KeyTriple::new(
+            ApplicationName::new("Grantee Application 🤝".to_string()),
+            ProviderID::MbedProvider,
+            "insert_get_remove_grant".to_string(),
+        );
+        let grant = KeyGrant::new(owner_triple.app_name().clone(), false, true, false);
+
+        manager
+            .insert(owner_triple.clone(), vec![0x11, 0x22, 0x33])
+            .unwrap();
+        assert!(manager.get_grant(&grantee_triple).unwrap().is_none());
+        assert!(manager
+            .insert_grant(grantee_triple.clone(), grant.clone())
+            .unwrap()
+            .is_none());
+        assert_eq!(manager.get_grant(&grantee_triple).unwrap(), Some(&grant));
+        assert_eq!(
+            manager.get_grantees(&owner_triple).unwrap(),
+            vec![&grantee_triple]
+        );
+        // A grant is not a key of the grantee.
+        assert!(!manager.exists(&grantee_triple).unwrap());
+
+        assert_eq!(manager.remove_grant(&grantee_triple).unwrap(), Some(grant));
+        assert!(manager.get_grantees(&owner_triple).unwrap().is_empty());
+        assert!(manager.remove(&owner_triple).unwrap().is_some());
+        fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn create_and_load_grants() {
+        let path = PathBuf::from(env!("OUT_DIR").to_owned() + "/create_and_load_grants_mappings");
+
+        let owner_triple = new_key_triple("😀 Shared Key 😀".to_string());
+        let grantee_triple = KeyTriple::new(
+            ApplicationName::new("😇 Grantee 😇".to_string()),
+            ProviderID::MbedProvider,
+            "😀 Shared Key 😀".to_string(),
+        );
+        let key_id = vec![0x11, 0x22, 0x33];
+        let grant = KeyGrant::new(owner_triple.app_name().clone(), true, false, true);
+        {
+            let mut manager = OnDiskKeyIDManager::new(path.clone()).unwrap();
+
+            manager
+                .insert(owner_triple.clone(), key_id.clone())
+                .unwrap();
+            manager
+                .insert_grant(grantee_triple.clone(), grant.clone())
+                .unwrap();
+        }
+        // The grants directory should not be read as a mapping.
+        {
+            let mut manager = OnDiskKeyIDManager::new(path.clone()).unwrap();
+
+            assert_eq!(
+                manager.get_all(ProviderID::MbedProvider).unwrap(),
+                vec![&owner_triple]
+            );
+            assert_eq!(manager.remove_grant(&grantee_triple).unwrap(), Some(grant));
+            assert_eq!(manager.remove(&owner_triple).unwrap().unwrap(), key_id);
+        }
+
+        fs::remove_dir_all(path).unwrap();
+    }
+
     fn new_key_triple(key_name: String) -> KeyTriple {
         KeyTriple::new(
             ApplicationName::new("Testing Application 😎".to_string()),
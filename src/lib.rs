@@ -13,6 +13,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 pub mod authenticators;
+pub mod authorizers;
 pub mod back;
 pub mod front;
 pub mod key_id_managers;
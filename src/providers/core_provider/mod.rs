@@ -13,11 +13,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use super::Provide;
+use crate::authenticators::ApplicationName;
+use log::warn;
 use parsec_interface::operations::ProviderInfo;
+use parsec_interface::operations::{OpGenerateRandom, ResultGenerateRandom};
+use parsec_interface::operations::{OpListKeys, ResultListKeys};
 use parsec_interface::operations::{OpListOpcodes, ResultListOpcodes};
 use parsec_interface::operations::{OpListProviders, ResultListProviders};
 use parsec_interface::operations::{OpPing, ResultPing};
-use parsec_interface::requests::{Opcode, ProviderID, Result};
+use parsec_interface::requests::{Opcode, ProviderID, ResponseStatus, Result};
+use std::collections::HashSet;
+use std::sync::Arc;
 use uuid::Uuid;
 
 const SUPPORTED_OPCODES: [Opcode; 3] = [Opcode::ListProviders, Opcode::ListOpcodes, Opcode::Ping];
@@ -26,13 +32,45 @@ pub struct CoreProvider {
     version_min: u8,
     version_maj: u8,
     providers: Vec<ProviderInfo>,
+    // Provider that GenerateRandom operations are forwarded to, if any.
+    random_provider: Option<Arc<dyn Provide + Send + Sync>>,
+    // Providers holding keys, all listed by ListKeys operations.
+    key_providers: Vec<Arc<dyn Provide + Send + Sync>>,
 }
 
 impl Provide for CoreProvider {
     fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes> {
-        Ok(ResultListOpcodes {
-            opcodes: SUPPORTED_OPCODES.iter().copied().collect(),
-        })
+        let mut opcodes: HashSet<Opcode> = SUPPORTED_OPCODES.iter().copied().collect();
+        if self.random_provider.is_some() {
+            opcodes.insert(Opcode::GenerateRandom);
+        }
+        if !self.key_providers.is_empty() {
+            opcodes.insert(Opcode::ListKeys);
+        }
+        Ok(ResultListOpcodes { opcodes })
+    }
+
+    fn forwarded_to(&self, opcode: Opcode) -> Option<ProviderID> {
+        match (opcode, &self.random_provider) {
+            (Opcode::GenerateRandom, Some(random_provider)) => Some(random_provider.describe().id),
+            _ => None,
+        }
+    }
+
+    fn list_keys(&self, app_name: ApplicationName, _op: OpListKeys) -> Result<ResultListKeys> {
+        let mut keys = Vec::new();
+        for provider in &self.key_providers {
+            match provider.list_keys(app_name.clone(), OpListKeys {}) {
+                Ok(result) => keys.extend(result.keys),
+                Err(status) => warn!(
+                    "Keys of provider {} can not be listed (error: {}), continuing...",
+                    provider.describe().id,
+                    status
+                ),
+            }
+        }
+
+        Ok(ResultListKeys { keys })
     }
 
     fn list_providers(&self, _op: OpListProviders) -> Result<ResultListProviders> {
@@ -62,6 +100,13 @@ impl Provide for CoreProvider {
 
         Ok(result)
     }
+
+    fn generate_random(&self, op: OpGenerateRandom) -> Result<ResultGenerateRandom> {
+        match &self.random_provider {
+            Some(random_provider) => random_provider.generate_random(op),
+            None => Err(ResponseStatus::UnsupportedOperation),
+        }
+    }
 }
 
 #[derive(Default)]
@@ -69,6 +114,8 @@ pub struct CoreProviderBuilder {
     version_maj: Option<u8>,
     version_min: Option<u8>,
     providers: Option<Vec<ProviderInfo>>,
+    random_provider: Option<Arc<dyn Provide + Send + Sync>>,
+    key_providers: Vec<Arc<dyn Provide + Send + Sync>>,
 }
 
 impl CoreProviderBuilder {
@@ -77,6 +124,8 @@ impl CoreProviderBuilder {
             version_maj: None,
             version_min: None,
             providers: None,
+            random_provider: None,
+            key_providers: Vec::new(),
         }
     }
 
@@ -103,11 +152,25 @@ impl CoreProviderBuilder {
         self
     }
 
+    pub fn with_random_provider(mut self, random_provider: Arc<dyn Provide + Send + Sync>) -> Self {
+        self.random_provider = Some(random_provider);
+
+        self
+    }
+
+    pub fn with_key_provider(mut self, key_provider: Arc<dyn Provide + Send + Sync>) -> Self {
+        self.key_providers.push(key_provider);
+
+        self
+    }
+
     pub fn build(self) -> CoreProvider {
         let mut core_provider = CoreProvider {
             version_maj: self.version_maj.expect("Version Maj missing"),
             version_min: self.version_min.expect("Version Min missing"),
             providers: self.providers.expect("Providers info is missing"),
+            random_provider: self.random_provider,
+            key_providers: self.key_providers,
         };
 
         core_provider.providers.push(core_provider.describe());
@@ -119,6 +182,8 @@ impl CoreProviderBuilder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::KeyInfo;
 
     #[test]
     fn test_ping() {
@@ -126,10 +191,187 @@ mod tests {
             version_min: 8,
             version_maj: 10,
             providers: Vec::new(),
+            random_provider: None,
+            key_providers: Vec::new(),
         };
         let op = OpPing {};
         let result = provider.ping(op).unwrap();
         assert_eq!(result.supp_version_maj, provider.version_maj);
         assert_eq!(result.supp_version_min, provider.version_min);
     }
+
+    struct RandomProvider;
+
+    impl Provide for RandomProvider {
+        fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes> {
+            Ok(ResultListOpcodes {
+                opcodes: vec![Opcode::GenerateRandom].into_iter().collect(),
+            })
+        }
+
+        fn describe(&self) -> ProviderInfo {
+            ProviderInfo {
+                uuid: Uuid::nil(),
+                description: String::new(),
+                vendor: String::new(),
+                version_maj: 0,
+                version_min: 1,
+                version_rev: 0,
+                id: ProviderID::MbedProvider,
+            }
+        }
+
+        fn generate_random(&self, op: OpGenerateRandom) -> Result<ResultGenerateRandom> {
+            Ok(ResultGenerateRandom {
+                random_bytes: vec![0x2a; op.size],
+            })
+        }
+    }
+
+    #[test]
+    fn test_generate_random() {
+        let provider = CoreProvider {
+            version_min: 8,
+            version_maj: 10,
+            providers: Vec::new(),
+            random_provider: None,
+            key_providers: Vec::new(),
+        };
+        assert_eq!(
+            provider
+                .generate_random(OpGenerateRandom { size: 4 })
+                .unwrap_err(),
+            ResponseStatus::UnsupportedOperation
+        );
+        assert!(!provider
+            .list_opcodes(OpListOpcodes {})
+            .unwrap()
+            .opcodes
+            .contains(&Opcode::GenerateRandom));
+        assert_eq!(provider.forwarded_to(Opcode::GenerateRandom), None);
+
+        let provider = CoreProvider {
+            random_provider: Some(Arc::new(RandomProvider {})),
+            ..provider
+        };
+        let result = provider
+            .generate_random(OpGenerateRandom { size: 4 })
+            .unwrap();
+        assert_eq!(result.random_bytes, vec![0x2a; 4]);
+        assert_eq!(
+            provider.forwarded_to(Opcode::GenerateRandom),
+            Some(ProviderID::MbedProvider)
+        );
+        assert_eq!(provider.forwarded_to(Opcode::Ping), None);
+        assert!(provider
+            .list_opcodes(OpListOpcodes {})
+            .unwrap()
+            .opcodes
+            .contains(&Opcode::GenerateRandom));
+    }
+
+    // Provider holding one key for each application, or failing to list its keys.
+    struct KeyProvider {
+        id: ProviderID,
+        fail: bool,
+    }
+
+    impl Provide for KeyProvider {
+        fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes> {
+            Ok(ResultListOpcodes {
+                opcodes: vec![Opcode::ListKeys].into_iter().collect(),
+            })
+        }
+
+        fn describe(&self) -> ProviderInfo {
+            ProviderInfo {
+                uuid: Uuid::nil(),
+                description: String::new(),
+                vendor: String::new(),
+                version_maj: 0,
+                version_min: 1,
+                version_rev: 0,
+                id: self.id,
+            }
+        }
+
+        fn list_keys(&self, app_name: ApplicationName, _op: OpListKeys) -> Result<ResultListKeys> {
+            if self.fail {
+                return Err(ResponseStatus::PsaErrorCommunicationFailure);
+            }
+            Ok(ResultListKeys {
+                keys: vec![KeyInfo {
+                    provider_id: self.id,
+                    name: format!("{} key", app_name),
+                    attributes: KeyAttributes {
+                        key_type: KeyType::RsaKeypair,
+                        ecc_curve: None,
+                        algorithm: Algorithm::sign(
+                            SignAlgorithm::RsaPkcs1v15Sign,
+                            Some(HashAlgorithm::Sha256),
+                        ),
+                        key_size: 2048,
+                        permit_sign: true,
+                        permit_verify: true,
+                        permit_export: true,
+                        permit_derive: false,
+                        permit_encrypt: false,
+                        permit_decrypt: false,
+                    },
+                }],
+            })
+        }
+    }
+
+    #[test]
+    fn test_list_keys() {
+        let provider = CoreProviderBuilder::new()
+            .with_version(8, 10)
+            .with_provider_info(
+                KeyProvider {
+                    id: ProviderID::MbedProvider,
+                    fail: false,
+                }
+                .describe(),
+            )
+            .build();
+        assert!(!provider
+            .list_opcodes(OpListOpcodes {})
+            .unwrap()
+            .opcodes
+            .contains(&Opcode::ListKeys));
+
+        let provider = CoreProviderBuilder::new()
+            .with_version(8, 10)
+            .with_key_provider(Arc::new(KeyProvider {
+                id: ProviderID::Pkcs11Provider,
+                fail: true,
+            }))
+            .with_key_provider(Arc::new(KeyProvider {
+                id: ProviderID::MbedProvider,
+                fail: false,
+            }))
+            .with_provider_info(
+                KeyProvider {
+                    id: ProviderID::MbedProvider,
+                    fail: false,
+                }
+                .describe(),
+            )
+            .build();
+        assert!(provider
+            .list_opcodes(OpListOpcodes {})
+            .unwrap()
+            .opcodes
+            .contains(&Opcode::ListKeys));
+
+        // The keys of the failing provider are skipped.
+        let keys = provider
+            .list_keys(ApplicationName::new(String::from("app")), OpListKeys {})
+            .unwrap()
+            .keys;
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].provider_id, ProviderID::MbedProvider);
+        assert_eq!(keys[0].name, "app key");
+    }
 }
@@ -58,6 +58,7 @@ pub const PSA_KEY_TYPE_AES: psa_key_type_t = 0x4000_0001;
 pub const PSA_KEY_TYPE_DES: psa_key_type_t = 0x4000_0002;
 pub const PSA_KEY_TYPE_CAMELLIA: psa_key_type_t = 0x4000_0003;
 pub const PSA_KEY_TYPE_ARC4: psa_key_type_t = 0x4000_0004;
+pub const PSA_KEY_TYPE_CHACHA20: psa_key_type_t = 0x4000_0005;
 pub const PSA_KEY_TYPE_RSA_PUBLIC_KEY: psa_key_type_t = 0x6001_0000;
 pub const PSA_KEY_TYPE_RSA_KEYPAIR: psa_key_type_t = 0x7001_0000;
 pub const PSA_KEY_TYPE_DSA_PUBLIC_KEY: psa_key_type_t = 0x6002_0000;
@@ -103,10 +104,8 @@ pub const PSA_ALG_CATEGORY_CIPHER: psa_algorithm_t = 0x0400_0000;
 pub const PSA_ALG_CATEGORY_AEAD: psa_algorithm_t = 0x0600_0000;
 pub const PSA_ALG_CATEGORY_SIGN: psa_algorithm_t = 0x1000_0000;
 pub const PSA_ALG_CATEGORY_ASYMMETRIC_ENCRYPTION: psa_algorithm_t = 0x1200_0000;
-pub const PSA_ALG_CATEGORY_KEY_AGREEMENT: psa_algorithm_t = 0x2200_0000;
-pub const PSA_ALG_CATEGORY_KEY_DERIVATION: psa_algorithm_t = 0x3000_0000;
-pub const PSA_ALG_CATEGORY_KEY_SELECTION: psa_algorithm_t = 0x3100_0000;
-pub const PSA_ALG_KEY_SELECTION_FLAG: psa_algorithm_t = 0x0100_0000;
+pub const PSA_ALG_CATEGORY_KEY_DERIVATION: psa_algorithm_t = 0x2000_0000;
+pub const PSA_ALG_CATEGORY_KEY_AGREEMENT: psa_algorithm_t = 0x3000_0000;
 pub const PSA_ALG_HASH_MASK: psa_algorithm_t = 0x0000_00ff;
 pub const PSA_ALG_MD2: psa_algorithm_t = 0x0100_0001;
 pub const PSA_ALG_MD4: psa_algorithm_t = 0x0100_0002;
@@ -142,7 +141,9 @@ pub const PSA_ALG_CBC_NO_PADDING: psa_algorithm_t = 0x0460_0100;
 pub const PSA_ALG_CBC_PKCS7: psa_algorithm_t = 0x0460_0101;
 pub const PSA_ALG_CCM: psa_algorithm_t = 0x0600_1001;
 pub const PSA_ALG_GCM: psa_algorithm_t = 0x0600_1002;
+pub const PSA_ALG_CHACHA20_POLY1305: psa_algorithm_t = 0x0600_1005;
 pub const PSA_ALG_AEAD_TAG_LENGTH_MASK: psa_algorithm_t = 0x0000_3f00;
+pub const PSA_AEAD_TAG_LENGTH_OFFSET: psa_algorithm_t = 8;
 pub const PSA_ALG_RSA_PKCS1V15_SIGN_BASE: psa_algorithm_t = 0x1002_0000;
 pub const PSA_ALG_RSA_PSS_BASE: psa_algorithm_t = 0x1003_0000;
 pub const PSA_ALG_DSA_BASE: psa_algorithm_t = 0x1004_0000;
@@ -152,13 +153,18 @@ pub const PSA_ALG_ECDSA_BASE: psa_algorithm_t = 0x1006_0000;
 pub const PSA_ALG_DETERMINISTIC_ECDSA_BASE: psa_algorithm_t = 0x1007_0000;
 pub const PSA_ALG_RSA_PKCS1V15_CRYPT: psa_algorithm_t = 0x1202_0000;
 pub const PSA_ALG_RSA_OAEP_BASE: psa_algorithm_t = 0x1203_0000;
-pub const PSA_ALG_HKDF_BASE: psa_algorithm_t = 0x3000_0100;
-pub const PSA_ALG_TLS12_PRF_BASE: psa_algorithm_t = 0x3000_0200;
-pub const PSA_ALG_TLS12_PSK_TO_MS_BASE: psa_algorithm_t = 0x3000_0300;
-pub const PSA_ALG_KEY_DERIVATION_MASK: psa_algorithm_t = 0x010f_ffff;
-pub const PSA_ALG_SELECT_RAW: psa_algorithm_t = 0x3100_0001;
-pub const PSA_ALG_FFDH_BASE: psa_algorithm_t = 0x2210_0000;
-pub const PSA_ALG_ECDH_BASE: psa_algorithm_t = 0x2220_0000;
+pub const PSA_ALG_HKDF_BASE: psa_algorithm_t = 0x2000_0100;
+pub const PSA_ALG_TLS12_PRF_BASE: psa_algorithm_t = 0x2000_0200;
+pub const PSA_ALG_TLS12_PSK_TO_MS_BASE: psa_algorithm_t = 0x2000_0300;
+pub const PSA_ALG_KEY_DERIVATION_MASK: psa_algorithm_t = 0x0803_ffff;
+pub const PSA_ALG_KEY_AGREEMENT_MASK: psa_algorithm_t = 0x10fc_0000;
+pub const PSA_ALG_FFDH: psa_algorithm_t = 0x3010_0000;
+pub const PSA_ALG_ECDH: psa_algorithm_t = 0x3020_0000;
+pub const PSA_KEY_DERIVATION_INPUT_SECRET: psa_key_derivation_step_t = 0x0101;
+pub const PSA_KEY_DERIVATION_INPUT_LABEL: psa_key_derivation_step_t = 0x0201;
+pub const PSA_KEY_DERIVATION_INPUT_SALT: psa_key_derivation_step_t = 0x0202;
+pub const PSA_KEY_DERIVATION_INPUT_INFO: psa_key_derivation_step_t = 0x0203;
+pub const PSA_KEY_DERIVATION_INPUT_SEED: psa_key_derivation_step_t = 0x0204;
 pub const PSA_KEY_LIFETIME_VOLATILE: psa_key_lifetime_t = 0x0000_0000;
 pub const PSA_KEY_LIFETIME_PERSISTENT: psa_key_lifetime_t = 0x0000_0001;
 pub const PSA_KEY_USAGE_EXPORT: psa_key_usage_t = 0x0000_0001;
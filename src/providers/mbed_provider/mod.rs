@@ -14,21 +14,39 @@
 // limitations under the License.
 use super::Provide;
 use crate::authenticators::ApplicationName;
-use crate::key_id_managers::{KeyTriple, ManageKeyIDs};
+use crate::key_id_managers::{self, KeyTriple, KeyUsage, ManageKeyIDs};
 use std::collections::HashSet;
 use std::convert::TryInto;
 use std::sync::{Arc, Mutex, RwLock};
 
 use constants::PSA_SUCCESS;
 use log::{error, info, warn};
-use parsec_interface::operations::ProviderInfo;
+use parsec_interface::operations::{KeyInfo, ProviderInfo};
+use parsec_interface::operations::{OpAeadDecrypt, ResultAeadDecrypt};
+use parsec_interface::operations::{OpAeadEncrypt, ResultAeadEncrypt};
 use parsec_interface::operations::{OpAsymSign, ResultAsymSign};
+use parsec_interface::operations::{OpAsymSignMessage, ResultAsymSignMessage};
 use parsec_interface::operations::{OpAsymVerify, ResultAsymVerify};
+use parsec_interface::operations::{OpAsymVerifyMessage, ResultAsymVerifyMessage};
+use parsec_interface::operations::{OpAsymmetricDecrypt, ResultAsymmetricDecrypt};
+use parsec_interface::operations::{OpAsymmetricEncrypt, ResultAsymmetricEncrypt};
 use parsec_interface::operations::{OpCreateKey, ResultCreateKey};
 use parsec_interface::operations::{OpDestroyKey, ResultDestroyKey};
 use parsec_interface::operations::{OpExportPublicKey, ResultExportPublicKey};
+use parsec_interface::operations::{OpGenerateRandom, ResultGenerateRandom};
+use parsec_interface::operations::{OpGrantKey, ResultGrantKey};
+use parsec_interface::operations::{OpHashCompare, ResultHashCompare};
+use parsec_interface::operations::{OpHashCompute, ResultHashCompute};
 use parsec_interface::operations::{OpImportKey, ResultImportKey};
+use parsec_interface::operations::{OpKeyAgreementDeriveKey, ResultKeyAgreementDeriveKey};
+use parsec_interface::operations::{OpKeyDerivationOutputBytes, ResultKeyDerivationOutputBytes};
+use parsec_interface::operations::{OpKeyDerivationOutputKey, ResultKeyDerivationOutputKey};
+use parsec_interface::operations::{OpListKeys, ResultListKeys};
 use parsec_interface::operations::{OpListOpcodes, ResultListOpcodes};
+use parsec_interface::operations::{OpMacCompute, ResultMacCompute};
+use parsec_interface::operations::{OpMacVerify, ResultMacVerify};
+use parsec_interface::operations::{OpRawKeyAgreement, ResultRawKeyAgreement};
+use parsec_interface::operations::{OpRevokeKey, ResultRevokeKey};
 use parsec_interface::requests::{Opcode, ProviderID, ResponseStatus, Result};
 use psa_crypto_binding::psa_key_handle_t as KeyHandle;
 use psa_crypto_binding::psa_key_id_t as KeyId;
@@ -53,7 +71,7 @@ mod utils;
 
 type LocalIdStore = HashSet<KeyId>;
 
-const SUPPORTED_OPCODES: [Opcode; 7] = [
+const SUPPORTED_OPCODES: [Opcode; 25] = [
     Opcode::CreateKey,
     Opcode::DestroyKey,
     Opcode::AsymSign,
@@ -61,8 +79,30 @@ const SUPPORTED_OPCODES: [Opcode; 7] = [
     Opcode::ImportKey,
     Opcode::ExportPublicKey,
     Opcode::ListOpcodes,
+    Opcode::GrantKey,
+    Opcode::RevokeKey,
+    Opcode::ListKeys,
+    Opcode::AsymmetricEncrypt,
+    Opcode::AsymmetricDecrypt,
+    Opcode::AeadEncrypt,
+    Opcode::AeadDecrypt,
+    Opcode::MacCompute,
+    Opcode::MacVerify,
+    Opcode::HashCompute,
+    Opcode::HashCompare,
+    Opcode::AsymSignMessage,
+    Opcode::AsymVerifyMessage,
+    Opcode::RawKeyAgreement,
+    Opcode::KeyAgreementDeriveKey,
+    Opcode::KeyDerivationOutputKey,
+    Opcode::KeyDerivationOutputBytes,
+    Opcode::GenerateRandom,
 ];
 
+// Maximum number of bytes a key derivation can output in one request: the most HKDF can output,
+// with SHA-512.
+const MAX_KEY_DERIVATION_OUTPUT_SIZE: usize = 255 * 64;
+
 pub struct MbedProvider {
     // When calling write on a reference of key_id_store, a type
     // std::sync::RwLockWriteGuard<dyn ManageKeyIDs + Send + Sync> is returned. We need to use the
@@ -150,16 +190,6 @@ fn remove_key_id(
     }
 }
 
-fn key_id_exists(key_triple: &KeyTriple, store_handle: &dyn ManageKeyIDs) -> Result<bool> {
-    match store_handle.exists(key_triple) {
-        Ok(val) => Ok(val),
-        Err(string) => {
-            error!("Key ID Manager error: {}", string);
-            Err(ResponseStatus::KeyIDManagerError)
-        }
-    }
-}
-
 impl MbedProvider {
     /// Creates and initialise a new instance of MbedProvider.
     /// Checks if there are not more keys stored in the Key ID Manager than in the MbedProvider and
@@ -258,6 +288,41 @@ impl Provide for MbedProvider {
         }
     }
 
+    fn list_keys(&self, app_name: ApplicationName, _op: OpListKeys) -> Result<ResultListKeys> {
+        info!("Mbed Provider - List Keys");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triples = store_handle
+            .get_all(ProviderID::MbedProvider)
+            .or_else(|string| {
+                error!("Key ID Manager error: {}", string);
+                Err(ResponseStatus::KeyIDManagerError)
+            })?;
+
+        let mut keys = Vec::new();
+        for key_triple in key_triples
+            .into_iter()
+            .filter(|key_triple| key_triple.app_name() == &app_name)
+        {
+            let attributes = get_key_id(key_triple, &*store_handle)
+                .and_then(|key_id| Key::open_key(key_id, &self.key_handle_mutex)?.get_attributes())
+                .and_then(|key_attrs| utils::convert_psa_key_attributes(&key_attrs));
+            match attributes {
+                Ok(attributes) => keys.push(KeyInfo {
+                    provider_id: ProviderID::MbedProvider,
+                    name: key_triple.key_name().to_string(),
+                    attributes,
+                }),
+                Err(status) => warn!(
+                    "Key {} can not be listed (error: {}), continuing...",
+                    key_triple, status
+                ),
+            }
+        }
+
+        Ok(ResultListKeys { keys })
+    }
+
     fn create_key(&self, app_name: ApplicationName, op: OpCreateKey) -> Result<ResultCreateKey> {
         info!("Mbed Provider - Create Key");
         let _semaphore_guard = self.key_slot_semaphore.access();
@@ -266,7 +331,7 @@ impl Provide for MbedProvider {
         let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, key_name);
         let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
         let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
-        if key_id_exists(&key_triple, &*store_handle)? {
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
             return Err(ResponseStatus::KeyAlreadyExists);
         }
         let key_id = create_key_id(
@@ -275,7 +340,15 @@ impl Provide for MbedProvider {
             &mut local_ids_handle,
         )?;
 
-        let key_attrs = utils::convert_key_attributes(&key_attributes, key_id);
+        let key_attrs = utils::convert_key_attributes(&key_attributes, key_id).or_else(|err| {
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            Err(err)
+        })?;
         let mut key = Key::new(&self.key_handle_mutex);
 
         let generate_key_status = unsafe {
@@ -309,7 +382,7 @@ impl Provide for MbedProvider {
         let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, key_name);
         let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
         let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
-        if key_id_exists(&key_triple, &*store_handle)? {
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
             return Err(ResponseStatus::KeyAlreadyExists);
         }
         let key_id = create_key_id(
@@ -318,7 +391,15 @@ impl Provide for MbedProvider {
             &mut local_ids_handle,
         )?;
 
-        let key_attrs = utils::convert_key_attributes(&key_attributes, key_id);
+        let key_attrs = utils::convert_key_attributes(&key_attributes, key_id).or_else(|err| {
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            Err(err)
+        })?;
         let mut key = Key::new(&self.key_handle_mutex);
 
         let import_key_status = unsafe {
@@ -358,6 +439,8 @@ impl Provide for MbedProvider {
         let key_name = op.key_name;
         let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, key_name);
         let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Export, &*store_handle)?;
         let key_id = get_key_id(&key_triple, &*store_handle)?;
         let key = Key::open_key(key_id, &self.key_handle_mutex)?;
 
@@ -403,6 +486,7 @@ impl Provide for MbedProvider {
                 &mut *store_handle,
                 &mut local_ids_handle,
             )?;
+            key_id_managers::remove_grants(&key_triple, &mut *store_handle)?;
             Ok(ResultDestroyKey {})
         } else {
             error!("Destroy key status: {}", destroy_key_status);
@@ -417,6 +501,8 @@ impl Provide for MbedProvider {
         let hash = op.hash;
         let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, key_name);
         let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Sign, &*store_handle)?;
         let key_id = get_key_id(&key_triple, &*store_handle)?;
         let key = Key::open_key(key_id, &self.key_handle_mutex)?;
         let key_attrs = key.get_attributes()?;
@@ -459,6 +545,8 @@ impl Provide for MbedProvider {
         let signature = op.signature;
         let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, key_name);
         let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Verify, &*store_handle)?;
         let key_id = get_key_id(&key_triple, &*store_handle)?;
         let key = Key::open_key(key_id, &self.key_handle_mutex)?;
         let key_attrs = key.get_attributes()?;
@@ -480,6 +568,717 @@ impl Provide for MbedProvider {
             Err(utils::convert_status(verify_status))
         }
     }
+
+    fn asym_encrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymmetricEncrypt,
+    ) -> Result<ResultAsymmetricEncrypt> {
+        info!("Mbed Provider - Asym Encrypt");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let plaintext = op.plaintext;
+        let salt = op.salt.unwrap_or_default();
+        let buffer_size = utils::psa_asymmetric_encrypt_output_size(&key_attrs)?;
+        let mut ciphertext = vec![0u8; buffer_size];
+        let mut ciphertext_size: usize = 0;
+
+        let encrypt_status = unsafe {
+            psa_crypto_binding::psa_asymmetric_encrypt(
+                key.raw_handle(),
+                key_attrs.core.policy.alg,
+                plaintext.as_ptr(),
+                plaintext.len(),
+                salt.as_ptr(),
+                salt.len(),
+                ciphertext.as_mut_ptr(),
+                buffer_size,
+                &mut ciphertext_size,
+            )
+        };
+
+        if encrypt_status == PSA_SUCCESS {
+            ciphertext.truncate(ciphertext_size);
+            Ok(ResultAsymmetricEncrypt { ciphertext })
+        } else {
+            error!("Encrypt status: {}", encrypt_status);
+            Err(utils::convert_status(encrypt_status))
+        }
+    }
+
+    fn asym_decrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymmetricDecrypt,
+    ) -> Result<ResultAsymmetricDecrypt> {
+        info!("Mbed Provider - Asym Decrypt");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let ciphertext = op.ciphertext;
+        let salt = op.salt.unwrap_or_default();
+        let buffer_size = utils::psa_asymmetric_decrypt_output_size(&key_attrs)?;
+        let mut plaintext = vec![0u8; buffer_size];
+        let mut plaintext_size: usize = 0;
+
+        let decrypt_status = unsafe {
+            psa_crypto_binding::psa_asymmetric_decrypt(
+                key.raw_handle(),
+                key_attrs.core.policy.alg,
+                ciphertext.as_ptr(),
+                ciphertext.len(),
+                salt.as_ptr(),
+                salt.len(),
+                plaintext.as_mut_ptr(),
+                buffer_size,
+                &mut plaintext_size,
+            )
+        };
+
+        if decrypt_status == PSA_SUCCESS {
+            plaintext.truncate(plaintext_size);
+            Ok(ResultAsymmetricDecrypt { plaintext })
+        } else {
+            error!("Decrypt status: {}", decrypt_status);
+            Err(utils::convert_status(decrypt_status))
+        }
+    }
+
+    fn aead_encrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAeadEncrypt,
+    ) -> Result<ResultAeadEncrypt> {
+        info!("Mbed Provider - AEAD Encrypt");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let nonce = op.nonce;
+        let additional_data = op.additional_data;
+        let plaintext = op.plaintext;
+        let buffer_size =
+            utils::psa_aead_encrypt_output_size(key_attrs.core.policy.alg, plaintext.len())?;
+        let mut ciphertext = vec![0u8; buffer_size];
+        let mut ciphertext_size: usize = 0;
+
+        let encrypt_status = unsafe {
+            psa_crypto_binding::psa_aead_encrypt(
+                key.raw_handle(),
+                key_attrs.core.policy.alg,
+                nonce.as_ptr(),
+                nonce.len(),
+                additional_data.as_ptr(),
+                additional_data.len(),
+                plaintext.as_ptr(),
+                plaintext.len(),
+                ciphertext.as_mut_ptr(),
+                buffer_size,
+                &mut ciphertext_size,
+            )
+        };
+
+        if encrypt_status == PSA_SUCCESS {
+            ciphertext.truncate(ciphertext_size);
+            Ok(ResultAeadEncrypt { ciphertext })
+        } else {
+            error!("AEAD encrypt status: {}", encrypt_status);
+            Err(utils::convert_status(encrypt_status))
+        }
+    }
+
+    fn aead_decrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAeadDecrypt,
+    ) -> Result<ResultAeadDecrypt> {
+        info!("Mbed Provider - AEAD Decrypt");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let nonce = op.nonce;
+        let additional_data = op.additional_data;
+        let ciphertext = op.ciphertext;
+        let buffer_size =
+            utils::psa_aead_decrypt_output_size(key_attrs.core.policy.alg, ciphertext.len())?;
+        let mut plaintext = vec![0u8; buffer_size];
+        let mut plaintext_size: usize = 0;
+
+        let decrypt_status = unsafe {
+            psa_crypto_binding::psa_aead_decrypt(
+                key.raw_handle(),
+                key_attrs.core.policy.alg,
+                nonce.as_ptr(),
+                nonce.len(),
+                additional_data.as_ptr(),
+                additional_data.len(),
+                ciphertext.as_ptr(),
+                ciphertext.len(),
+                plaintext.as_mut_ptr(),
+                buffer_size,
+                &mut plaintext_size,
+            )
+        };
+
+        if decrypt_status == PSA_SUCCESS {
+            plaintext.truncate(plaintext_size);
+            Ok(ResultAeadDecrypt { plaintext })
+        } else {
+            error!("AEAD decrypt status: {}", decrypt_status);
+            Err(utils::convert_status(decrypt_status))
+        }
+    }
+
+    fn mac_compute(&self, app_name: ApplicationName, op: OpMacCompute) -> Result<ResultMacCompute> {
+        info!("Mbed Provider - MAC Compute");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let input = op.input;
+        let alg = key_attrs.core.policy.alg;
+        let mac_size = utils::psa_mac_output_size(alg)?;
+        let mut mac = vec![0u8; mac_size];
+        let mut mac_length: usize = 0;
+        let mut operation = utils::get_empty_mac_operation();
+
+        let mut mac_status = unsafe {
+            psa_crypto_binding::psa_mac_sign_setup(&mut operation, key.raw_handle(), alg)
+        };
+        if mac_status == PSA_SUCCESS {
+            mac_status = unsafe {
+                psa_crypto_binding::psa_mac_update(&mut operation, input.as_ptr(), input.len())
+            };
+        }
+        if mac_status == PSA_SUCCESS {
+            mac_status = unsafe {
+                psa_crypto_binding::psa_mac_sign_finish(
+                    &mut operation,
+                    mac.as_mut_ptr(),
+                    mac_size,
+                    &mut mac_length,
+                )
+            };
+        }
+
+        if mac_status == PSA_SUCCESS {
+            mac.truncate(mac_length);
+            Ok(ResultMacCompute { mac })
+        } else {
+            unsafe { psa_crypto_binding::psa_mac_abort(&mut operation) };
+            error!("MAC compute status: {}", mac_status);
+            Err(utils::convert_status(mac_status))
+        }
+    }
+
+    fn mac_verify(&self, app_name: ApplicationName, op: OpMacVerify) -> Result<ResultMacVerify> {
+        info!("Mbed Provider - MAC Verify");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let input = op.input;
+        let mac = op.mac;
+        let mut operation = utils::get_empty_mac_operation();
+
+        // The comparison of the MAC is done in constant time by Mbed Crypto.
+        let mut mac_status = unsafe {
+            psa_crypto_binding::psa_mac_verify_setup(
+                &mut operation,
+                key.raw_handle(),
+                key_attrs.core.policy.alg,
+            )
+        };
+        if mac_status == PSA_SUCCESS {
+            mac_status = unsafe {
+                psa_crypto_binding::psa_mac_update(&mut operation, input.as_ptr(), input.len())
+            };
+        }
+        if mac_status == PSA_SUCCESS {
+            mac_status = unsafe {
+                psa_crypto_binding::psa_mac_verify_finish(&mut operation, mac.as_ptr(), mac.len())
+            };
+        }
+
+        if mac_status == PSA_SUCCESS {
+            Ok(ResultMacVerify {})
+        } else {
+            unsafe { psa_crypto_binding::psa_mac_abort(&mut operation) };
+            error!("MAC verify status: {}", mac_status);
+            Err(utils::convert_status(mac_status))
+        }
+    }
+
+    fn hash_compute(&self, op: OpHashCompute) -> Result<ResultHashCompute> {
+        info!("Mbed Provider - Hash Compute");
+        let alg = utils::convert_hash_algorithm(op.alg);
+        let hash = utils::compute_hash(alg, &op.input)?;
+        Ok(ResultHashCompute { hash })
+    }
+
+    fn hash_compare(&self, op: OpHashCompare) -> Result<ResultHashCompare> {
+        info!("Mbed Provider - Hash Compare");
+        let alg = utils::convert_hash_algorithm(op.alg);
+        let input = op.input;
+        let hash = op.hash;
+        let mut operation = utils::get_empty_hash_operation();
+
+        // The comparison of the hash is done in constant time by Mbed Crypto.
+        let mut hash_status = unsafe { psa_crypto_binding::psa_hash_setup(&mut operation, alg) };
+        if hash_status == PSA_SUCCESS {
+            hash_status = unsafe {
+                psa_crypto_binding::psa_hash_update(&mut operation, input.as_ptr(), input.len())
+            };
+        }
+        if hash_status == PSA_SUCCESS {
+            hash_status = unsafe {
+                psa_crypto_binding::psa_hash_verify(&mut operation, hash.as_ptr(), hash.len())
+            };
+        }
+
+        if hash_status == PSA_SUCCESS {
+            Ok(ResultHashCompare {})
+        } else {
+            unsafe { psa_crypto_binding::psa_hash_abort(&mut operation) };
+            error!("Hash compare status: {}", hash_status);
+            Err(utils::convert_status(hash_status))
+        }
+    }
+
+    fn asym_sign_message(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymSignMessage,
+    ) -> Result<ResultAsymSignMessage> {
+        info!("Mbed Provider - Asym Sign Message");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Sign, &*store_handle)?;
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let alg = key_attrs.core.policy.alg;
+        let hash = utils::compute_hash(utils::psa_sign_hash_algorithm(alg)?, &op.message)?;
+        let buffer_size = utils::psa_asymmetric_sign_output_size(&key_attrs)?;
+        let mut signature = vec![0u8; buffer_size];
+        let mut signature_size: usize = 0;
+
+        let sign_status = unsafe {
+            psa_crypto_binding::psa_asymmetric_sign(
+                key.raw_handle(),
+                alg,
+                hash.as_ptr(),
+                hash.len(),
+                signature.as_mut_ptr(),
+                buffer_size,
+                &mut signature_size,
+            )
+        };
+
+        if sign_status == PSA_SUCCESS {
+            signature.truncate(signature_size);
+            Ok(ResultAsymSignMessage { signature })
+        } else {
+            error!("Sign status: {}", sign_status);
+            Err(utils::convert_status(sign_status))
+        }
+    }
+
+    fn asym_verify_message(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymVerifyMessage,
+    ) -> Result<ResultAsymVerifyMessage> {
+        info!("Mbed Provider - Asym Verify Message");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Verify, &*store_handle)?;
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let alg = key_attrs.core.policy.alg;
+        let hash = utils::compute_hash(utils::psa_sign_hash_algorithm(alg)?, &op.message)?;
+        let signature = op.signature;
+
+        let verify_status = unsafe {
+            psa_crypto_binding::psa_asymmetric_verify(
+                key.raw_handle(),
+                alg,
+                hash.as_ptr(),
+                hash.len(),
+                signature.as_ptr(),
+                signature.len(),
+            )
+        };
+
+        if verify_status == PSA_SUCCESS {
+            Ok(ResultAsymVerifyMessage {})
+        } else {
+            Err(utils::convert_status(verify_status))
+        }
+    }
+
+    fn raw_key_agreement(
+        &self,
+        app_name: ApplicationName,
+        op: OpRawKeyAgreement,
+    ) -> Result<ResultRawKeyAgreement> {
+        info!("Mbed Provider - Raw Key Agreement");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.private_key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let alg = key_attrs.core.policy.alg;
+        if utils::psa_key_agreement_kdf(alg)?.is_some() {
+            error!("The key can only be used for a key agreement followed by a key derivation.");
+            return Err(ResponseStatus::PsaErrorNotPermitted);
+        }
+        let peer_key = op.peer_key;
+        let secret_size = utils::psa_raw_key_agreement_output_size(&key_attrs)?;
+        let mut shared_secret = vec![0u8; secret_size];
+        let mut shared_secret_length: usize = 0;
+
+        let agreement_status = unsafe {
+            psa_crypto_binding::psa_raw_key_agreement(
+                alg,
+                key.raw_handle(),
+                peer_key.as_ptr(),
+                peer_key.len(),
+                shared_secret.as_mut_ptr(),
+                secret_size,
+                &mut shared_secret_length,
+            )
+        };
+
+        if agreement_status == PSA_SUCCESS {
+            shared_secret.truncate(shared_secret_length);
+            Ok(ResultRawKeyAgreement { shared_secret })
+        } else {
+            error!("Raw key agreement status: {}", agreement_status);
+            Err(utils::convert_status(agreement_status))
+        }
+    }
+
+    fn key_agreement_derive_key(
+        &self,
+        app_name: ApplicationName,
+        op: OpKeyAgreementDeriveKey,
+    ) -> Result<ResultKeyAgreementDeriveKey> {
+        info!("Mbed Provider - Key Agreement Derive Key");
+        // The private key and the derived key both take a key slot but the semaphore is only
+        // accessed once, to not deadlock with other threads waiting for their second slot. Mbed
+        // Crypto fails the derivation cleanly if it runs out of slots.
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let private_key_triple = KeyTriple::new(
+            app_name.clone(),
+            ProviderID::MbedProvider,
+            op.private_key_name,
+        );
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        let private_key_id = get_key_id(&private_key_triple, &*store_handle)?;
+        let private_key = Key::open_key(private_key_id, &self.key_handle_mutex)?;
+        let private_key_attrs = private_key.get_attributes()?;
+
+        let alg = private_key_attrs.core.policy.alg;
+        let kdf = match utils::psa_key_agreement_kdf(alg)? {
+            Some(kdf) => kdf,
+            None => {
+                error!("The key can only be used for a raw key agreement.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+        };
+        let (salt_step, info_step) = utils::psa_key_derivation_input_steps(kdf)?;
+
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
+            return Err(ResponseStatus::KeyAlreadyExists);
+        }
+        let key_id = create_key_id(
+            key_triple.clone(),
+            &mut *store_handle,
+            &mut local_ids_handle,
+        )?;
+        let key_attrs =
+            utils::convert_key_attributes(&op.key_attributes, key_id).or_else(|err| {
+                remove_key_id(
+                    &key_triple,
+                    key_id,
+                    &mut *store_handle,
+                    &mut local_ids_handle,
+                )?;
+                Err(err)
+            })?;
+        let mut key = Key::new(&self.key_handle_mutex);
+        let mut operation = utils::get_empty_key_derivation_operation();
+
+        let mut derivation_status =
+            unsafe { psa_crypto_binding::psa_key_derivation_setup(&mut operation, alg) };
+        if derivation_status == PSA_SUCCESS && !op.salt.is_empty() {
+            derivation_status = unsafe {
+                psa_crypto_binding::psa_key_derivation_input_bytes(
+                    &mut operation,
+                    salt_step,
+                    op.salt.as_ptr(),
+                    op.salt.len(),
+                )
+            };
+        }
+        if derivation_status == PSA_SUCCESS {
+            derivation_status = unsafe {
+                psa_crypto_binding::psa_key_derivation_key_agreement(
+                    &mut operation,
+                    constants::PSA_KEY_DERIVATION_INPUT_SECRET,
+                    private_key.raw_handle(),
+                    op.peer_key.as_ptr(),
+                    op.peer_key.len(),
+                )
+            };
+        }
+        if derivation_status == PSA_SUCCESS && !op.info.is_empty() {
+            derivation_status = unsafe {
+                psa_crypto_binding::psa_key_derivation_input_bytes(
+                    &mut operation,
+                    info_step,
+                    op.info.as_ptr(),
+                    op.info.len(),
+                )
+            };
+        }
+        if derivation_status == PSA_SUCCESS {
+            derivation_status = unsafe {
+                let _guard = self
+                    .key_handle_mutex
+                    .lock()
+                    .expect("Grabbing key handle mutex failed");
+                psa_crypto_binding::psa_key_derivation_output_key(
+                    &key_attrs,
+                    &mut operation,
+                    key.as_mut(),
+                )
+            };
+        }
+        unsafe { psa_crypto_binding::psa_key_derivation_abort(&mut operation) };
+
+        if derivation_status != PSA_SUCCESS {
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            error!("Key agreement derive key status: {}", derivation_status);
+            return Err(utils::convert_status(derivation_status));
+        }
+
+        Ok(ResultKeyAgreementDeriveKey {})
+    }
+
+    fn key_derivation_output_key(
+        &self,
+        app_name: ApplicationName,
+        op: OpKeyDerivationOutputKey,
+    ) -> Result<ResultKeyDerivationOutputKey> {
+        info!("Mbed Provider - Key Derivation Output Key");
+        // As for key agreements, the semaphore is only accessed once for the two key slots used.
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let base_key_triple =
+            KeyTriple::new(app_name.clone(), ProviderID::MbedProvider, op.base_key_name);
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        let base_key_id = get_key_id(&base_key_triple, &*store_handle)?;
+        let base_key = Key::open_key(base_key_id, &self.key_handle_mutex)?;
+        let base_key_attrs = base_key.get_attributes()?;
+
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
+            return Err(ResponseStatus::KeyAlreadyExists);
+        }
+        let key_id = create_key_id(
+            key_triple.clone(),
+            &mut *store_handle,
+            &mut local_ids_handle,
+        )?;
+        let key_attrs =
+            utils::convert_key_attributes(&op.key_attributes, key_id).or_else(|err| {
+                remove_key_id(
+                    &key_triple,
+                    key_id,
+                    &mut *store_handle,
+                    &mut local_ids_handle,
+                )?;
+                Err(err)
+            })?;
+        let mut key = Key::new(&self.key_handle_mutex);
+        let mut operation = utils::get_empty_key_derivation_operation();
+
+        let derivation_result = utils::setup_key_derivation(
+            &mut operation,
+            base_key_attrs.core.policy.alg,
+            base_key.raw_handle(),
+            &op.salt,
+            &op.info,
+        )
+        .and_then(|_| {
+            let output_status = unsafe {
+                let _guard = self
+                    .key_handle_mutex
+                    .lock()
+                    .expect("Grabbing key handle mutex failed");
+                psa_crypto_binding::psa_key_derivation_output_key(
+                    &key_attrs,
+                    &mut operation,
+                    key.as_mut(),
+                )
+            };
+            if output_status == PSA_SUCCESS {
+                Ok(())
+            } else {
+                error!("Key derivation output key status: {}", output_status);
+                Err(utils::convert_status(output_status))
+            }
+        });
+        unsafe { psa_crypto_binding::psa_key_derivation_abort(&mut operation) };
+
+        if let Err(err) = derivation_result {
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            return Err(err);
+        }
+
+        Ok(ResultKeyDerivationOutputKey {})
+    }
+
+    fn key_derivation_output_bytes(
+        &self,
+        app_name: ApplicationName,
+        op: OpKeyDerivationOutputBytes,
+    ) -> Result<ResultKeyDerivationOutputBytes> {
+        info!("Mbed Provider - Key Derivation Output Bytes");
+        let _semaphore_guard = self.key_slot_semaphore.access();
+        let output_size = op.length as usize;
+        if output_size == 0 || output_size > MAX_KEY_DERIVATION_OUTPUT_SIZE {
+            error!(
+                "Key derivations can output between 1 and {} bytes.",
+                MAX_KEY_DERIVATION_OUTPUT_SIZE
+            );
+            return Err(ResponseStatus::PsaErrorInvalidArgument);
+        }
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.base_key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        let key = Key::open_key(key_id, &self.key_handle_mutex)?;
+        let key_attrs = key.get_attributes()?;
+
+        let mut output = vec![0u8; output_size];
+        let mut operation = utils::get_empty_key_derivation_operation();
+
+        let derivation_result = utils::setup_key_derivation(
+            &mut operation,
+            key_attrs.core.policy.alg,
+            key.raw_handle(),
+            &op.salt,
+            &op.info,
+        )
+        .and_then(|_| {
+            let output_status = unsafe {
+                psa_crypto_binding::psa_key_derivation_output_bytes(
+                    &mut operation,
+                    output.as_mut_ptr(),
+                    output_size,
+                )
+            };
+            if output_status == PSA_SUCCESS {
+                Ok(())
+            } else {
+                error!("Key derivation output bytes status: {}", output_status);
+                Err(utils::convert_status(output_status))
+            }
+        });
+        unsafe { psa_crypto_binding::psa_key_derivation_abort(&mut operation) };
+
+        derivation_result.map(|_| ResultKeyDerivationOutputBytes { output })
+    }
+
+    fn generate_random(&self, op: OpGenerateRandom) -> Result<ResultGenerateRandom> {
+        info!("Mbed Provider - Generate Random");
+        let mut random_bytes = vec![0u8; op.size];
+
+        let generate_status = unsafe {
+            psa_crypto_binding::psa_generate_random(random_bytes.as_mut_ptr(), random_bytes.len())
+        };
+
+        if generate_status == PSA_SUCCESS {
+            Ok(ResultGenerateRandom { random_bytes })
+        } else {
+            error!("Generate random status: {}", generate_status);
+            Err(utils::convert_status(generate_status))
+        }
+    }
+
+    fn grant_key(&self, app_name: ApplicationName, op: OpGrantKey) -> Result<ResultGrantKey> {
+        info!("Mbed Provider - Grant Key");
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        key_id_managers::grant_key(
+            &key_triple,
+            ApplicationName::new(op.grantee),
+            op.permit_sign,
+            op.permit_verify,
+            op.permit_export,
+            &mut *store_handle,
+        )?;
+
+        Ok(ResultGrantKey {})
+    }
+
+    fn revoke_key(&self, app_name: ApplicationName, op: OpRevokeKey) -> Result<ResultRevokeKey> {
+        info!("Mbed Provider - Revoke Key");
+        let key_triple = KeyTriple::new(app_name, ProviderID::MbedProvider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        key_id_managers::revoke_key(
+            &key_triple,
+            ApplicationName::new(op.grantee),
+            &mut *store_handle,
+        )?;
+
+        Ok(ResultRevokeKey {})
+    }
 }
 
 #[derive(Default)]
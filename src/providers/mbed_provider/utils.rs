@@ -14,9 +14,10 @@
 // limitations under the License.
 use super::constants::*;
 use super::psa_crypto_binding::{
-    self, psa_algorithm_t, psa_core_key_attributes_t, psa_key_attributes_t, psa_key_bits_t,
-    psa_key_handle_t, psa_key_id_t, psa_key_policy_s, psa_key_type_t, psa_key_usage_t,
-    psa_status_t,
+    self, psa_algorithm_t, psa_core_key_attributes_t, psa_ecc_curve_t, psa_hash_operation_t,
+    psa_key_attributes_t, psa_key_bits_t, psa_key_derivation_operation_t,
+    psa_key_derivation_step_t, psa_key_handle_t, psa_key_id_t, psa_key_policy_s, psa_key_type_t,
+    psa_key_usage_t, psa_mac_operation_t, psa_status_t,
 };
 use log::error;
 use parsec_interface::operations::key_attributes::*;
@@ -27,19 +28,22 @@ use std::sync::Mutex;
 /// Converts between native PARSEC key attributes and ID and the
 /// `psa_key_attributes_t` structure required by Mbed Crypto.
 ///
-/// # Panics
+/// # Errors
 ///
 /// If either algorithm or key type conversion fails. See docs for
 /// `convert_key_type` and `convert_algorithm` for more details.
-pub fn convert_key_attributes(attrs: &KeyAttributes, key_id: psa_key_id_t) -> psa_key_attributes_t {
-    psa_key_attributes_t {
+pub fn convert_key_attributes(
+    attrs: &KeyAttributes,
+    key_id: psa_key_id_t,
+) -> Result<psa_key_attributes_t> {
+    Ok(psa_key_attributes_t {
         core: psa_core_key_attributes_t {
-            type_: convert_key_type(attrs.key_type),
+            type_: convert_key_type(attrs.key_type, attrs.ecc_curve)?,
             lifetime: PSA_KEY_LIFETIME_PERSISTENT,
             id: key_id,
             policy: psa_key_policy_s {
                 usage: convert_key_usage(&attrs),
-                alg: convert_algorithm(&attrs.algorithm),
+                alg: convert_algorithm(&attrs.algorithm)?,
                 alg2: 0,
             },
             bits: convert_key_bits(attrs.key_size),
@@ -47,7 +51,7 @@ pub fn convert_key_attributes(attrs: &KeyAttributes, key_id: psa_key_id_t) -> ps
         },
         domain_parameters: ::std::ptr::null_mut(),
         domain_parameters_size: 0,
-    }
+    })
 }
 
 /// Generates a blank `psa_key_attributes_t` object.
@@ -70,6 +74,25 @@ pub fn get_empty_key_attributes() -> psa_key_attributes_t {
     }
 }
 
+/// Generates a blank `psa_mac_operation_t` object, as `PSA_MAC_OPERATION_INIT` does.
+pub fn get_empty_mac_operation() -> psa_mac_operation_t {
+    // All-bits-zero is the initial state of a MAC operation.
+    unsafe { std::mem::zeroed() }
+}
+
+/// Generates a blank `psa_hash_operation_t` object, as `PSA_HASH_OPERATION_INIT` does.
+pub fn get_empty_hash_operation() -> psa_hash_operation_t {
+    // All-bits-zero is the initial state of a hash operation.
+    unsafe { std::mem::zeroed() }
+}
+
+/// Generates a blank `psa_key_derivation_operation_t` object, as `PSA_KEY_DERIVATION_OPERATION_INIT`
+/// does.
+pub fn get_empty_key_derivation_operation() -> psa_key_derivation_operation_t {
+    // All-bits-zero is the initial state of a key derivation operation.
+    unsafe { std::mem::zeroed() }
+}
+
 /// Convert down from a `u32` value to a `u16` (`psa_key_bits_t`), capping the
 /// result at `PSA_KEY_BITS_TOO_LARGE`.
 pub fn convert_key_bits(key_size: u32) -> psa_key_bits_t {
@@ -78,15 +101,51 @@ pub fn convert_key_bits(key_size: u32) -> psa_key_bits_t {
 
 /// Converts between native and Mbed Crypto type values.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Only `KeyType::RsaKeypair` and `KeyType::RsaPublicKey` are supported. Panics otherwise.
-pub fn convert_key_type(key_type: KeyType) -> psa_key_type_t {
+/// Only RSA key pairs and public keys, ECC key pairs and public keys on the NIST P-256, P-384
+/// and P-521 curves, AES, ChaCha20 and HMAC keys, and key derivation secrets are supported. Returns
+/// `ResponseStatus::PsaErrorNotSupported` otherwise, or `ResponseStatus::PsaErrorInvalidArgument`
+/// if an ECC key type is given without a curve.
+pub fn convert_key_type(key_type: KeyType, ecc_curve: Option<EccCurve>) -> Result<psa_key_type_t> {
     match key_type {
-        KeyType::RsaKeypair => PSA_KEY_TYPE_RSA_KEYPAIR,
-        KeyType::RsaPublicKey => PSA_KEY_TYPE_RSA_PUBLIC_KEY,
+        KeyType::RsaKeypair => Ok(PSA_KEY_TYPE_RSA_KEYPAIR),
+        KeyType::RsaPublicKey => Ok(PSA_KEY_TYPE_RSA_PUBLIC_KEY),
+        KeyType::EccKeypair => {
+            Ok(PSA_KEY_TYPE_ECC_KEYPAIR_BASE | psa_key_type_t::from(convert_ecc_curve(ecc_curve)?))
+        }
+        KeyType::EccPublicKey => {
+            Ok(PSA_KEY_TYPE_ECC_PUBLIC_KEY_BASE
+                | psa_key_type_t::from(convert_ecc_curve(ecc_curve)?))
+        }
+        KeyType::Aes => Ok(PSA_KEY_TYPE_AES),
+        KeyType::Chacha20 => Ok(PSA_KEY_TYPE_CHACHA20),
+        KeyType::Hmac => Ok(PSA_KEY_TYPE_HMAC),
+        KeyType::Derive => Ok(PSA_KEY_TYPE_DERIVE),
         _ => {
-            unimplemented!();
+            error!("Key type {:?} is not supported by this provider.", key_type);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Converts between native and Mbed Crypto elliptic curve values.
+///
+/// # Errors
+///
+/// Only the NIST P-256, P-384 and P-521 curves are supported.
+pub fn convert_ecc_curve(ecc_curve: Option<EccCurve>) -> Result<psa_ecc_curve_t> {
+    match ecc_curve {
+        Some(EccCurve::Secp256r1) => Ok(PSA_ECC_CURVE_SECP256R1),
+        Some(EccCurve::Secp384r1) => Ok(PSA_ECC_CURVE_SECP384R1),
+        Some(EccCurve::Secp521r1) => Ok(PSA_ECC_CURVE_SECP521R1),
+        Some(curve) => {
+            error!("Curve {:?} is not supported by this provider.", curve);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+        None => {
+            error!("An ECC key type requires a curve to be specified.");
+            Err(ResponseStatus::PsaErrorInvalidArgument)
         }
     }
 }
@@ -127,29 +186,309 @@ pub fn convert_key_usage(operation: &KeyAttributes) -> psa_key_usage_t {
 
 /// Converts between native and Mbed Crypto algorithm values.
 ///
-/// # Panics
+/// # Errors
 ///
-/// Only `AlgorithmInner::Sign` is supported as algorithm with only the
-/// `SignAlgorithm::RsaPkcs1v15Sign` signing algorithm. Will panic otherwise.
-pub fn convert_algorithm(alg: &Algorithm) -> psa_algorithm_t {
-    let mut algo_val: psa_algorithm_t;
+/// Only `AlgorithmInner::Sign` is supported as algorithm, with the
+/// `SignAlgorithm::RsaPkcs1v15Sign`, `SignAlgorithm::RsaPss`, `SignAlgorithm::Ecdsa` and
+/// `SignAlgorithm::DeterministicEcdsa` signing algorithms, and
+/// `AlgorithmInner::AsymmetricEncryption` with the
+/// `AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt` and `AsymmetricEncryptionAlgorithm::RsaOaep`
+/// algorithms, and `AlgorithmInner::Aead` with the `AeadAlgorithm::Ccm`, `AeadAlgorithm::Gcm` and
+/// `AeadAlgorithm::Chacha20Poly1305` algorithms and their default tag length, and
+/// `AlgorithmInner::Mac` with the `MacAlgorithm::Hmac` and `MacAlgorithm::Cmac` algorithms, not
+/// truncated, and `AlgorithmInner::KeyAgreement` with the `KeyAgreementAlgorithm::Ecdh`
+/// algorithm, raw or followed by a key derivation, and `AlgorithmInner::KeyDerivation` (see
+/// `convert_key_derivation_algorithm` for both).
+/// Returns
+/// `ResponseStatus::PsaErrorNotSupported` otherwise. RSA-PSS and RSA-OAEP require a hash
+/// algorithm, the salt length of RSA-PSS is then the length of the hash. So does HMAC.
+pub fn convert_algorithm(alg: &Algorithm) -> Result<psa_algorithm_t> {
     match alg.inner() {
         AlgorithmInner::Sign(sign, hash) => {
-            algo_val = match sign {
+            let mut algo_val = match sign {
                 SignAlgorithm::RsaPkcs1v15Sign => PSA_ALG_RSA_PKCS1V15_SIGN_BASE,
+                SignAlgorithm::RsaPss if hash.is_some() => PSA_ALG_RSA_PSS_BASE,
+                SignAlgorithm::RsaPss => {
+                    error!("RSA-PSS requires a hash algorithm to be specified.");
+                    return Err(ResponseStatus::PsaErrorInvalidArgument);
+                }
+                SignAlgorithm::Ecdsa => PSA_ALG_ECDSA_BASE,
+                SignAlgorithm::DeterministicEcdsa => PSA_ALG_DETERMINISTIC_ECDSA_BASE,
                 _ => {
-                    unimplemented!();
+                    error!(
+                        "Sign algorithm {:?} is not supported by this provider.",
+                        sign
+                    );
+                    return Err(ResponseStatus::PsaErrorNotSupported);
                 }
             };
             if hash.is_some() {
                 algo_val |= convert_hash_algorithm(hash.unwrap()) & PSA_ALG_HASH_MASK;
             }
+            Ok(algo_val)
+        }
+        AlgorithmInner::AsymmetricEncryption(encryption, hash) => match encryption {
+            AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt => Ok(PSA_ALG_RSA_PKCS1V15_CRYPT),
+            AsymmetricEncryptionAlgorithm::RsaOaep if hash.is_some() => {
+                Ok(PSA_ALG_RSA_OAEP_BASE
+                    | (convert_hash_algorithm(hash.unwrap()) & PSA_ALG_HASH_MASK))
+            }
+            AsymmetricEncryptionAlgorithm::RsaOaep => {
+                error!("RSA-OAEP requires a hash algorithm to be specified.");
+                Err(ResponseStatus::PsaErrorInvalidArgument)
+            }
+        },
+        AlgorithmInner::Aead(aead) => match aead {
+            AeadAlgorithm::Ccm => Ok(PSA_ALG_CCM),
+            AeadAlgorithm::Gcm => Ok(PSA_ALG_GCM),
+            AeadAlgorithm::Chacha20Poly1305 => Ok(PSA_ALG_CHACHA20_POLY1305),
+        },
+        AlgorithmInner::Mac(mac, hash) => match mac {
+            MacAlgorithm::Hmac if hash.is_some() => {
+                Ok(PSA_ALG_HMAC_BASE | (convert_hash_algorithm(hash.unwrap()) & PSA_ALG_HASH_MASK))
+            }
+            MacAlgorithm::Hmac => {
+                error!("HMAC requires a hash algorithm to be specified.");
+                Err(ResponseStatus::PsaErrorInvalidArgument)
+            }
+            MacAlgorithm::Cmac => Ok(PSA_ALG_CMAC),
+            _ => {
+                error!("MAC algorithm {:?} is not supported by this provider.", mac);
+                Err(ResponseStatus::PsaErrorNotSupported)
+            }
+        },
+        AlgorithmInner::KeyAgreement(agreement, derivation, hash) => {
+            let agreement_val = match agreement {
+                KeyAgreementAlgorithm::Ecdh => PSA_ALG_ECDH,
+                _ => {
+                    error!(
+                        "Key agreement algorithm {:?} is not supported by this provider.",
+                        agreement
+                    );
+                    return Err(ResponseStatus::PsaErrorNotSupported);
+                }
+            };
+            match derivation {
+                Some(derivation) => {
+                    Ok(agreement_val | convert_key_derivation_algorithm(*derivation, *hash)?)
+                }
+                None => Ok(agreement_val),
+            }
+        }
+        AlgorithmInner::KeyDerivation(derivation, hash) => {
+            convert_key_derivation_algorithm(*derivation, *hash)
+        }
+        _ => {
+            error!("Algorithm {:?} is not supported by this provider.", alg);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Converts between native and Mbed Crypto key derivation algorithm values.
+///
+/// # Errors
+///
+/// Only `KeyDerivationAlgorithm::Hkdf`, `KeyDerivationAlgorithm::Tls12Prf` and
+/// `KeyDerivationAlgorithm::Tls12PskToMs` are supported, returns
+/// `ResponseStatus::PsaErrorNotSupported` otherwise, as for PBKDF2 which Mbed Crypto does not
+/// implement. They all require a hash algorithm.
+pub fn convert_key_derivation_algorithm(
+    derivation: KeyDerivationAlgorithm,
+    hash: Option<HashAlgorithm>,
+) -> Result<psa_algorithm_t> {
+    let derivation_val = match derivation {
+        KeyDerivationAlgorithm::Hkdf => PSA_ALG_HKDF_BASE,
+        KeyDerivationAlgorithm::Tls12Prf => PSA_ALG_TLS12_PRF_BASE,
+        KeyDerivationAlgorithm::Tls12PskToMs => PSA_ALG_TLS12_PSK_TO_MS_BASE,
+        _ => {
+            error!(
+                "Key derivation algorithm {:?} is not supported by this provider.",
+                derivation
+            );
+            return Err(ResponseStatus::PsaErrorNotSupported);
+        }
+    };
+    if hash.is_some() {
+        Ok(derivation_val | (convert_hash_algorithm(hash.unwrap()) & PSA_ALG_HASH_MASK))
+    } else {
+        error!("Key derivation algorithms require a hash algorithm to be specified.");
+        Err(ResponseStatus::PsaErrorInvalidArgument)
+    }
+}
+
+/// Converts back from the `psa_key_attributes_t` structure of a stored key to native PARSEC key
+/// attributes.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotSupported` if the key type or algorithm has no native
+/// equivalent.
+pub fn convert_psa_key_attributes(key_attrs: &psa_key_attributes_t) -> Result<KeyAttributes> {
+    let usage = key_attrs.core.policy.usage;
+    Ok(KeyAttributes {
+        key_type: convert_psa_key_type(key_attrs.core.type_)?,
+        ecc_curve: convert_psa_ecc_curve(key_attrs.core.type_)?,
+        algorithm: convert_psa_algorithm(key_attrs.core.policy.alg)?,
+        key_size: u32::from(key_attrs.core.bits),
+        permit_sign: usage & PSA_KEY_USAGE_SIGN != 0,
+        permit_verify: usage & PSA_KEY_USAGE_VERIFY != 0,
+        permit_export: usage & PSA_KEY_USAGE_EXPORT != 0,
+        permit_derive: usage & PSA_KEY_USAGE_DERIVE != 0,
+        permit_encrypt: usage & PSA_KEY_USAGE_ENCRYPT != 0,
+        permit_decrypt: usage & PSA_KEY_USAGE_DECRYPT != 0,
+    })
+}
+
+/// Converts between Mbed Crypto and native type values.
+pub fn convert_psa_key_type(key_type: psa_key_type_t) -> Result<KeyType> {
+    match key_type {
+        PSA_KEY_TYPE_RSA_KEYPAIR => Ok(KeyType::RsaKeypair),
+        PSA_KEY_TYPE_RSA_PUBLIC_KEY => Ok(KeyType::RsaPublicKey),
+        PSA_KEY_TYPE_AES => Ok(KeyType::Aes),
+        PSA_KEY_TYPE_CHACHA20 => Ok(KeyType::Chacha20),
+        PSA_KEY_TYPE_HMAC => Ok(KeyType::Hmac),
+        PSA_KEY_TYPE_DERIVE => Ok(KeyType::Derive),
+        _ => match key_type & !PSA_KEY_TYPE_ECC_CURVE_MASK {
+            PSA_KEY_TYPE_ECC_KEYPAIR_BASE => Ok(KeyType::EccKeypair),
+            PSA_KEY_TYPE_ECC_PUBLIC_KEY_BASE => Ok(KeyType::EccPublicKey),
+            _ => {
+                error!("Key type {:#x} has no native equivalent.", key_type);
+                Err(ResponseStatus::PsaErrorNotSupported)
+            }
+        },
+    }
+}
+
+/// Extracts the native elliptic curve value from an Mbed Crypto key type. Returns `None` for key
+/// types which are not ECC keys.
+pub fn convert_psa_ecc_curve(key_type: psa_key_type_t) -> Result<Option<EccCurve>> {
+    if !is_ecc_key_type(key_type) {
+        return Ok(None);
+    }
+    let curve = key_type & PSA_KEY_TYPE_ECC_CURVE_MASK;
+    match curve as psa_ecc_curve_t {
+        PSA_ECC_CURVE_SECP256R1 => Ok(Some(EccCurve::Secp256r1)),
+        PSA_ECC_CURVE_SECP384R1 => Ok(Some(EccCurve::Secp384r1)),
+        PSA_ECC_CURVE_SECP521R1 => Ok(Some(EccCurve::Secp521r1)),
+        _ => {
+            error!("Curve {:#x} has no native equivalent.", curve);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Checks whether the Mbed Crypto key type is an ECC key pair or public key.
+fn is_ecc_key_type(key_type: psa_key_type_t) -> bool {
+    let base = key_type & !PSA_KEY_TYPE_ECC_CURVE_MASK;
+    base == PSA_KEY_TYPE_ECC_KEYPAIR_BASE || base == PSA_KEY_TYPE_ECC_PUBLIC_KEY_BASE
+}
+
+/// Converts between Mbed Crypto and native algorithm values.
+pub fn convert_psa_algorithm(alg: psa_algorithm_t) -> Result<Algorithm> {
+    // The low bits of AEAD and cipher-based MAC algorithms are not a hash algorithm.
+    match alg {
+        PSA_ALG_CMAC => return Ok(Algorithm::mac(MacAlgorithm::Cmac, None)),
+        PSA_ALG_CCM => return Ok(Algorithm::aead(AeadAlgorithm::Ccm)),
+        PSA_ALG_GCM => return Ok(Algorithm::aead(AeadAlgorithm::Gcm)),
+        PSA_ALG_CHACHA20_POLY1305 => return Ok(Algorithm::aead(AeadAlgorithm::Chacha20Poly1305)),
+        _ => (),
+    }
+    if alg & PSA_ALG_CATEGORY_MASK == PSA_ALG_CATEGORY_KEY_AGREEMENT {
+        return convert_psa_key_agreement_algorithm(alg);
+    }
+    if alg & PSA_ALG_CATEGORY_MASK == PSA_ALG_CATEGORY_KEY_DERIVATION {
+        let (derivation, hash) = convert_psa_key_derivation_algorithm(alg)?;
+        return Ok(Algorithm::key_derivation(derivation, hash));
+    }
+    let hash = match alg & PSA_ALG_HASH_MASK {
+        0 => None,
+        hash => Some(convert_psa_hash_algorithm(PSA_ALG_CATEGORY_HASH | hash)?),
+    };
+    match alg & !PSA_ALG_HASH_MASK {
+        PSA_ALG_HMAC_BASE => Ok(Algorithm::mac(MacAlgorithm::Hmac, hash)),
+        PSA_ALG_RSA_PKCS1V15_SIGN_BASE => Ok(Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, hash)),
+        PSA_ALG_RSA_PSS_BASE => Ok(Algorithm::sign(SignAlgorithm::RsaPss, hash)),
+        PSA_ALG_RSA_PKCS1V15_CRYPT => Ok(Algorithm::asymmetric_encryption(
+            AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt,
+            None,
+        )),
+        PSA_ALG_RSA_OAEP_BASE => Ok(Algorithm::asymmetric_encryption(
+            AsymmetricEncryptionAlgorithm::RsaOaep,
+            hash,
+        )),
+        PSA_ALG_ECDSA_BASE => Ok(Algorithm::sign(SignAlgorithm::Ecdsa, hash)),
+        PSA_ALG_DETERMINISTIC_ECDSA_BASE => {
+            Ok(Algorithm::sign(SignAlgorithm::DeterministicEcdsa, hash))
+        }
+        _ => {
+            error!("Algorithm {:#x} has no native equivalent.", alg);
+            Err(ResponseStatus::PsaErrorNotSupported)
         }
+    }
+}
+
+/// Converts between Mbed Crypto and native key agreement algorithm values, combined with a key
+/// derivation algorithm or not.
+fn convert_psa_key_agreement_algorithm(alg: psa_algorithm_t) -> Result<Algorithm> {
+    let agreement = match (alg & PSA_ALG_KEY_AGREEMENT_MASK) | PSA_ALG_CATEGORY_KEY_AGREEMENT {
+        PSA_ALG_ECDH => KeyAgreementAlgorithm::Ecdh,
         _ => {
-            unimplemented!();
+            error!("Algorithm {:#x} has no native equivalent.", alg);
+            return Err(ResponseStatus::PsaErrorNotSupported);
+        }
+    };
+    match psa_key_agreement_kdf(alg)? {
+        Some(kdf) => {
+            let (derivation, hash) = convert_psa_key_derivation_algorithm(kdf)?;
+            Ok(Algorithm::key_agreement(agreement, Some(derivation), hash))
+        }
+        None => Ok(Algorithm::key_agreement(agreement, None, None)),
+    }
+}
+
+/// Converts between Mbed Crypto and native key derivation algorithm values.
+pub fn convert_psa_key_derivation_algorithm(
+    alg: psa_algorithm_t,
+) -> Result<(KeyDerivationAlgorithm, Option<HashAlgorithm>)> {
+    let hash = match alg & PSA_ALG_HASH_MASK {
+        0 => None,
+        hash => Some(convert_psa_hash_algorithm(PSA_ALG_CATEGORY_HASH | hash)?),
+    };
+    match alg & !PSA_ALG_HASH_MASK {
+        PSA_ALG_HKDF_BASE => Ok((KeyDerivationAlgorithm::Hkdf, hash)),
+        PSA_ALG_TLS12_PRF_BASE => Ok((KeyDerivationAlgorithm::Tls12Prf, hash)),
+        PSA_ALG_TLS12_PSK_TO_MS_BASE => Ok((KeyDerivationAlgorithm::Tls12PskToMs, hash)),
+        _ => {
+            error!("Algorithm {:#x} has no native equivalent.", alg);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Converts between Mbed Crypto and native hash algorithm values.
+pub fn convert_psa_hash_algorithm(hash: psa_algorithm_t) -> Result<HashAlgorithm> {
+    match hash {
+        PSA_ALG_MD2 => Ok(HashAlgorithm::Md2),
+        PSA_ALG_MD4 => Ok(HashAlgorithm::Md4),
+        PSA_ALG_MD5 => Ok(HashAlgorithm::Md5),
+        PSA_ALG_RIPEMD160 => Ok(HashAlgorithm::Ripemd160),
+        PSA_ALG_SHA_1 => Ok(HashAlgorithm::Sha1),
+        PSA_ALG_SHA_224 => Ok(HashAlgorithm::Sha224),
+        PSA_ALG_SHA_256 => Ok(HashAlgorithm::Sha256),
+        PSA_ALG_SHA_384 => Ok(HashAlgorithm::Sha384),
+        PSA_ALG_SHA_512 => Ok(HashAlgorithm::Sha512),
+        PSA_ALG_SHA_512_224 => Ok(HashAlgorithm::Sha512224),
+        PSA_ALG_SHA_512_256 => Ok(HashAlgorithm::Sha512256),
+        PSA_ALG_SHA3_224 => Ok(HashAlgorithm::Sha3224),
+        PSA_ALG_SHA3_256 => Ok(HashAlgorithm::Sha3256),
+        PSA_ALG_SHA3_384 => Ok(HashAlgorithm::Sha3384),
+        PSA_ALG_SHA3_512 => Ok(HashAlgorithm::Sha3512),
+        _ => {
+            error!("Hash algorithm {:#x} has no native equivalent.", hash);
+            Err(ResponseStatus::PsaErrorNotSupported)
         }
     }
-    algo_val
 }
 
 /// Converts between native and Mbed Crypto hash algorithm values.
@@ -190,6 +529,8 @@ pub fn convert_status(psa_status: psa_status_t) -> ResponseStatus {
     ResponseStatus::from_u16(psa_status)
 }
 
+const AES_BLOCK_SIZE: usize = 16;
+
 macro_rules! bits_to_bytes {
     ($size:expr) => {
         ($size + 7) / 8
@@ -201,7 +542,9 @@ macro_rules! bits_to_bytes {
 pub fn psa_asymmetric_sign_output_size(key_attrs: &psa_key_attributes_t) -> Result<usize> {
     match key_attrs.core.type_ {
         PSA_KEY_TYPE_RSA_KEYPAIR => Ok(usize::from(bits_to_bytes!(key_attrs.core.bits))),
-        PSA_KEY_TYPE_ECC_KEYPAIR_BASE => Ok(usize::from(bits_to_bytes!(key_attrs.core.bits) * 2)),
+        key_type if is_ecc_key_type(key_type) => {
+            Ok(usize::from(bits_to_bytes!(key_attrs.core.bits)) * 2)
+        }
         _ => Err(ResponseStatus::PsaErrorInvalidArgument),
     }
 }
@@ -219,6 +562,10 @@ pub fn psa_export_public_key_size(key_attrs: &psa_key_attributes_t) -> Result<us
         PSA_KEY_TYPE_RSA_PUBLIC_KEY | PSA_KEY_TYPE_RSA_KEYPAIR => Ok(usize::from(
             export_asn1_int_max_size!(key_attrs.core.bits) + 11,
         )),
+        // Uncompressed point format: 0x04 followed by the x and y coordinates.
+        key_type if is_ecc_key_type(key_type) => {
+            Ok(usize::from(bits_to_bytes!(key_attrs.core.bits)) * 2 + 1)
+        }
         _ => Err(ResponseStatus::PsaErrorInvalidArgument),
     }
 }
@@ -299,3 +646,551 @@ impl AsMut<psa_key_handle_t> for Key<'_> {
         &mut self.0
     }
 }
+
+/// Compute the size of the output of an asymmetric encryption, given the attributes of the key.
+/// Implementing `PSA_ASYMMETRIC_ENCRYPT_OUTPUT_SIZE` as defined in `crypto_sizes.h` (Mbed Crypto).
+pub fn psa_asymmetric_encrypt_output_size(key_attrs: &psa_key_attributes_t) -> Result<usize> {
+    match key_attrs.core.type_ {
+        PSA_KEY_TYPE_RSA_PUBLIC_KEY | PSA_KEY_TYPE_RSA_KEYPAIR => {
+            Ok(usize::from(bits_to_bytes!(key_attrs.core.bits)))
+        }
+        _ => Err(ResponseStatus::PsaErrorInvalidArgument),
+    }
+}
+
+/// Compute the maximum size of the output of an asymmetric decryption, given the attributes of
+/// the key. Implementing `PSA_ASYMMETRIC_DECRYPT_OUTPUT_SIZE` as defined in `crypto_sizes.h`
+/// (Mbed Crypto).
+pub fn psa_asymmetric_decrypt_output_size(key_attrs: &psa_key_attributes_t) -> Result<usize> {
+    match key_attrs.core.type_ {
+        PSA_KEY_TYPE_RSA_KEYPAIR => Ok(usize::from(bits_to_bytes!(key_attrs.core.bits))),
+        _ => Err(ResponseStatus::PsaErrorInvalidArgument),
+    }
+}
+
+/// Get the length of the authentication tag of an AEAD algorithm. Implementing
+/// `PSA_AEAD_TAG_LENGTH` as defined in `crypto_sizes.h` (Mbed Crypto).
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the algorithm is not an AEAD algorithm, as
+/// the key it comes from can then not be used for AEAD operations.
+pub fn psa_aead_tag_length(alg: psa_algorithm_t) -> Result<usize> {
+    if alg & PSA_ALG_CATEGORY_MASK != PSA_ALG_CATEGORY_AEAD {
+        error!("Algorithm {:#x} is not an AEAD algorithm.", alg);
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    Ok(((alg & PSA_ALG_AEAD_TAG_LENGTH_MASK) >> PSA_AEAD_TAG_LENGTH_OFFSET) as usize)
+}
+
+/// Compute the size of the output of an AEAD encryption, given the algorithm and the length of
+/// the plaintext. Implementing `PSA_AEAD_ENCRYPT_OUTPUT_SIZE` as defined in `crypto_sizes.h`
+/// (Mbed Crypto).
+pub fn psa_aead_encrypt_output_size(
+    alg: psa_algorithm_t,
+    plaintext_length: usize,
+) -> Result<usize> {
+    Ok(plaintext_length + psa_aead_tag_length(alg)?)
+}
+
+/// Compute the size of the output of an AEAD decryption, given the algorithm and the length of
+/// the ciphertext. Implementing `PSA_AEAD_DECRYPT_OUTPUT_SIZE` as defined in `crypto_sizes.h`
+/// (Mbed Crypto).
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the ciphertext is shorter than the tag.
+pub fn psa_aead_decrypt_output_size(
+    alg: psa_algorithm_t,
+    ciphertext_length: usize,
+) -> Result<usize> {
+    ciphertext_length
+        .checked_sub(psa_aead_tag_length(alg)?)
+        .ok_or(ResponseStatus::PsaErrorInvalidArgument)
+}
+
+/// Get the size of the digest of a hash algorithm. Implementing `PSA_HASH_SIZE` as defined in
+/// `crypto_sizes.h` (Mbed Crypto).
+pub fn psa_hash_size(hash: psa_algorithm_t) -> Result<usize> {
+    match hash {
+        PSA_ALG_MD2 | PSA_ALG_MD4 | PSA_ALG_MD5 => Ok(16),
+        PSA_ALG_RIPEMD160 | PSA_ALG_SHA_1 => Ok(20),
+        PSA_ALG_SHA_224 | PSA_ALG_SHA_512_224 | PSA_ALG_SHA3_224 => Ok(28),
+        PSA_ALG_SHA_256 | PSA_ALG_SHA_512_256 | PSA_ALG_SHA3_256 => Ok(32),
+        PSA_ALG_SHA_384 | PSA_ALG_SHA3_384 => Ok(48),
+        PSA_ALG_SHA_512 | PSA_ALG_SHA3_512 => Ok(64),
+        _ => Err(ResponseStatus::PsaErrorInvalidArgument),
+    }
+}
+
+/// Get the hash algorithm of a signature algorithm, to hash messages before signing them.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the algorithm is not a signature algorithm
+/// and `ResponseStatus::PsaErrorInvalidArgument` if it does not specify a hash algorithm.
+pub fn psa_sign_hash_algorithm(alg: psa_algorithm_t) -> Result<psa_algorithm_t> {
+    if alg & PSA_ALG_CATEGORY_MASK != PSA_ALG_CATEGORY_SIGN {
+        error!("Algorithm {:#x} is not a signature algorithm.", alg);
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    match alg & PSA_ALG_HASH_MASK {
+        0 => {
+            error!("The key policy does not specify a hash algorithm to hash messages with.");
+            Err(ResponseStatus::PsaErrorInvalidArgument)
+        }
+        hash => Ok(PSA_ALG_CATEGORY_HASH | hash),
+    }
+}
+
+/// Compute the hash of the input with Mbed Crypto.
+pub fn compute_hash(alg: psa_algorithm_t, input: &[u8]) -> Result<Vec<u8>> {
+    let hash_size = psa_hash_size(alg)?;
+    let mut hash = vec![0u8; hash_size];
+    let mut hash_length: usize = 0;
+    let mut operation = get_empty_hash_operation();
+
+    let mut hash_status = unsafe { psa_crypto_binding::psa_hash_setup(&mut operation, alg) };
+    if hash_status == PSA_SUCCESS {
+        hash_status = unsafe {
+            psa_crypto_binding::psa_hash_update(&mut operation, input.as_ptr(), input.len())
+        };
+    }
+    if hash_status == PSA_SUCCESS {
+        hash_status = unsafe {
+            psa_crypto_binding::psa_hash_finish(
+                &mut operation,
+                hash.as_mut_ptr(),
+                hash_size,
+                &mut hash_length,
+            )
+        };
+    }
+
+    if hash_status == PSA_SUCCESS {
+        hash.truncate(hash_length);
+        Ok(hash)
+    } else {
+        unsafe { psa_crypto_binding::psa_hash_abort(&mut operation) };
+        error!("Hash compute status: {}", hash_status);
+        Err(convert_status(hash_status))
+    }
+}
+
+/// Get the key derivation algorithm a key agreement algorithm is followed by, `None` for a raw
+/// key agreement. Implementing `PSA_ALG_KEY_AGREEMENT_GET_KDF` as defined in `crypto_values.h`
+/// (Mbed Crypto).
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the algorithm is not a key agreement
+/// algorithm, as the key it comes from can then not be used for key agreement.
+pub fn psa_key_agreement_kdf(alg: psa_algorithm_t) -> Result<Option<psa_algorithm_t>> {
+    if alg & PSA_ALG_CATEGORY_MASK != PSA_ALG_CATEGORY_KEY_AGREEMENT {
+        error!("Algorithm {:#x} is not a key agreement algorithm.", alg);
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    match alg & PSA_ALG_KEY_DERIVATION_MASK {
+        0 => Ok(None),
+        kdf => Ok(Some(PSA_ALG_CATEGORY_KEY_DERIVATION | kdf)),
+    }
+}
+
+/// Get the input steps the salt and the info of a key derivation are given as, for a key
+/// derivation algorithm: the salt and info of HKDF, or the seed and label of the TLS 1.2 PRF.
+pub fn psa_key_derivation_input_steps(
+    kdf: psa_algorithm_t,
+) -> Result<(psa_key_derivation_step_t, psa_key_derivation_step_t)> {
+    match kdf & !PSA_ALG_HASH_MASK {
+        PSA_ALG_HKDF_BASE => Ok((PSA_KEY_DERIVATION_INPUT_SALT, PSA_KEY_DERIVATION_INPUT_INFO)),
+        PSA_ALG_TLS12_PRF_BASE | PSA_ALG_TLS12_PSK_TO_MS_BASE => Ok((
+            PSA_KEY_DERIVATION_INPUT_SEED,
+            PSA_KEY_DERIVATION_INPUT_LABEL,
+        )),
+        _ => {
+            error!(
+                "Key derivation algorithm {:#x} is not supported by this provider.",
+                kdf
+            );
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Set up a key derivation operation with the key derivation algorithm of the base key, and feed
+/// it the salt, the base key and the info, in the order the algorithm expects them. Empty salt and
+/// info are not given. The operation is left to the caller to abort, whether this fails or not.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the algorithm is not a key derivation
+/// algorithm, as the base key can then not be used for key derivation.
+pub fn setup_key_derivation(
+    operation: &mut psa_key_derivation_operation_t,
+    alg: psa_algorithm_t,
+    base_key: psa_key_handle_t,
+    salt: &[u8],
+    info: &[u8],
+) -> Result<()> {
+    if alg & PSA_ALG_CATEGORY_MASK != PSA_ALG_CATEGORY_KEY_DERIVATION {
+        error!("Algorithm {:#x} is not a key derivation algorithm.", alg);
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    let (salt_step, info_step) = psa_key_derivation_input_steps(alg)?;
+
+    let mut derivation_status =
+        unsafe { psa_crypto_binding::psa_key_derivation_setup(operation, alg) };
+    if derivation_status == PSA_SUCCESS && !salt.is_empty() {
+        derivation_status = unsafe {
+            psa_crypto_binding::psa_key_derivation_input_bytes(
+                operation,
+                salt_step,
+                salt.as_ptr(),
+                salt.len(),
+            )
+        };
+    }
+    if derivation_status == PSA_SUCCESS {
+        derivation_status = unsafe {
+            psa_crypto_binding::psa_key_derivation_input_key(
+                operation,
+                PSA_KEY_DERIVATION_INPUT_SECRET,
+                base_key,
+            )
+        };
+    }
+    if derivation_status == PSA_SUCCESS && !info.is_empty() {
+        derivation_status = unsafe {
+            psa_crypto_binding::psa_key_derivation_input_bytes(
+                operation,
+                info_step,
+                info.as_ptr(),
+                info.len(),
+            )
+        };
+    }
+
+    if derivation_status == PSA_SUCCESS {
+        Ok(())
+    } else {
+        error!("Key derivation setup status: {}", derivation_status);
+        Err(convert_status(derivation_status))
+    }
+}
+
+/// Compute the size of the shared secret of a raw key agreement, given the attributes of the
+/// private key. Implementing `PSA_RAW_KEY_AGREEMENT_OUTPUT_SIZE` as defined in `crypto_sizes.h`
+/// (Mbed Crypto) for ECDH.
+pub fn psa_raw_key_agreement_output_size(key_attrs: &psa_key_attributes_t) -> Result<usize> {
+    match key_attrs.core.type_ {
+        key_type if is_ecc_key_type(key_type) => {
+            Ok(usize::from(bits_to_bytes!(key_attrs.core.bits)))
+        }
+        _ => Err(ResponseStatus::PsaErrorInvalidArgument),
+    }
+}
+
+/// Compute the size of a MAC, given the MAC algorithm. Implementing `PSA_MAC_FINAL_SIZE` as
+/// defined in `crypto_sizes.h` (Mbed Crypto), for HMAC and CMAC without truncation.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the algorithm is not a MAC algorithm, as
+/// the key it comes from can then not be used for MAC operations.
+pub fn psa_mac_output_size(alg: psa_algorithm_t) -> Result<usize> {
+    if alg & PSA_ALG_CATEGORY_MASK != PSA_ALG_CATEGORY_MAC {
+        error!("Algorithm {:#x} is not a MAC algorithm.", alg);
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    match alg {
+        PSA_ALG_CMAC => Ok(AES_BLOCK_SIZE),
+        _ if alg & !PSA_ALG_HASH_MASK == PSA_ALG_HMAC_BASE => {
+            psa_hash_size(PSA_ALG_CATEGORY_HASH | (alg & PSA_ALG_HASH_MASK))
+        }
+        _ => {
+            error!(
+                "MAC algorithm {:#x} is not supported by this provider.",
+                alg
+            );
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn attributes(
+        key_type: KeyType,
+        ecc_curve: Option<EccCurve>,
+        sign: SignAlgorithm,
+    ) -> KeyAttributes {
+        KeyAttributes {
+            key_type,
+            ecc_curve,
+            algorithm: Algorithm::sign(sign, Some(HashAlgorithm::Sha256)),
+            key_size: 256,
+            permit_sign: true,
+            permit_verify: true,
+            permit_export: true,
+            permit_derive: false,
+            permit_encrypt: false,
+            permit_decrypt: false,
+        }
+    }
+
+    #[test]
+    fn ecc_key_attributes_round_trip() {
+        for curve in &[
+            EccCurve::Secp256r1,
+            EccCurve::Secp384r1,
+            EccCurve::Secp521r1,
+        ] {
+            let attrs = attributes(KeyType::EccKeypair, Some(*curve), SignAlgorithm::Ecdsa);
+            let psa_attrs = convert_key_attributes(&attrs, 1).unwrap();
+            assert_eq!(convert_psa_key_attributes(&psa_attrs).unwrap(), attrs);
+        }
+    }
+
+    #[test]
+    fn rsa_pss_algorithm() {
+        let alg = Algorithm::sign(SignAlgorithm::RsaPss, Some(HashAlgorithm::Sha384));
+        let psa_alg = convert_algorithm(&alg).unwrap();
+        assert_eq!(
+            psa_alg,
+            PSA_ALG_RSA_PSS_BASE | (PSA_ALG_SHA_384 & PSA_ALG_HASH_MASK)
+        );
+        assert_eq!(convert_psa_algorithm(psa_alg).unwrap(), alg);
+
+        assert_eq!(
+            convert_algorithm(&Algorithm::sign(SignAlgorithm::RsaPss, None)).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+    }
+
+    #[test]
+    fn rsa_encryption_algorithms() {
+        let alg = Algorithm::asymmetric_encryption(
+            AsymmetricEncryptionAlgorithm::RsaOaep,
+            Some(HashAlgorithm::Sha256),
+        );
+        let psa_alg = convert_algorithm(&alg).unwrap();
+        assert_eq!(
+            psa_alg,
+            PSA_ALG_RSA_OAEP_BASE | (PSA_ALG_SHA_256 & PSA_ALG_HASH_MASK)
+        );
+        assert_eq!(convert_psa_algorithm(psa_alg).unwrap(), alg);
+
+        let alg =
+            Algorithm::asymmetric_encryption(AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt, None);
+        assert_eq!(convert_algorithm(&alg).unwrap(), PSA_ALG_RSA_PKCS1V15_CRYPT);
+        assert_eq!(
+            convert_psa_algorithm(PSA_ALG_RSA_PKCS1V15_CRYPT).unwrap(),
+            alg
+        );
+    }
+
+    #[test]
+    fn ecc_key_type_without_curve() {
+        let attrs = attributes(KeyType::EccPublicKey, None, SignAlgorithm::Ecdsa);
+        assert_eq!(
+            convert_key_attributes(&attrs, 1).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+    }
+
+    #[test]
+    fn unsupported_curve() {
+        let attrs = attributes(
+            KeyType::EccKeypair,
+            Some(EccCurve::Secp256k1),
+            SignAlgorithm::Ecdsa,
+        );
+        assert_eq!(
+            convert_key_attributes(&attrs, 1).unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+    }
+
+    #[test]
+    fn ecc_output_sizes() {
+        let attrs = attributes(
+            KeyType::EccKeypair,
+            Some(EccCurve::Secp521r1),
+            SignAlgorithm::DeterministicEcdsa,
+        );
+        let mut psa_attrs = convert_key_attributes(&attrs, 1).unwrap();
+        psa_attrs.core.bits = 521;
+        assert_eq!(psa_asymmetric_sign_output_size(&psa_attrs).unwrap(), 132);
+        assert_eq!(psa_export_public_key_size(&psa_attrs).unwrap(), 133);
+    }
+
+    #[test]
+    fn aead_key_attributes_round_trip() {
+        for (key_type, aead) in &[
+            (KeyType::Aes, AeadAlgorithm::Ccm),
+            (KeyType::Aes, AeadAlgorithm::Gcm),
+            (KeyType::Chacha20, AeadAlgorithm::Chacha20Poly1305),
+        ] {
+            let attrs = KeyAttributes {
+                key_type: *key_type,
+                ecc_curve: None,
+                algorithm: Algorithm::aead(*aead),
+                key_size: 256,
+                permit_sign: false,
+                permit_verify: false,
+                permit_export: false,
+                permit_derive: false,
+                permit_encrypt: true,
+                permit_decrypt: true,
+            };
+            let psa_attrs = convert_key_attributes(&attrs, 1).unwrap();
+            assert_eq!(convert_psa_key_attributes(&psa_attrs).unwrap(), attrs);
+        }
+    }
+
+    #[test]
+    fn aead_output_sizes() {
+        assert_eq!(psa_aead_tag_length(PSA_ALG_GCM).unwrap(), 16);
+        assert_eq!(psa_aead_encrypt_output_size(PSA_ALG_GCM, 32).unwrap(), 48);
+        assert_eq!(psa_aead_decrypt_output_size(PSA_ALG_GCM, 48).unwrap(), 32);
+        assert_eq!(
+            psa_aead_decrypt_output_size(PSA_ALG_GCM, 15).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+        assert_eq!(
+            psa_aead_tag_length(PSA_ALG_RSA_PKCS1V15_CRYPT).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn sign_hash_algorithm() {
+        let alg = convert_algorithm(&Algorithm::sign(
+            SignAlgorithm::RsaPkcs1v15Sign,
+            Some(HashAlgorithm::Sha384),
+        ))
+        .unwrap();
+        assert_eq!(psa_sign_hash_algorithm(alg).unwrap(), PSA_ALG_SHA_384);
+
+        let alg =
+            convert_algorithm(&Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None)).unwrap();
+        assert_eq!(
+            psa_sign_hash_algorithm(alg).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+        assert_eq!(
+            psa_sign_hash_algorithm(PSA_ALG_HMAC_BASE | (PSA_ALG_SHA_256 & PSA_ALG_HASH_MASK))
+                .unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn mac_algorithms() {
+        for hash in &[
+            HashAlgorithm::Md5,
+            HashAlgorithm::Sha1,
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Sha3256,
+        ] {
+            let alg = Algorithm::mac(MacAlgorithm::Hmac, Some(*hash));
+            let psa_alg = convert_algorithm(&alg).unwrap();
+            assert_eq!(convert_psa_algorithm(psa_alg).unwrap(), alg);
+            assert_eq!(
+                psa_mac_output_size(psa_alg).unwrap(),
+                psa_hash_size(convert_hash_algorithm(*hash)).unwrap()
+            );
+        }
+
+        let alg = Algorithm::mac(MacAlgorithm::Cmac, None);
+        assert_eq!(convert_algorithm(&alg).unwrap(), PSA_ALG_CMAC);
+        assert_eq!(convert_psa_algorithm(PSA_ALG_CMAC).unwrap(), alg);
+        assert_eq!(psa_mac_output_size(PSA_ALG_CMAC).unwrap(), 16);
+
+        assert_eq!(
+            convert_algorithm(&Algorithm::mac(MacAlgorithm::Hmac, None)).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+        assert_eq!(
+            psa_mac_output_size(PSA_ALG_GCM).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn key_agreement_algorithms() {
+        let raw = Algorithm::key_agreement(KeyAgreementAlgorithm::Ecdh, None, None);
+        let alg = convert_algorithm(&raw).unwrap();
+        assert_eq!(alg, PSA_ALG_ECDH);
+        assert_eq!(psa_key_agreement_kdf(alg).unwrap(), None);
+        assert_eq!(convert_psa_algorithm(alg).unwrap(), raw);
+
+        let hkdf = Algorithm::key_agreement(
+            KeyAgreementAlgorithm::Ecdh,
+            Some(KeyDerivationAlgorithm::Hkdf),
+            Some(HashAlgorithm::Sha256),
+        );
+        let alg = convert_algorithm(&hkdf).unwrap();
+        assert_eq!(alg, PSA_ALG_ECDH | PSA_ALG_HKDF_BASE | 0x09);
+        let kdf = psa_key_agreement_kdf(alg).unwrap().unwrap();
+        assert_eq!(kdf, PSA_ALG_HKDF_BASE | 0x09);
+        assert_eq!(
+            psa_key_derivation_input_steps(kdf).unwrap(),
+            (PSA_KEY_DERIVATION_INPUT_SALT, PSA_KEY_DERIVATION_INPUT_INFO)
+        );
+        assert_eq!(convert_psa_algorithm(alg).unwrap(), hkdf);
+
+        assert_eq!(
+            convert_algorithm(&Algorithm::key_agreement(
+                KeyAgreementAlgorithm::Ecdh,
+                Some(KeyDerivationAlgorithm::Hkdf),
+                None,
+            ))
+            .unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+        assert_eq!(
+            psa_key_agreement_kdf(PSA_ALG_CMAC).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn key_derivation_algorithms() {
+        let hkdf =
+            Algorithm::key_derivation(KeyDerivationAlgorithm::Hkdf, Some(HashAlgorithm::Sha256));
+        let alg = convert_algorithm(&hkdf).unwrap();
+        assert_eq!(alg, PSA_ALG_HKDF_BASE | 0x09);
+        assert_eq!(convert_psa_algorithm(alg).unwrap(), hkdf);
+
+        let prf = Algorithm::key_derivation(
+            KeyDerivationAlgorithm::Tls12Prf,
+            Some(HashAlgorithm::Sha384),
+        );
+        let alg = convert_algorithm(&prf).unwrap();
+        assert_eq!(
+            psa_key_derivation_input_steps(alg).unwrap(),
+            (
+                PSA_KEY_DERIVATION_INPUT_SEED,
+                PSA_KEY_DERIVATION_INPUT_LABEL
+            )
+        );
+        assert_eq!(convert_psa_algorithm(alg).unwrap(), prf);
+
+        assert_eq!(
+            convert_algorithm(&Algorithm::key_derivation(
+                KeyDerivationAlgorithm::Pbkdf2Hmac,
+                Some(HashAlgorithm::Sha256),
+            ))
+            .unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+        assert_eq!(
+            convert_psa_key_type(convert_key_type(KeyType::Derive, None).unwrap()).unwrap(),
+            KeyType::Derive
+        );
+    }
+}
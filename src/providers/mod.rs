@@ -44,17 +44,47 @@ pub struct ProviderConfig {
     pub key_id_manager: String,
     pub library_path: Option<String>,
     pub slot_number: Option<usize>,
+    pub token_label: Option<String>,
+    pub token_serial_number: Option<String>,
+    pub token_manufacturer: Option<String>,
+    pub token_model: Option<String>,
     pub user_pin: Option<String>,
+    pub user_pin_file: Option<String>,
+    pub user_pin_env_var: Option<String>,
+    pub user_pin_credential: Option<String>,
+    pub user_pin_command: Option<Vec<String>>,
+    pub min_sessions: Option<usize>,
+    pub max_sessions: Option<usize>,
+    pub rebuild_key_id_mappings: Option<bool>,
+    pub adopt_key: Option<Vec<AdoptKeyConfig>>,
+}
+
+/// Key created on a token outside of the service, given to an application. Its objects are
+/// selected either by label or by identifier.
+#[derive(Deserialize, Debug)]
+pub struct AdoptKeyConfig {
+    pub app_name: String,
+    pub key_name: String,
+    pub object_label: Option<String>,
+    pub object_id: Option<Vec<u8>>,
 }
 
 use crate::authenticators::ApplicationName;
 use parsec_interface::operations::{
-    OpAsymSign, OpAsymVerify, OpCreateKey, OpDestroyKey, OpExportPublicKey, OpImportKey,
-    OpListOpcodes, OpListProviders, OpPing, ProviderInfo, ResultAsymSign, ResultAsymVerify,
-    ResultCreateKey, ResultDestroyKey, ResultExportPublicKey, ResultImportKey, ResultListOpcodes,
-    ResultListProviders, ResultPing,
+    OpAeadDecrypt, OpAeadEncrypt, OpAsymSign, OpAsymSignMessage, OpAsymVerify, OpAsymVerifyMessage,
+    OpAsymmetricDecrypt, OpAsymmetricEncrypt, OpCreateKey, OpDestroyKey, OpExportPublicKey,
+    OpGenerateRandom, OpGrantKey, OpHashCompare, OpHashCompute, OpImportKey,
+    OpKeyAgreementDeriveKey, OpKeyDerivationOutputBytes, OpKeyDerivationOutputKey, OpListKeys,
+    OpListOpcodes, OpListProviders, OpMacCompute, OpMacVerify, OpPing, OpRawKeyAgreement,
+    OpRevokeKey, ProviderInfo, ResultAeadDecrypt, ResultAeadEncrypt, ResultAsymSign,
+    ResultAsymSignMessage, ResultAsymVerify, ResultAsymVerifyMessage, ResultAsymmetricDecrypt,
+    ResultAsymmetricEncrypt, ResultCreateKey, ResultDestroyKey, ResultExportPublicKey,
+    ResultGenerateRandom, ResultGrantKey, ResultHashCompare, ResultHashCompute, ResultImportKey,
+    ResultKeyAgreementDeriveKey, ResultKeyDerivationOutputBytes, ResultKeyDerivationOutputKey,
+    ResultListKeys, ResultListOpcodes, ResultListProviders, ResultMacCompute, ResultMacVerify,
+    ResultPing, ResultRawKeyAgreement, ResultRevokeKey,
 };
-use parsec_interface::requests::{ResponseStatus, Result};
+use parsec_interface::requests::{Opcode, ResponseStatus, Result};
 
 /// Definition of the interface that a provider must implement to
 /// be linked into the service through a backend handler.
@@ -72,6 +102,13 @@ pub trait Provide {
     /// List the opcodes supported by the current provider.
     fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes>;
 
+    /// Returns the ID of the provider that operations with this opcode are forwarded to, if the
+    /// current provider does not execute them itself. The backend handler authorizes forwarded
+    /// operations against that provider.
+    fn forwarded_to(&self, _opcode: Opcode) -> Option<ProviderID> {
+        None
+    }
+
     /// Execute a Ping operation to get the version minor and version major information.
     ///
     /// # Errors
@@ -82,6 +119,11 @@ pub trait Provide {
         Err(ResponseStatus::UnsupportedOperation)
     }
 
+    /// List the keys owned by the application in the current provider, with their attributes.
+    fn list_keys(&self, _app_name: ApplicationName, _op: OpListKeys) -> Result<ResultListKeys> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
     /// Execute a CreateKey operation.
     fn create_key(&self, _app_name: ApplicationName, _op: OpCreateKey) -> Result<ResultCreateKey> {
         Err(ResponseStatus::UnsupportedOperation)
@@ -124,4 +166,145 @@ pub trait Provide {
     ) -> Result<ResultAsymVerify> {
         Err(ResponseStatus::UnsupportedOperation)
     }
+
+    /// Execute a GrantKey operation. The application has to own the key and grants some of its
+    /// usages to another application.
+    fn grant_key(&self, _app_name: ApplicationName, _op: OpGrantKey) -> Result<ResultGrantKey> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute a RevokeKey operation, removing a grant previously given by the application.
+    fn revoke_key(&self, _app_name: ApplicationName, _op: OpRevokeKey) -> Result<ResultRevokeKey> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an AsymmetricEncrypt operation, with the algorithm permitted by the key policy. The
+    /// salt is used as label for RSA-OAEP.
+    fn asym_encrypt(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAsymmetricEncrypt,
+    ) -> Result<ResultAsymmetricEncrypt> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an AsymmetricDecrypt operation, with the algorithm permitted by the key policy.
+    fn asym_decrypt(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAsymmetricDecrypt,
+    ) -> Result<ResultAsymmetricDecrypt> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an authenticated encryption with additional data operation.
+    fn aead_encrypt(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAeadEncrypt,
+    ) -> Result<ResultAeadEncrypt> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an authenticated decryption with additional data operation.
+    fn aead_decrypt(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAeadDecrypt,
+    ) -> Result<ResultAeadDecrypt> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Compute the MAC of a message.
+    fn mac_compute(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpMacCompute,
+    ) -> Result<ResultMacCompute> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Verify the MAC of a message.
+    fn mac_verify(&self, _app_name: ApplicationName, _op: OpMacVerify) -> Result<ResultMacVerify> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Compute the hash of a message.
+    fn hash_compute(&self, _op: OpHashCompute) -> Result<ResultHashCompute> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Compare the hash of a message with an expected hash.
+    fn hash_compare(&self, _op: OpHashCompare) -> Result<ResultHashCompare> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an AsymSignMessage operation. The message is hashed with the hash algorithm of the
+    /// key policy before being signed.
+    fn asym_sign_message(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAsymSignMessage,
+    ) -> Result<ResultAsymSignMessage> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Execute an AsymVerifyMessage operation. The message is hashed with the hash algorithm of the
+    /// key policy before the signature is verified.
+    fn asym_verify_message(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpAsymVerifyMessage,
+    ) -> Result<ResultAsymVerifyMessage> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Perform a raw key agreement between a private key stored in the provider and a peer public
+    /// key, and return the shared secret.
+    fn raw_key_agreement(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpRawKeyAgreement,
+    ) -> Result<ResultRawKeyAgreement> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Perform a key agreement between a private key stored in the provider and a peer public key,
+    /// and derive a new key from the shared secret with the key derivation algorithm of the private
+    /// key. The new key is stored in the provider, the shared secret never leaves it.
+    fn key_agreement_derive_key(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpKeyAgreementDeriveKey,
+    ) -> Result<ResultKeyAgreementDeriveKey> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Derive a new key from a base key stored in the provider, with the key derivation algorithm of
+    /// the base key. The new key is stored in the provider.
+    fn key_derivation_output_key(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpKeyDerivationOutputKey,
+    ) -> Result<ResultKeyDerivationOutputKey> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Derive bytes from a base key stored in the provider, with the key derivation algorithm of the
+    /// base key, and return them. Only meant for non-sensitive material such as public nonces.
+    fn key_derivation_output_bytes(
+        &self,
+        _app_name: ApplicationName,
+        _op: OpKeyDerivationOutputBytes,
+    ) -> Result<ResultKeyDerivationOutputBytes> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
+
+    /// Generate the given number of random bytes.
+    ///
+    /// The size of the request is checked against the configured maximum by the backend handler
+    /// before reaching the provider.
+    fn generate_random(&self, _op: OpGenerateRandom) -> Result<ResultGenerateRandom> {
+        Err(ResponseStatus::UnsupportedOperation)
+    }
 }
@@ -14,35 +14,63 @@
 // limitations under the License.
 use super::Provide;
 use crate::authenticators::ApplicationName;
-use crate::key_id_managers::{KeyTriple, ManageKeyIDs};
+use crate::key_id_managers::{self, KeyTriple, KeyUsage, ManageKeyIDs};
 use log::{error, info, warn};
 use parsec_interface::operations::key_attributes::*;
-use parsec_interface::operations::ProviderInfo;
+use parsec_interface::operations::{KeyInfo, ProviderInfo};
+use parsec_interface::operations::{OpAeadDecrypt, ResultAeadDecrypt};
+use parsec_interface::operations::{OpAeadEncrypt, ResultAeadEncrypt};
 use parsec_interface::operations::{OpAsymSign, ResultAsymSign};
+use parsec_interface::operations::{OpAsymSignMessage, ResultAsymSignMessage};
 use parsec_interface::operations::{OpAsymVerify, ResultAsymVerify};
+use parsec_interface::operations::{OpAsymVerifyMessage, ResultAsymVerifyMessage};
+use parsec_interface::operations::{OpAsymmetricDecrypt, ResultAsymmetricDecrypt};
+use parsec_interface::operations::{OpAsymmetricEncrypt, ResultAsymmetricEncrypt};
 use parsec_interface::operations::{OpCreateKey, ResultCreateKey};
 use parsec_interface::operations::{OpDestroyKey, ResultDestroyKey};
 use parsec_interface::operations::{OpExportPublicKey, ResultExportPublicKey};
+use parsec_interface::operations::{OpGenerateRandom, ResultGenerateRandom};
+use parsec_interface::operations::{OpGrantKey, ResultGrantKey};
+use parsec_interface::operations::{OpHashCompare, ResultHashCompare};
+use parsec_interface::operations::{OpHashCompute, ResultHashCompute};
 use parsec_interface::operations::{OpImportKey, ResultImportKey};
+use parsec_interface::operations::{OpListKeys, ResultListKeys};
 use parsec_interface::operations::{OpListOpcodes, ResultListOpcodes};
+use parsec_interface::operations::{OpMacCompute, ResultMacCompute};
+use parsec_interface::operations::{OpMacVerify, ResultMacVerify};
+use parsec_interface::operations::{OpRawKeyAgreement, ResultRawKeyAgreement};
+use parsec_interface::operations::{OpRevokeKey, ResultRevokeKey};
 use parsec_interface::requests::{Opcode, ProviderID, ResponseStatus, Result};
 use pkcs11::types::{
-    CKF_OS_LOCKING_OK, CKF_RW_SESSION, CKF_SERIAL_SESSION, CKR_OK, CKR_SIGNATURE_INVALID, CKU_USER,
-    CK_ATTRIBUTE, CK_C_INITIALIZE_ARGS, CK_MECHANISM, CK_OBJECT_HANDLE, CK_SESSION_HANDLE,
-    CK_SLOT_ID,
+    CKF_OS_LOCKING_OK, CKR_ATTRIBUTE_TYPE_INVALID, CKR_ENCRYPTED_DATA_INVALID,
+    CKR_KEY_FUNCTION_NOT_PERMITTED, CKR_MECHANISM_INVALID, CKR_MECHANISM_PARAM_INVALID,
+    CKR_SIGNATURE_INVALID, CKR_SIGNATURE_LEN_RANGE, CK_ATTRIBUTE, CK_ATTRIBUTE_TYPE, CK_BBOOL,
+    CK_C_INITIALIZE_ARGS, CK_ECDH1_DERIVE_PARAMS, CK_GCM_PARAMS, CK_KEY_TYPE, CK_MECHANISM,
+    CK_MECHANISM_TYPE, CK_OBJECT_HANDLE, CK_RSA_PKCS_OAEP_PARAMS, CK_RSA_PKCS_PSS_PARAMS,
+    CK_SLOT_ID, CK_TOKEN_INFO, CK_ULONG, CK_VOID_PTR,
 };
 use pkcs11::Ctx;
 use serde::{Deserialize, Serialize};
 use serde_asn1_der::asn1_wrapper::*;
 use std::collections::HashSet;
-use std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
+use zeroize::Zeroizing;
 extern crate num_bigint_dig as num_bigint;
 use num_bigint::{BigInt, Sign};
 
+mod pin;
+mod session;
+mod utils;
+
+pub use pin::PinSource;
+use session::{ReadWriteSession, Session, SessionPool};
+
 type LocalIdStore = HashSet<[u8; 4]>;
 
-const SUPPORTED_OPCODES: [Opcode; 7] = [
+const SUPPORTED_OPCODES: [Opcode; 22] = [
     Opcode::CreateKey,
     Opcode::DestroyKey,
     Opcode::AsymSign,
@@ -50,29 +78,62 @@ const SUPPORTED_OPCODES: [Opcode; 7] = [
     Opcode::ImportKey,
     Opcode::ExportPublicKey,
     Opcode::ListOpcodes,
+    Opcode::GrantKey,
+    Opcode::RevokeKey,
+    Opcode::ListKeys,
+    Opcode::AsymmetricEncrypt,
+    Opcode::AsymmetricDecrypt,
+    Opcode::AeadEncrypt,
+    Opcode::AeadDecrypt,
+    Opcode::MacCompute,
+    Opcode::MacVerify,
+    Opcode::HashCompute,
+    Opcode::HashCompare,
+    Opcode::AsymSignMessage,
+    Opcode::AsymVerifyMessage,
+    Opcode::RawKeyAgreement,
+    Opcode::GenerateRandom,
 ];
 
-// Public exponent value for all RSA keys.
+// Maximum number of object handles read at once when searching the token.
+const FIND_OBJECTS_BATCH: CK_ULONG = 16;
+
+// Minimum time between two attempts to recover a lost token.
+const RECOVERY_INTERVAL: Duration = Duration::from_secs(1);
+
+// Public exponent value for all RSA keys. The key attributes do not carry one, this is the PSA
+// default.
 const PUBLIC_EXPONENT: [u8; 3] = [0x01, 0x00, 0x01];
 
 pub struct Pkcs11Provider {
     key_id_store: Arc<RwLock<dyn ManageKeyIDs + Send + Sync>>,
-    // TODO: the local ID store is currently only used to prevent creating a key that does not
-    // exist, it should also act as a cache for non-desctrucitve operations. Same for Mbed Crypto.
+    // Key IDs in use, to prevent creating a key with an existing key ID.
     local_ids: RwLock<LocalIdStore>,
-    // The authentication state is common to all sessions. A counter of logged in sessions is used
-    // to keep track of current logged in sessions, ignore logging in if the user is already
-    // logged in and only log out when no other session is.
-    // The mutex is both used to have interior mutability on the counter and to create a critical
-    // section inside login/logout functions. The clippy warning is ignored here to not have one
-    // Mutex<()> and an AtomicUsize which would make the code more complicated. Maybe a better
-    // way exists.
-    #[allow(clippy::mutex_atomic)]
-    logged_sessions_counter: Mutex<usize>,
-    backend: Ctx,
-    slot_number: CK_SLOT_ID,
-    // Some PKCS 11 devices do not need a pin, the None variant means that.
-    user_pin: Option<String>,
+    // Sessions are kept open, and the user logged in, between operations. The handles of the
+    // key objects found are kept with them.
+    session_pool: SessionPool,
+    // Operations hold the read lock through their session, the library is initialised again
+    // under the write lock when the token is lost.
+    backend: RwLock<Backend>,
+    // Used to find the token again once it is lost.
+    token_matcher: TokenMatcher,
+    // Set once the token is recovered, until its keys are checked.
+    key_ids_to_check: AtomicBool,
+    // Some PKCS 11 devices do not need a pin, the None variant means that. The pin is zeroed when
+    // the provider is dropped.
+    user_pin: Option<Zeroizing<String>>,
+}
+
+// The PKCS 11 library and the slot of the token in use.
+struct Backend {
+    ctx: Ctx,
+    // `None` while the token is unavailable.
+    slot_number: Option<CK_SLOT_ID>,
+    // Incremented each time the token is lost, so that a loss is only handled once by the
+    // operations noticing it.
+    generation: u64,
+    // When the provider last tried to recover the token.
+    last_recovery: Option<Instant>,
 }
 
 // The RSA Public Key data are DER encoded with the following representation:
@@ -86,173 +147,48 @@ struct RsaPublicKey {
     public_exponent: IntegerAsn1,
 }
 
+// The RSA Private Key data are DER encoded with the following representation:
+// RSAPrivateKey ::= SEQUENCE {
+//     version            Version,  -- 0 for two-prime keys
+//     modulus            INTEGER,  -- n
+//     publicExponent     INTEGER,  -- e
+//     privateExponent    INTEGER,  -- d
+//     prime1             INTEGER,  -- p
+//     prime2             INTEGER,  -- q
+//     exponent1          INTEGER,  -- d mod (p-1)
+//     exponent2          INTEGER,  -- d mod (q-1)
+//     coefficient        INTEGER   -- (inverse of q) mod p
+// }
+#[derive(Serialize, Deserialize, Debug)]
+struct RsaPrivateKey {
+    version: IntegerAsn1,
+    modulus: IntegerAsn1,
+    public_exponent: IntegerAsn1,
+    private_exponent: IntegerAsn1,
+    prime_1: IntegerAsn1,
+    prime_2: IntegerAsn1,
+    exponent_1: IntegerAsn1,
+    exponent_2: IntegerAsn1,
+    coefficient: IntegerAsn1,
+}
+
 // For PKCS 11, a key pair consists of two independant public and private keys. Both will share the
-// same key ID.
+// same key ID. Symmetric keys are a single secret key object.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 enum KeyPairType {
     PublicKey,
     PrivateKey,
+    SecretKey,
     Any,
 }
 
-// Representation of a PKCS 11 session.
-struct Session<'a> {
-    provider: &'a Pkcs11Provider,
-    session_handle: CK_SESSION_HANDLE,
-    // This information is necessary to log out when dropped.
-    is_logged_in: bool,
-}
-
-#[derive(PartialEq)]
-enum ReadWriteSession {
-    ReadOnly,
-    ReadWrite,
-}
-
-impl Session<'_> {
-    fn new(provider: &Pkcs11Provider, read_write: ReadWriteSession) -> Result<Session> {
-        info!("Opening session on slot {}", provider.slot_number);
-
-        let mut session_flags = CKF_SERIAL_SESSION;
-        if read_write == ReadWriteSession::ReadWrite {
-            session_flags |= CKF_RW_SESSION;
-        }
-
-        match provider
-            .backend
-            .open_session(provider.slot_number, session_flags, None, None)
-        {
-            Ok(session_handle) => {
-                let mut session = Session {
-                    provider,
-                    session_handle,
-                    is_logged_in: false,
-                };
-
-                // The stress tests revealed bugs when sessions were concurrently running and some
-                // of them where logging in and out during their execution. These bugs seemed to
-                // disappear when *all* sessions are logged in by default.
-                // See https://github.com/opendnssec/SoftHSMv2/issues/509 for reference.
-                // This has security implications and should be disclosed.
-                session.login()?;
-
-                Ok(session)
-            }
-            Err(e) => {
-                error!(
-                    "Error opening session for slot {}: {}.",
-                    provider.slot_number, e
-                );
-                Err(ResponseStatus::PsaErrorCommunicationFailure)
-            }
-        }
-    }
-
-    fn session_handle(&self) -> CK_SESSION_HANDLE {
-        self.session_handle
-    }
-
-    fn login(&mut self) -> Result<()> {
-        #[allow(clippy::mutex_atomic)]
-        let mut logged_sessions_counter = self
-            .provider
-            .logged_sessions_counter
-            .lock()
-            .expect("Error while locking mutex.");
-
-        if self.is_logged_in {
-            info!(
-                "This session ({}) has already requested authentication.",
-                self.session_handle
-            );
-            Ok(())
-        } else if *logged_sessions_counter > 0 {
-            info!(
-                "Logging in ignored as {} sessions are already requiring authentication.",
-                *logged_sessions_counter
-            );
-            *logged_sessions_counter += 1;
-            self.is_logged_in = true;
-            Ok(())
-        } else if let Some(user_pin) = self.provider.user_pin.as_ref() {
-            match self
-                .provider
-                .backend
-                .login(self.session_handle, CKU_USER, Some(user_pin))
-            {
-                Ok(_) => {
-                    info!("Logging in session {}.", self.session_handle);
-                    *logged_sessions_counter += 1;
-                    self.is_logged_in = true;
-                    Ok(())
-                }
-                Err(e) => {
-                    error!("Login operation failed with {}", e);
-                    Err(ResponseStatus::PsaErrorHardwareFailure)
-                }
-            }
-        } else {
-            warn!("Authentication requested but the provider has no user pin set!");
-            Ok(())
-        }
-    }
-
-    fn logout(&mut self) -> Result<()> {
-        #[allow(clippy::mutex_atomic)]
-        let mut logged_sessions_counter = self
-            .provider
-            .logged_sessions_counter
-            .lock()
-            .expect("Error while locking mutex.");
-
-        if !self.is_logged_in {
-            info!("Session {} has already logged out.", self.session_handle);
-            Ok(())
-        } else if *logged_sessions_counter == 0 {
-            info!("The user is already logged out, ignoring.");
-            Ok(())
-        } else if *logged_sessions_counter == 1 {
-            // Only this session requires authentication.
-            match self.provider.backend.logout(self.session_handle) {
-                Ok(_) => {
-                    info!("Logged out in session {}.", self.session_handle);
-                    *logged_sessions_counter -= 1;
-                    self.is_logged_in = false;
-                    Ok(())
-                }
-                Err(e) => {
-                    error!(
-                        "Failed to log out from session {} due to error {}. Continuing...",
-                        self.session_handle, e
-                    );
-                    Err(ResponseStatus::PsaErrorHardwareFailure)
-                }
-            }
-        } else {
-            info!(
-                "{} sessions are still requiring authentication, not logging out.",
-                *logged_sessions_counter
-            );
-            *logged_sessions_counter -= 1;
-            self.is_logged_in = false;
-            Ok(())
-        }
-    }
-}
-
-impl Drop for Session<'_> {
-    fn drop(&mut self) {
-        if self.logout().is_err() {
-            error!("Error while logging out. Continuing...");
-        }
-        match self.provider.backend.close_session(self.session_handle) {
-            Ok(_) => info!("Session {} closed.", self.session_handle),
-            // Treat this as best effort.
-            Err(e) => error!(
-                "Failed to close session {} due to error {}. Continuing...",
-                self.session_handle, e
-            ),
-        }
-    }
+/// Selects the objects of a key created on the token outside of the service, to adopt them.
+#[derive(Debug, Clone)]
+pub enum TokenObject {
+    /// The key objects with this label (`CKA_LABEL`), which must all share the same `CKA_ID`.
+    Label(String),
+    /// The key objects with this identifier (`CKA_ID`).
+    Id(Vec<u8>),
 }
 
 /// Gets a key identifier from the Key ID Manager.
@@ -319,18 +255,8 @@ fn remove_key_id(
     }
 }
 
-fn key_id_exists(key_triple: &KeyTriple, store_handle: &dyn ManageKeyIDs) -> Result<bool> {
-    match store_handle.exists(key_triple) {
-        Ok(val) => Ok(val),
-        Err(string) => {
-            error!("Key ID Manager error: {}", string);
-            Err(ResponseStatus::KeyIDManagerError)
-        }
-    }
-}
-
 impl Pkcs11Provider {
-    /// Creates and initialise a new instance of Pkcs11Provider.
+    /// Creates and initialise a new instance of Pkcs11Provider, opening the sessions of the pool.
     /// Checks if there are not more keys stored in the Key ID Manager than in the PKCS 11 library
     /// and if there are, delete them. Adds Key IDs currently in use in the local IDs store.
     /// Returns `None` if the initialisation failed.
@@ -338,17 +264,37 @@ impl Pkcs11Provider {
         key_id_store: Arc<RwLock<dyn ManageKeyIDs + Send + Sync>>,
         backend: Ctx,
         slot_number: usize,
-        user_pin: Option<String>,
+        token_matcher: TokenMatcher,
+        user_pin: Option<Zeroizing<String>>,
+        session_pool: SessionPool,
     ) -> Option<Pkcs11Provider> {
-        #[allow(clippy::mutex_atomic)]
         let pkcs11_provider = Pkcs11Provider {
             key_id_store,
             local_ids: RwLock::new(HashSet::new()),
-            logged_sessions_counter: Mutex::new(0),
-            backend,
-            slot_number,
+            session_pool,
+            backend: RwLock::new(Backend {
+                ctx: backend,
+                slot_number: Some(slot_number),
+                generation: 0,
+                last_recovery: None,
+            }),
+            token_matcher,
+            key_ids_to_check: AtomicBool::new(false),
             user_pin,
         };
+        {
+            let backend = pkcs11_provider
+                .backend
+                .read()
+                .expect("Backend lock poisoned");
+            if let Err(e) = pkcs11_provider
+                .session_pool
+                .fill(&pkcs11_provider, &backend)
+            {
+                error!("Error opening the sessions of the pool: {}.", e);
+                return None;
+            }
+        }
         {
             // The local scope allows to drop store_handle and local_ids_handle in order to return
             // the pkcs11_provider.
@@ -377,11 +323,7 @@ impl Pkcs11Provider {
                                 continue;
                             }
                         };
-                        match pkcs11_provider.find_key(
-                            session.session_handle(),
-                            key_id,
-                            KeyPairType::Any,
-                        ) {
+                        match pkcs11_provider.find_key(&session, key_id, KeyPairType::Any) {
                             Ok(_) => {
                                 warn!(
                                     "Key {} found in the PKCS 11 library, adding it.",
@@ -419,493 +361,2288 @@ impl Pkcs11Provider {
         Some(pkcs11_provider)
     }
 
-    /// Find the PKCS 11 object handle corresponding to the key ID and the key type (public or
-    /// private key) given as parameters for the current session.
-    fn find_key(
-        &self,
-        session: CK_SESSION_HANDLE,
-        key_id: [u8; 4],
-        key_type: KeyPairType,
-    ) -> Result<CK_OBJECT_HANDLE> {
-        let mut template = vec![CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id)];
-        match key_type {
-            KeyPairType::PublicKey => template.push(
-                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
-                    .with_ck_ulong(&pkcs11::types::CKO_PUBLIC_KEY),
-            ),
-            KeyPairType::PrivateKey => template.push(
-                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
-                    .with_ck_ulong(&pkcs11::types::CKO_PRIVATE_KEY),
-            ),
-            KeyPairType::Any => (),
-        }
-
-        if let Err(e) = self.backend.find_objects_init(session, &template) {
-            error!("Object enumeration init failed with {}", e);
-            Err(ResponseStatus::PsaErrorHardwareFailure)
-        } else {
-            match self.backend.find_objects(session, 1) {
-                Ok(objects) => {
-                    if let Err(e) = self.backend.find_objects_final(session) {
-                        error!("Object enumeration final failed with {}", e);
-                        Err(ResponseStatus::PsaErrorHardwareFailure)
-                    } else if objects.is_empty() {
-                        Err(ResponseStatus::PsaErrorDoesNotExist)
-                    } else {
-                        Ok(objects[0])
-                    }
-                }
-                Err(e) => {
-                    error!("Finding objects failed with {}", e);
-                    Err(ResponseStatus::PsaErrorHardwareFailure)
+    /// Checks, after the token was recovered, that the keys of the PKCS 11 key triple to key ID
+    /// mappings are still on it. The mappings are not deleted, that is only done when the
+    /// provider starts. Returns `false` if the check could not be done yet because the Key ID
+    /// Manager is locked for writing.
+    fn verify_key_ids(&self, session: &Session) -> bool {
+        // The operation which the session is for might already hold the lock.
+        let store_handle = match self.key_id_store.try_read() {
+            Ok(store_handle) => store_handle,
+            Err(_) => return false,
+        };
+        let key_triples = match store_handle.get_all(ProviderID::Pkcs11Provider) {
+            Ok(key_triples) => key_triples,
+            Err(string) => {
+                error!("Key ID Manager error: {}", string);
+                return true;
+            }
+        };
+        for key_triple in key_triples {
+            let key_id = match get_key_id(key_triple, &*store_handle) {
+                Ok(key_id) => key_id,
+                Err(response_status) => {
+                    error!(
+                        "Error getting the Key ID for triple:\n{}\n(error: {}), continuing...",
+                        key_triple, response_status
+                    );
+                    continue;
                 }
+            };
+            match self.find_key(session, key_id, KeyPairType::Any) {
+                Ok(_) => (),
+                Err(ResponseStatus::PsaErrorDoesNotExist) => warn!(
+                    "Key {} not found in the recovered PKCS 11 token, it can only be destroyed.",
+                    key_triple
+                ),
+                Err(e) => error!("Error finding the objects of key {}: {}.", key_triple, e),
             }
         }
-    }
-}
-
-impl Provide for Pkcs11Provider {
-    fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes> {
-        Ok(ResultListOpcodes {
-            opcodes: SUPPORTED_OPCODES.iter().copied().collect(),
-        })
+        info!("Checked the keys of the recovered PKCS 11 token.");
+        true
     }
 
-    fn describe(&self) -> ProviderInfo {
-        ProviderInfo {
-            // Assigned UUID for this provider: 30e39502-eba6-4d60-a4af-c518b7f5e38f
-            uuid: Uuid::parse_str("30e39502-eba6-4d60-a4af-c518b7f5e38f").unwrap(),
-            description: String::from("PKCS #11 provider, interfacing with a PKCS #11 library."),
-            vendor: String::from("OASIS Standard."),
-            version_maj: 0,
-            version_min: 1,
-            version_rev: 0,
-            id: ProviderID::Pkcs11Provider,
+    /// Adds the mappings of the keys found on the token which are missing from the Key ID
+    /// Manager, for example after its mappings were lost. The key triples are read from the
+    /// labels of the objects, those which were not created by the service are ignored.
+    fn rebuild_key_id_mappings(&self) -> Result<()> {
+        let mut found_keys: Vec<(KeyTriple, [u8; 4])> = Vec::new();
+        {
+            let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+            let template = vec![
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE)
+            ];
+            for object in self.find_objects(&session, template)? {
+                // Objects without a readable label were not created by the service.
+                let label =
+                    match self.get_attribute_bytes(&session, object, pkcs11::types::CKA_LABEL) {
+                        Ok(label) => label,
+                        Err(_) => continue,
+                    };
+                let key_triple = match utils::parse_key_label(&label) {
+                    Some((key_triple, _)) => key_triple,
+                    None => continue,
+                };
+                let key_id = self.get_attribute_bytes(&session, object, pkcs11::types::CKA_ID)?;
+                if key_id.len() != 4 {
+                    warn!(
+                        "Object of key {} has an invalid key ID, ignoring it.",
+                        key_triple
+                    );
+                    continue;
+                }
+                let mut dst = [0; 4];
+                dst.copy_from_slice(&key_id);
+                // The objects of a key pair both carry the label.
+                match found_keys.iter().find(|(found, _)| *found == key_triple) {
+                    Some((_, found_id)) if *found_id != dst => warn!(
+                        "Objects of key {} have different key IDs, only keeping the first one.",
+                        key_triple
+                    ),
+                    Some(_) => (),
+                    None => found_keys.push((key_triple, dst)),
+                }
+            }
         }
-    }
 
-    fn create_key(&self, app_name: ApplicationName, op: OpCreateKey) -> Result<ResultCreateKey> {
-        info!("Pkcs11 Provider - Create Key");
-
-        if op.key_attributes.key_type != KeyType::RsaKeypair
-            || op.key_attributes.algorithm != Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None)
-        {
-            error!("The PKCS 11 provider currently only supports creating RSA key pairs for signing and verifying.");
-            return Err(ResponseStatus::UnsupportedOperation);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        for (key_triple, key_id) in found_keys {
+            match store_handle.exists(&key_triple) {
+                Ok(true) => continue,
+                Ok(false) => (),
+                Err(string) => {
+                    error!("Key ID Manager error: {}", string);
+                    return Err(ResponseStatus::KeyIDManagerError);
+                }
+            }
+            if local_ids_handle.contains(&key_id) {
+                warn!(
+                    "The key ID of key {} is used by another key, not adding its mapping.",
+                    key_triple
+                );
+                continue;
+            }
+            if let Err(string) = store_handle.insert(key_triple.clone(), key_id.to_vec()) {
+                error!("Key ID Manager error: {}", string);
+                return Err(ResponseStatus::KeyIDManagerError);
+            }
+            local_ids_handle.insert(key_id);
+            info!("Key {} found on the PKCS 11 token, adding it.", key_triple);
         }
 
-        let key_name = op.key_name;
-        // This should never panic on 32 bits or more machines.
-        let key_size = std::convert::TryFrom::try_from(op.key_attributes.key_size).unwrap();
+        Ok(())
+    }
 
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+    /// Adopts the objects of a key created on the token outside of the service under the key
+    /// triple: a new key ID is mapped to the key triple and written, with the label of the key
+    /// triple, in the `CKA_ID` and `CKA_LABEL` attributes of the objects. Nothing is done if the
+    /// key triple already exists.
+    fn adopt_key(&self, key_triple: KeyTriple, object: &TokenObject) -> Result<()> {
         let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
         let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
-        if key_id_exists(&key_triple, &*store_handle)? {
-            return Err(ResponseStatus::KeyAlreadyExists);
+        match store_handle.exists(&key_triple) {
+            Ok(true) => {
+                info!(
+                    "Key {} already exists, not adopting {:?}.",
+                    key_triple, object
+                );
+                return Ok(());
+            }
+            Ok(false) => (),
+            Err(string) => {
+                error!("Key ID Manager error: {}", string);
+                return Err(ResponseStatus::KeyIDManagerError);
+            }
+        }
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        let find_key_objects =
+            |attribute_type: CK_ATTRIBUTE_TYPE, value: &[u8]| -> Result<Vec<CK_OBJECT_HANDLE>> {
+                let mut objects = Vec::new();
+                for class in &[
+                    pkcs11::types::CKO_PUBLIC_KEY,
+                    pkcs11::types::CKO_PRIVATE_KEY,
+                    pkcs11::types::CKO_SECRET_KEY,
+                ] {
+                    let template = vec![
+                        CK_ATTRIBUTE::new(attribute_type).with_bytes(value),
+                        CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS).with_ck_ulong(class),
+                    ];
+                    objects.append(&mut self.find_objects(&session, template)?);
+                }
+                Ok(objects)
+            };
+        let object_id = match object {
+            TokenObject::Id(object_id) => object_id.clone(),
+            TokenObject::Label(label) => {
+                let mut object_ids = Vec::new();
+                for key_object in find_key_objects(pkcs11::types::CKA_LABEL, label.as_bytes())? {
+                    let object_id =
+                        self.get_attribute_bytes(&session, key_object, pkcs11::types::CKA_ID)?;
+                    if !object_ids.contains(&object_id) {
+                        object_ids.push(object_id);
+                    }
+                }
+                if object_ids.len() > 1 {
+                    error!(
+                        "The key objects labelled \"{}\" have different identifiers.",
+                        label
+                    );
+                    return Err(ResponseStatus::PsaErrorInvalidArgument);
+                }
+                match object_ids.pop() {
+                    Some(object_id) => object_id,
+                    None => {
+                        error!("No key object is labelled \"{}\".", label);
+                        return Err(ResponseStatus::PsaErrorDoesNotExist);
+                    }
+                }
+            }
+        };
+        let key_objects = find_key_objects(pkcs11::types::CKA_ID, &object_id)?;
+        if key_objects.is_empty() {
+            error!("No key object found for {:?}.", object);
+            return Err(ResponseStatus::PsaErrorDoesNotExist);
         }
+        // The objects might be those of a key of the service, found with their old key ID.
+        if object_id.len() == 4 {
+            let mut old_key_id = [0; 4];
+            old_key_id.copy_from_slice(&object_id);
+            self.session_pool.forget_object_handles(old_key_id);
+        }
+
         let key_id = create_key_id(
             key_triple.clone(),
             &mut *store_handle,
             &mut local_ids_handle,
         )?;
-
-        let mech = CK_MECHANISM {
-            mechanism: pkcs11::types::CKM_RSA_PKCS_KEY_PAIR_GEN,
-            pParameter: std::ptr::null_mut(),
-            ulParameterLen: 0,
-        };
-
-        let mut priv_template: Vec<CK_ATTRIBUTE> = Vec::new();
-        let mut pub_template: Vec<CK_ATTRIBUTE> = Vec::new();
-
-        priv_template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_SIGN).with_bool(&pkcs11::types::CK_TRUE));
-        priv_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
-        priv_template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
-
-        pub_template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_VERIFY).with_bool(&pkcs11::types::CK_TRUE));
-        pub_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
-        pub_template.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_PUBLIC_EXPONENT).with_bytes(&PUBLIC_EXPONENT),
-        );
-        pub_template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_MODULUS_BITS).with_ck_ulong(&key_size));
-        pub_template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
-        pub_template.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_PRIVATE).with_bool(&pkcs11::types::CK_FALSE),
-        );
-
-        let session = Session::new(self, ReadWriteSession::ReadWrite).or_else(|err| {
-            error!("Error creating a new session: {}.", err);
-            remove_key_id(
-                &key_triple,
-                key_id,
-                &mut *store_handle,
-                &mut local_ids_handle,
-            )?;
-            Err(err)
-        })?;
-
-        info!(
-            "Generating RSA key pair in session {}",
-            session.session_handle()
-        );
-
-        match self.backend.generate_key_pair(
-            session.session_handle(),
-            &mech,
-            &pub_template,
-            &priv_template,
-        ) {
-            Ok(_key) => Ok(ResultCreateKey {}),
-            Err(e) => {
-                error!("Generate Key Pair operation failed with {}", e);
+        // The purpose of a key created outside of the service is unknown.
+        let label = utils::key_label(&key_triple, None);
+        let template = vec![
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id),
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()),
+        ];
+        for key_object in key_objects.iter() {
+            if let Err(e) = session.backend().set_attribute_value(
+                session.session_handle(),
+                *key_object,
+                &template,
+            ) {
+                let response_status = utils::to_response_status("C_SetAttributeValue", e);
+                // The objects already updated keep the new key ID, they can be selected with it
+                // to adopt them again.
+                error!(
+                    "Failed to adopt {:?}, its objects might now have the identifier {:?}.",
+                    object, key_id
+                );
                 remove_key_id(
                     &key_triple,
                     key_id,
                     &mut *store_handle,
                     &mut local_ids_handle,
                 )?;
-                Err(ResponseStatus::PsaErrorHardwareFailure)
+                return Err(response_status);
             }
         }
-    }
+        info!("Adopted {:?} as key {}.", object, key_triple);
 
-    fn import_key(&self, app_name: ApplicationName, op: OpImportKey) -> Result<ResultImportKey> {
-        info!("Pkcs11 Provider - Import Key");
+        Ok(())
+    }
 
-        if op.key_attributes.key_type != KeyType::RsaPublicKey
-            || op.key_attributes.algorithm != Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None)
+    /// Locks the backend for an operation, trying to recover the token first if it is
+    /// unavailable. Fails with `PsaErrorCommunicationFailure` as long as the token is not back.
+    fn read_backend(&self) -> Result<RwLockReadGuard<Backend>> {
         {
-            error!("The PKCS 11 provider currently only supports importing RSA public key for verifying.");
-            return Err(ResponseStatus::UnsupportedOperation);
+            let backend = self.backend.read().expect("Backend lock poisoned");
+            if backend.slot_number.is_some() {
+                return Ok(backend);
+            }
         }
-
-        let key_name = op.key_name;
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
-        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
-        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
-        if key_id_exists(&key_triple, &*store_handle)? {
-            return Err(ResponseStatus::KeyAlreadyExists);
+        self.recover()?;
+        let backend = self.backend.read().expect("Backend lock poisoned");
+        if backend.slot_number.is_some() {
+            Ok(backend)
+        } else {
+            error!("The PKCS 11 token is unavailable.");
+            Err(ResponseStatus::PsaErrorCommunicationFailure)
         }
-        let key_id = create_key_id(
-            key_triple.clone(),
-            &mut *store_handle,
-            &mut local_ids_handle,
-        )?;
+    }
 
-        let mut template: Vec<CK_ATTRIBUTE> = Vec::new();
+    /// Marks the token as unavailable after an operation noticed it was lost, unless that loss,
+    /// identified by the generation of the backend at the time, was already handled.
+    fn token_lost(&self, generation: u64) {
+        let mut backend = self.backend.write().expect("Backend lock poisoned");
+        if backend.generation != generation {
+            return;
+        }
+        if let Some(slot_number) = backend.slot_number.take() {
+            error!(
+                "The PKCS 11 token in slot {} is unavailable, the provider will try to recover it.",
+                slot_number
+            );
+        }
+        backend.generation += 1;
+        backend.last_recovery = None;
+    }
 
-        let public_key: RsaPublicKey = serde_asn1_der::from_bytes(&op.key_data).unwrap();
+    /// Tries to recover a lost token: initialises the PKCS 11 library again, looks for the token
+    /// with the same criteria as when the provider was built and opens the sessions of the pool.
+    /// Tries at most once every `RECOVERY_INTERVAL`.
+    fn recover(&self) -> Result<()> {
+        let slot_number = {
+            let mut backend = self.backend.write().expect("Backend lock poisoned");
+            if backend.slot_number.is_some() {
+                // Another operation recovered the token.
+                return Ok(());
+            }
+            if let Some(last_recovery) = backend.last_recovery {
+                if last_recovery.elapsed() < RECOVERY_INTERVAL {
+                    error!("The PKCS 11 token is unavailable.");
+                    return Err(ResponseStatus::PsaErrorCommunicationFailure);
+                }
+            }
+            backend.last_recovery = Some(Instant::now());
+            info!("Trying to recover the PKCS 11 token.");
+
+            // Finalising the library closes all the sessions. No operation holds one as the
+            // write lock is taken.
+            self.session_pool.clear();
+            if let Err(e) = backend.ctx.finalize() {
+                info!("Failed to finalise the PKCS 11 library: {}.", e);
+            }
+            if let Err(e) = backend.ctx.initialize(Some(initialize_args())) {
+                error!("Failed to initialise the PKCS 11 library: {}.", e);
+                return Err(ResponseStatus::PsaErrorCommunicationFailure);
+            }
+            let slot_number = match find_slot(&backend.ctx, &self.token_matcher) {
+                Some(slot_number) => slot_number,
+                None => {
+                    error!("The PKCS 11 token is still unavailable.");
+                    return Err(ResponseStatus::PsaErrorCommunicationFailure);
+                }
+            };
+            backend.slot_number = Some(slot_number);
+            if let Err(e) = self.session_pool.fill(self, &backend) {
+                error!("Error opening the sessions of the pool: {}.", e);
+                backend.slot_number = None;
+                self.session_pool.clear();
+                return Err(e);
+            }
+            slot_number
+        };
 
-        let (_, modulus_object) = &public_key.modulus.to_bytes_be();
-        let (_, exponent_object) = &public_key.public_exponent.to_bytes_be();
+        info!(
+            "The PKCS 11 token is available again in slot {}.",
+            slot_number
+        );
+        // The keys are checked with the next session taken out of the pool.
+        self.key_ids_to_check.store(true, Ordering::SeqCst);
+
+        Ok(())
+    }
+
+    /// Find the PKCS 11 object handle corresponding to the key ID and the key type (public or
+    /// private key) given as parameters. The handles found are cached, the operations using them
+    /// run through `with_key_objects`.
+    fn find_key(
+        &self,
+        session: &Session,
+        key_id: [u8; 4],
+        key_type: KeyPairType,
+    ) -> Result<CK_OBJECT_HANDLE> {
+        if let Some(object) = self.session_pool.object_handle(key_id, key_type) {
+            return Ok(object);
+        }
+
+        let mut template = vec![CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id)];
+        match key_type {
+            KeyPairType::PublicKey => template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                    .with_ck_ulong(&pkcs11::types::CKO_PUBLIC_KEY),
+            ),
+            KeyPairType::PrivateKey => template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                    .with_ck_ulong(&pkcs11::types::CKO_PRIVATE_KEY),
+            ),
+            KeyPairType::SecretKey => template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                    .with_ck_ulong(&pkcs11::types::CKO_SECRET_KEY),
+            ),
+            KeyPairType::Any => (),
+        }
+
+        // The search is finalised even if it fails, the session going back to the pool.
+        match self.find_objects(session, template)?.first() {
+            Some(object) => {
+                self.session_pool
+                    .cache_object_handle(key_id, key_type, *object);
+                Ok(*object)
+            }
+            None => Err(ResponseStatus::PsaErrorDoesNotExist),
+        }
+    }
+
+    /// Runs an operation using the objects of a key, found with `find_key`. A cached handle is
+    /// invalid if its object was destroyed outside of the service since it was found, the token
+    /// then failing with `CKR_OBJECT_HANDLE_INVALID` or `CKR_KEY_HANDLE_INVALID`: the handles of
+    /// the key are forgotten and the operation is tried once more, finding the objects again.
+    fn with_key_objects<T>(
+        &self,
+        key_id: [u8; 4],
+        mut operation: impl FnMut() -> Result<T>,
+    ) -> Result<T> {
+        let cached = self.session_pool.has_object_handles(key_id);
+        match operation() {
+            Err(ResponseStatus::PsaErrorDoesNotExist) if cached => {
+                warn!(
+                    "The cached handles of key ID {:?} may be invalid, looking the key up again.",
+                    key_id
+                );
+                self.session_pool.forget_object_handles(key_id);
+                operation()
+            }
+            result => result,
+        }
+    }
+
+    /// Find the handles of all the objects matching the template.
+    fn find_objects(
+        &self,
+        session: &Session,
+        template: Vec<CK_ATTRIBUTE>,
+    ) -> Result<Vec<CK_OBJECT_HANDLE>> {
+        session
+            .backend()
+            .find_objects_init(session.session_handle(), &template)
+            .or_else(|e| Err(utils::to_response_status("C_FindObjectsInit", e)))?;
+
+        let mut objects = Vec::new();
+        let found = loop {
+            match session
+                .backend()
+                .find_objects(session.session_handle(), FIND_OBJECTS_BATCH)
+            {
+                Ok(batch) => {
+                    if batch.is_empty() {
+                        break Ok(());
+                    }
+                    objects.extend(batch);
+                }
+                Err(e) => break Err(utils::to_response_status("C_FindObjects", e)),
+            }
+        };
+        // The search is always finalised, even if it failed.
+        session
+            .backend()
+            .find_objects_final(session.session_handle())
+            .or_else(|e| Err(utils::to_response_status("C_FindObjectsFinal", e)))?;
+
+        found.map(|_| objects)
+    }
+
+    /// Read the value of a byte array attribute of an object.
+    fn get_attribute_bytes(
+        &self,
+        session: &Session,
+        key: CK_OBJECT_HANDLE,
+        attribute_type: CK_ATTRIBUTE_TYPE,
+    ) -> Result<Vec<u8>> {
+        match self.get_optional_attribute_bytes(session, key, attribute_type)? {
+            Some(value) => Ok(value),
+            None => Err(utils::to_response_status(
+                "C_GetAttributeValue",
+                pkcs11::errors::Error::Pkcs11(CKR_ATTRIBUTE_TYPE_INVALID),
+            )),
+        }
+    }
+
+    /// Read the value of a byte array attribute of an object, `None` if the token or the object
+    /// does not have the attribute.
+    fn get_optional_attribute_bytes(
+        &self,
+        session: &Session,
+        key: CK_OBJECT_HANDLE,
+        attribute_type: CK_ATTRIBUTE_TYPE,
+    ) -> Result<Option<Vec<u8>>> {
+        // Get the length of the attribute to retrieve.
+        let mut size_attrs = vec![CK_ATTRIBUTE::new(attribute_type)];
+        let len = match session.backend().get_attribute_value(
+            session.session_handle(),
+            key,
+            &mut size_attrs,
+        ) {
+            Ok((CKR_ATTRIBUTE_TYPE_INVALID, _))
+            | Err(pkcs11::errors::Error::Pkcs11(CKR_ATTRIBUTE_TYPE_INVALID)) => return Ok(None),
+            Ok((rv, attrs)) => {
+                utils::check_rv("C_GetAttributeValue", rv)?;
+                attrs[0].ulValueLen
+            }
+            Err(e) => return Err(utils::to_response_status("C_GetAttributeValue", e)),
+        };
+
+        let mut value: Vec<pkcs11::types::CK_BYTE> = vec![0; len];
+        let mut extract_attrs =
+            vec![CK_ATTRIBUTE::new(attribute_type).with_bytes(value.as_mut_slice())];
+        session
+            .backend()
+            .get_attribute_value(session.session_handle(), key, &mut extract_attrs)
+            .or_else(|e| Err(utils::to_response_status("C_GetAttributeValue", e)))
+            .and_then(|(rv, attrs)| {
+                utils::check_rv("C_GetAttributeValue", rv)?;
+                Ok(Some(attrs[0].get_bytes()))
+            })
+    }
+
+    /// Read the PKCS 11 key type (`CKA_KEY_TYPE`) of an object.
+    fn get_key_type(&self, session: &Session, key: CK_OBJECT_HANDLE) -> Result<CK_KEY_TYPE> {
+        let key_type: CK_KEY_TYPE = 0;
+        let mut template =
+            vec![CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE).with_ck_ulong(&key_type)];
+        session
+            .backend()
+            .get_attribute_value(session.session_handle(), key, &mut template)
+            .or_else(|e| Err(utils::to_response_status("C_GetAttributeValue", e)))
+            .and_then(|(rv, attrs)| {
+                utils::check_rv("C_GetAttributeValue", rv)?;
+                Ok(attrs[0].get_ck_ulong())
+            })
+    }
+
+    /// Read the value of a boolean attribute of an object, such as `CKA_SIGN` or `CKA_ENCRYPT`.
+    fn get_attribute_bool(
+        &self,
+        session: &Session,
+        key: CK_OBJECT_HANDLE,
+        attribute_type: CK_ATTRIBUTE_TYPE,
+    ) -> Result<bool> {
+        let value: CK_BBOOL = pkcs11::types::CK_FALSE;
+        let mut template = vec![CK_ATTRIBUTE::new(attribute_type).with_bool(&value)];
+        session
+            .backend()
+            .get_attribute_value(session.session_handle(), key, &mut template)
+            .or_else(|e| Err(utils::to_response_status("C_GetAttributeValue", e)))
+            .and_then(|(rv, attrs)| {
+                utils::check_rv("C_GetAttributeValue", rv)?;
+                Ok(attrs[0].get_bool())
+            })
+    }
+
+    /// Read the mechanisms (`CKA_ALLOWED_MECHANISMS`) a key is allowed to be used with. An empty
+    /// list means that no restriction was set when creating the key.
+    fn get_allowed_mechanisms(
+        &self,
+        session: &Session,
+        key: CK_OBJECT_HANDLE,
+    ) -> Result<Vec<CK_MECHANISM_TYPE>> {
+        // Tokens or objects without the attribute put no restriction on the mechanisms.
+        let bytes = self
+            .get_optional_attribute_bytes(session, key, pkcs11::types::CKA_ALLOWED_MECHANISMS)?
+            .unwrap_or_default();
+        Ok(utils::mechanisms_from_bytes(&bytes))
+    }
+
+    /// Read the purpose of a key from the label (`CKA_LABEL`) of its object. Keys created before
+    /// the purpose was recorded or outside of the service have none. The objects created by the
+    /// service are not modifiable, so their label can not be changed to widen their policy.
+    fn get_key_purpose(
+        &self,
+        session: &Session,
+        key: CK_OBJECT_HANDLE,
+    ) -> Result<Option<utils::KeyPurpose>> {
+        Ok(self
+            .get_optional_attribute_bytes(session, key, pkcs11::types::CKA_LABEL)?
+            .and_then(|label| utils::parse_key_label(&label))
+            .and_then(|(_, purpose)| purpose))
+    }
+
+    /// Compute the digest of the input with the token.
+    fn digest(&self, session: &Session, hash: HashAlgorithm, input: &[u8]) -> Result<Vec<u8>> {
+        let mech = CK_MECHANISM {
+            mechanism: utils::digest_mechanism(hash)?,
+            pParameter: std::ptr::null_mut(),
+            ulParameterLen: 0,
+        };
+
+        match session
+            .backend()
+            .digest_init(session.session_handle(), &mech)
+        {
+            Ok(_) => {
+                info!("Digest operation initialized.");
+                match session.backend().digest(session.session_handle(), input) {
+                    Ok(digest) => Ok(digest),
+                    Err(e) => Err(utils::to_response_status("C_Digest", e)),
+                }
+            }
+            Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_INVALID)) => {
+                error!("The token does not support {:?}.", hash);
+                Err(ResponseStatus::PsaErrorNotSupported)
+            }
+            Err(e) => Err(utils::to_response_status("C_DigestInit", e)),
+        }
+    }
+
+    /// Rebuild the attributes of the key identified by the key ID from the objects stored on the
+    /// token. Only the RSA, ECC and AES keys created or imported by this provider are recognised.
+    fn key_attributes(&self, session: &Session, key_id: [u8; 4]) -> Result<KeyAttributes> {
+        self.with_key_objects(key_id, || {
+            match self.find_key(session, key_id, KeyPairType::SecretKey) {
+                Ok(secret_key) => return self.secret_key_attributes(session, secret_key),
+                Err(ResponseStatus::PsaErrorDoesNotExist) => (),
+                Err(e) => return Err(e),
+            }
+
+            let public_key = self.find_key(session, key_id, KeyPairType::PublicKey)?;
+            let private_key = match self.find_key(session, key_id, KeyPairType::PrivateKey) {
+                Ok(private_key) => Some(private_key),
+                Err(ResponseStatus::PsaErrorDoesNotExist) => None,
+                Err(e) => return Err(e),
+            };
+            let is_keypair = private_key.is_some();
+
+            let permit_verify =
+                self.get_attribute_bool(session, public_key, pkcs11::types::CKA_VERIFY)?;
+            let permit_encrypt =
+                self.get_attribute_bool(session, public_key, pkcs11::types::CKA_ENCRYPT)?;
+            // A public key on its own can always be exported.
+            let (permit_sign, permit_decrypt, permit_derive, permit_export) = match private_key {
+                Some(private_key) => (
+                    self.get_attribute_bool(session, private_key, pkcs11::types::CKA_SIGN)?,
+                    self.get_attribute_bool(session, private_key, pkcs11::types::CKA_DECRYPT)?,
+                    self.get_attribute_bool(session, private_key, pkcs11::types::CKA_DERIVE)?,
+                    self.get_attribute_bool(session, private_key, pkcs11::types::CKA_EXTRACTABLE)?,
+                ),
+                None => (false, false, false, true),
+            };
+
+            let pkcs11_key_type = self.get_key_type(session, public_key)?;
+            let allowed_mechanisms = self.get_allowed_mechanisms(session, public_key)?;
+            let purpose = self.get_key_purpose(session, public_key)?;
+            let algorithm =
+                utils::algorithm_from_mechanisms(pkcs11_key_type, &allowed_mechanisms, purpose)?;
+            let (key_type, ecc_curve, key_size) = match pkcs11_key_type {
+                pkcs11::types::CKK_RSA => {
+                    let modulus =
+                        self.get_attribute_bytes(session, public_key, pkcs11::types::CKA_MODULUS)?;
+                    (
+                        if is_keypair {
+                            KeyType::RsaKeypair
+                        } else {
+                            KeyType::RsaPublicKey
+                        },
+                        None,
+                        (modulus.len() * 8) as u32,
+                    )
+                }
+                pkcs11::types::CKK_EC => {
+                    let ec_params = self.get_attribute_bytes(
+                        session,
+                        public_key,
+                        pkcs11::types::CKA_EC_PARAMS,
+                    )?;
+                    let curve = utils::ecc_curve_from_params(&ec_params)?;
+                    (
+                        if is_keypair {
+                            KeyType::EccKeypair
+                        } else {
+                            KeyType::EccPublicKey
+                        },
+                        Some(curve),
+                        utils::ecc_curve_bits(curve),
+                    )
+                }
+                key_type => {
+                    error!("Key type {} has no native equivalent.", key_type);
+                    return Err(ResponseStatus::PsaErrorNotSupported);
+                }
+            };
+
+            Ok(KeyAttributes {
+                key_type,
+                ecc_curve,
+                algorithm,
+                key_size,
+                permit_sign,
+                permit_verify,
+                permit_export,
+                permit_derive,
+                permit_encrypt,
+                permit_decrypt,
+            })
+        })
+    }
+
+    /// Rebuild the attributes of a secret key from the object stored on the token.
+    fn secret_key_attributes(
+        &self,
+        session: &Session,
+        secret_key: CK_OBJECT_HANDLE,
+    ) -> Result<KeyAttributes> {
+        let pkcs11_key_type = self.get_key_type(session, secret_key)?;
+        let key_type = match pkcs11_key_type {
+            pkcs11::types::CKK_AES => KeyType::Aes,
+            pkcs11::types::CKK_GENERIC_SECRET => KeyType::Hmac,
+            key_type => {
+                error!("Key type {} has no native equivalent.", key_type);
+                return Err(ResponseStatus::PsaErrorNotSupported);
+            }
+        };
+        let allowed_mechanisms = self.get_allowed_mechanisms(session, secret_key)?;
+        let purpose = self.get_key_purpose(session, secret_key)?;
+        let algorithm =
+            utils::algorithm_from_mechanisms(pkcs11_key_type, &allowed_mechanisms, purpose)?;
+
+        let value_len: CK_ULONG = 0;
+        let mut template =
+            vec![CK_ATTRIBUTE::new(pkcs11::types::CKA_VALUE_LEN).with_ck_ulong(&value_len)];
+        let value_len = session
+            .backend()
+            .get_attribute_value(session.session_handle(), secret_key, &mut template)
+            .or_else(|e| Err(utils::to_response_status("C_GetAttributeValue", e)))
+            .and_then(|(rv, attrs)| {
+                utils::check_rv("C_GetAttributeValue", rv)?;
+                Ok(attrs[0].get_ck_ulong())
+            })?;
+
+        Ok(KeyAttributes {
+            key_type,
+            ecc_curve: None,
+            algorithm,
+            key_size: (value_len * 8) as u32,
+            permit_sign: self.get_attribute_bool(session, secret_key, pkcs11::types::CKA_SIGN)?,
+            permit_verify: self.get_attribute_bool(
+                session,
+                secret_key,
+                pkcs11::types::CKA_VERIFY,
+            )?,
+            permit_export: self.get_attribute_bool(
+                session,
+                secret_key,
+                pkcs11::types::CKA_EXTRACTABLE,
+            )?,
+            permit_derive: self.get_attribute_bool(
+                session,
+                secret_key,
+                pkcs11::types::CKA_DERIVE,
+            )?,
+            permit_encrypt: self.get_attribute_bool(
+                session,
+                secret_key,
+                pkcs11::types::CKA_ENCRYPT,
+            )?,
+            permit_decrypt: self.get_attribute_bool(
+                session,
+                secret_key,
+                pkcs11::types::CKA_DECRYPT,
+            )?,
+        })
+    }
+}
+
+impl Provide for Pkcs11Provider {
+    fn list_opcodes(&self, _op: OpListOpcodes) -> Result<ResultListOpcodes> {
+        Ok(ResultListOpcodes {
+            opcodes: SUPPORTED_OPCODES.iter().copied().collect(),
+        })
+    }
+
+    fn describe(&self) -> ProviderInfo {
+        ProviderInfo {
+            // Assigned UUID for this provider: 30e39502-eba6-4d60-a4af-c518b7f5e38f
+            uuid: Uuid::parse_str("30e39502-eba6-4d60-a4af-c518b7f5e38f").unwrap(),
+            description: match self
+                .backend
+                .read()
+                .expect("Backend lock poisoned")
+                .slot_number
+            {
+                Some(slot_number) => format!(
+                    "PKCS #11 provider, interfacing with a PKCS #11 library, using the token in slot {}.",
+                    slot_number
+                ),
+                None => String::from(
+                    "PKCS #11 provider, interfacing with a PKCS #11 library, the token is currently unavailable.",
+                ),
+            },
+            vendor: String::from("OASIS Standard."),
+            version_maj: 0,
+            version_min: 1,
+            version_rev: 0,
+            id: ProviderID::Pkcs11Provider,
+        }
+    }
+
+    fn list_keys(&self, app_name: ApplicationName, _op: OpListKeys) -> Result<ResultListKeys> {
+        info!("Pkcs11 Provider - List Keys");
+
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triples = store_handle
+            .get_all(ProviderID::Pkcs11Provider)
+            .or_else(|string| {
+                error!("Key ID Manager error: {}", string);
+                Err(ResponseStatus::KeyIDManagerError)
+            })?;
+
+        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        info!("Listing keys in session {}", session.session_handle());
+
+        let mut keys = Vec::new();
+        for key_triple in key_triples
+            .into_iter()
+            .filter(|key_triple| key_triple.app_name() == &app_name)
+        {
+            let attributes = get_key_id(key_triple, &*store_handle)
+                .and_then(|key_id| self.key_attributes(&session, key_id));
+            match attributes {
+                Ok(attributes) => keys.push(KeyInfo {
+                    provider_id: ProviderID::Pkcs11Provider,
+                    name: key_triple.key_name().to_string(),
+                    attributes,
+                }),
+                Err(status) => warn!(
+                    "Key {} can not be listed (error: {}), continuing...",
+                    key_triple, status
+                ),
+            }
+        }
+
+        Ok(ResultListKeys { keys })
+    }
+
+    fn create_key(&self, app_name: ApplicationName, op: OpCreateKey) -> Result<ResultCreateKey> {
+        info!("Pkcs11 Provider - Create Key");
+
+        let key_attributes = op.key_attributes;
+        let (mechanism, key_type) = match key_attributes.key_type {
+            KeyType::RsaKeypair => (
+                pkcs11::types::CKM_RSA_PKCS_KEY_PAIR_GEN,
+                pkcs11::types::CKK_RSA,
+            ),
+            KeyType::EccKeypair => (pkcs11::types::CKM_EC_KEY_PAIR_GEN, pkcs11::types::CKK_EC),
+            KeyType::Aes => (pkcs11::types::CKM_AES_KEY_GEN, pkcs11::types::CKK_AES),
+            KeyType::Hmac => (
+                pkcs11::types::CKM_GENERIC_SECRET_KEY_GEN,
+                pkcs11::types::CKK_GENERIC_SECRET,
+            ),
+            _ => {
+                error!(
+                    "The PKCS 11 provider currently only supports creating RSA or ECC key pairs and AES or HMAC keys."
+                );
+                return Err(ResponseStatus::UnsupportedOperation);
+            }
+        };
+        let allowed_mechanisms = utils::mechanisms_to_bytes(&utils::allowed_mechanisms(
+            key_type,
+            &key_attributes.algorithm,
+        )?);
+        let purpose = utils::KeyPurpose::of_algorithm(&key_attributes.algorithm)?;
+        let ec_params = match key_attributes.key_type {
+            KeyType::EccKeypair => utils::ec_params(key_attributes.ecc_curve)?,
+            _ => &[],
+        };
+        utils::check_key_size(key_type, key_attributes.ecc_curve, key_attributes.key_size)?;
+
+        let key_name = op.key_name;
+        // This should never panic on 32 bits or more machines.
+        let key_size: CK_ULONG = std::convert::TryFrom::try_from(key_attributes.key_size).unwrap();
+        let value_len = key_size / 8;
+
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
+            return Err(ResponseStatus::KeyAlreadyExists);
+        }
+        let key_id = create_key_id(
+            key_triple.clone(),
+            &mut *store_handle,
+            &mut local_ids_handle,
+        )?;
+        // The key triple is also written on the token, to rebuild the mappings from it if needed,
+        // with the purpose of the key.
+        let label = utils::key_label(&key_triple, Some(purpose));
+
+        let mech = CK_MECHANISM {
+            mechanism,
+            pParameter: std::ptr::null_mut(),
+            ulParameterLen: 0,
+        };
+
+        let mut priv_template: Vec<CK_ATTRIBUTE> = Vec::new();
+        let mut pub_template: Vec<CK_ATTRIBUTE> = Vec::new();
+        let mut secret_template: Vec<CK_ATTRIBUTE> = Vec::new();
+
+        // Secret and private keys can only be read out of the token if they are exportable.
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                .with_ck_ulong(&pkcs11::types::CKO_SECRET_KEY),
+        );
+        secret_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE).with_ck_ulong(&key_type));
+        secret_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_VALUE_LEN).with_ck_ulong(&value_len));
+        secret_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+        secret_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()));
+        secret_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
+        // The label, which records the purpose of the key, and the policy of the key must not be
+        // changed on the token after the key is created.
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_MODIFIABLE).with_bool(&pkcs11::types::CK_FALSE),
+        );
+        secret_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_PRIVATE).with_bool(&pkcs11::types::CK_TRUE));
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_SENSITIVE)
+                .with_bool(utils::ck_bool(!key_attributes.permit_export)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_EXTRACTABLE)
+                .with_bool(utils::ck_bool(key_attributes.permit_export)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ENCRYPT)
+                .with_bool(utils::ck_bool(key_attributes.permit_encrypt)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_DECRYPT)
+                .with_bool(utils::ck_bool(key_attributes.permit_decrypt)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_SIGN)
+                .with_bool(utils::ck_bool(key_attributes.permit_sign)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_VERIFY)
+                .with_bool(utils::ck_bool(key_attributes.permit_verify)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_DERIVE)
+                .with_bool(utils::ck_bool(key_attributes.permit_derive)),
+        );
+        secret_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ALLOWED_MECHANISMS)
+                .with_bytes(&allowed_mechanisms),
+        );
+
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_SIGN)
+                .with_bool(utils::ck_bool(key_attributes.permit_sign)),
+        );
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_DECRYPT)
+                .with_bool(utils::ck_bool(key_attributes.permit_decrypt)),
+        );
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_DERIVE)
+                .with_bool(utils::ck_bool(key_attributes.permit_derive)),
+        );
+        priv_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+        priv_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()));
+        priv_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_MODIFIABLE).with_bool(&pkcs11::types::CK_FALSE),
+        );
+        priv_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_PRIVATE).with_bool(&pkcs11::types::CK_TRUE));
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_SENSITIVE)
+                .with_bool(utils::ck_bool(!key_attributes.permit_export)),
+        );
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_EXTRACTABLE)
+                .with_bool(utils::ck_bool(key_attributes.permit_export)),
+        );
+        priv_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ALLOWED_MECHANISMS)
+                .with_bytes(&allowed_mechanisms),
+        );
+
+        pub_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_VERIFY)
+                .with_bool(utils::ck_bool(key_attributes.permit_verify)),
+        );
+        pub_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ENCRYPT)
+                .with_bool(utils::ck_bool(key_attributes.permit_encrypt)),
+        );
+        pub_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+        pub_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()));
+        if mechanism == pkcs11::types::CKM_EC_KEY_PAIR_GEN {
+            pub_template
+                .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_EC_PARAMS).with_bytes(ec_params));
+        } else {
+            pub_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_PUBLIC_EXPONENT).with_bytes(&PUBLIC_EXPONENT),
+            );
+            pub_template
+                .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_MODULUS_BITS).with_ck_ulong(&key_size));
+        }
+        pub_template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
+        pub_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_MODIFIABLE).with_bool(&pkcs11::types::CK_FALSE),
+        );
+        // As with PSA, exporting the public key is always permitted.
+        pub_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_PRIVATE).with_bool(&pkcs11::types::CK_FALSE),
+        );
+        pub_template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ALLOWED_MECHANISMS)
+                .with_bytes(&allowed_mechanisms),
+        );
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite).or_else(|err| {
+            error!("Error creating a new session: {}.", err);
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            Err(err)
+        })?;
+
+        info!("Generating key in session {}", session.session_handle());
+
+        let generate_result = if utils::is_secret_key_type(key_type) {
+            session
+                .backend()
+                .generate_key(session.session_handle(), &mech, &secret_template)
+                .map(|_key| ())
+                .or_else(|e| Err(utils::to_response_status("C_GenerateKey", e)))
+        } else {
+            session
+                .backend()
+                .generate_key_pair(
+                    session.session_handle(),
+                    &mech,
+                    &pub_template,
+                    &priv_template,
+                )
+                .map(|_keys| ())
+                .or_else(|e| Err(utils::to_response_status("C_GenerateKeyPair", e)))
+        };
+
+        match generate_result {
+            Ok(()) => Ok(ResultCreateKey {}),
+            Err(e) => {
+                remove_key_id(
+                    &key_triple,
+                    key_id,
+                    &mut *store_handle,
+                    &mut local_ids_handle,
+                )?;
+                Err(e)
+            }
+        }
+    }
+
+    fn import_key(&self, app_name: ApplicationName, op: OpImportKey) -> Result<ResultImportKey> {
+        info!("Pkcs11 Provider - Import Key");
+
+        let key_attributes = op.key_attributes;
+        let (key_type, ec_params, is_key_pair) = match key_attributes.key_type {
+            KeyType::RsaPublicKey => (pkcs11::types::CKK_RSA, None, false),
+            KeyType::RsaKeypair => (pkcs11::types::CKK_RSA, None, true),
+            KeyType::EccPublicKey => (
+                pkcs11::types::CKK_EC,
+                Some(utils::ec_params(key_attributes.ecc_curve)?),
+                false,
+            ),
+            KeyType::EccKeypair => (
+                pkcs11::types::CKK_EC,
+                Some(utils::ec_params(key_attributes.ecc_curve)?),
+                true,
+            ),
+            KeyType::Aes => (pkcs11::types::CKK_AES, None, false),
+            KeyType::Hmac => (pkcs11::types::CKK_GENERIC_SECRET, None, false),
+            _ => {
+                error!("The PKCS 11 provider currently only supports importing RSA or ECC keys and AES or HMAC keys.");
+                return Err(ResponseStatus::UnsupportedOperation);
+            }
+        };
+        let allowed_mechanisms = utils::mechanisms_to_bytes(&utils::allowed_mechanisms(
+            key_type,
+            &key_attributes.algorithm,
+        )?);
+        let purpose = utils::KeyPurpose::of_algorithm(&key_attributes.algorithm)?;
+
+        // The key data is parsed before a key ID is created so that malformed data has nothing to
+        // clean up. The values are stored as the attributes of the public and private key objects.
+        let mut public_values: Vec<(CK_ATTRIBUTE_TYPE, Vec<u8>)> = Vec::new();
+        let mut private_values: Vec<(CK_ATTRIBUTE_TYPE, Vec<u8>)> = Vec::new();
+        if !utils::is_secret_key_type(key_type) {
+            match (ec_params, is_key_pair) {
+                (Some(ec_params), false) => {
+                    // The key data is the uncompressed point, PKCS 11 expects it wrapped in an
+                    // OCTET STRING.
+                    public_values.push((pkcs11::types::CKA_EC_PARAMS, ec_params.to_vec()));
+                    public_values.push((
+                        pkcs11::types::CKA_EC_POINT,
+                        utils::ec_point_to_der(&op.key_data, ec_params)?,
+                    ));
+                }
+                (Some(ec_params), true) => {
+                    let (private_value, point) = utils::ec_private_key_from_der(
+                        utils::pkcs8_private_key(&op.key_data)?,
+                        ec_params,
+                    )?;
+                    public_values.push((pkcs11::types::CKA_EC_PARAMS, ec_params.to_vec()));
+                    public_values.push((
+                        pkcs11::types::CKA_EC_POINT,
+                        utils::ec_point_to_der(&point, ec_params)?,
+                    ));
+                    private_values.push((pkcs11::types::CKA_EC_PARAMS, ec_params.to_vec()));
+                    private_values.push((pkcs11::types::CKA_VALUE, private_value));
+                }
+                (None, false) => {
+                    let public_key: RsaPublicKey = serde_asn1_der::from_bytes(&op.key_data)
+                        .or_else(|err| {
+                            error!("Could not parse the RSA public key: {}.", err);
+                            Err(ResponseStatus::PsaErrorInvalidArgument)
+                        })?;
+                    public_values.push((
+                        pkcs11::types::CKA_MODULUS,
+                        public_key.modulus.to_bytes_be().1,
+                    ));
+                    public_values.push((
+                        pkcs11::types::CKA_PUBLIC_EXPONENT,
+                        public_key.public_exponent.to_bytes_be().1,
+                    ));
+                }
+                (None, true) => {
+                    let private_key: RsaPrivateKey =
+                        serde_asn1_der::from_bytes(utils::pkcs8_private_key(&op.key_data)?)
+                            .or_else(|err| {
+                                error!("Could not parse the RSA private key: {}.", err);
+                                Err(ResponseStatus::PsaErrorInvalidArgument)
+                            })?;
+                    // Only two-prime keys, version 0, can be stored in PKCS 11 objects.
+                    if private_key.version.0 != BigInt::from(0) {
+                        error!("Multi-prime RSA private keys are not supported.");
+                        return Err(ResponseStatus::PsaErrorNotSupported);
+                    }
+                    let modulus = private_key.modulus.to_bytes_be().1;
+                    let public_exponent = private_key.public_exponent.to_bytes_be().1;
+                    public_values.push((pkcs11::types::CKA_MODULUS, modulus.clone()));
+                    public_values
+                        .push((pkcs11::types::CKA_PUBLIC_EXPONENT, public_exponent.clone()));
+                    private_values.push((pkcs11::types::CKA_MODULUS, modulus));
+                    private_values.push((pkcs11::types::CKA_PUBLIC_EXPONENT, public_exponent));
+                    for (attribute, value) in &[
+                        (
+                            pkcs11::types::CKA_PRIVATE_EXPONENT,
+                            &private_key.private_exponent,
+                        ),
+                        (pkcs11::types::CKA_PRIME_1, &private_key.prime_1),
+                        (pkcs11::types::CKA_PRIME_2, &private_key.prime_2),
+                        (pkcs11::types::CKA_EXPONENT_1, &private_key.exponent_1),
+                        (pkcs11::types::CKA_EXPONENT_2, &private_key.exponent_2),
+                        (pkcs11::types::CKA_COEFFICIENT, &private_key.coefficient),
+                    ] {
+                        private_values.push((*attribute, value.to_bytes_be().1));
+                    }
+                }
+            }
+        }
+
+        let key_name = op.key_name;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        if key_id_managers::key_name_in_use(&key_triple, &*store_handle)? {
+            return Err(ResponseStatus::KeyAlreadyExists);
+        }
+        let key_id = create_key_id(
+            key_triple.clone(),
+            &mut *store_handle,
+            &mut local_ids_handle,
+        )?;
+        // The key triple is also written on the token, to rebuild the mappings from it if needed,
+        // with the purpose of the key.
+        let label = utils::key_label(&key_triple, Some(purpose));
+
+        let mut template: Vec<CK_ATTRIBUTE> = Vec::new();
+
+        let class = if utils::is_secret_key_type(key_type) {
+            pkcs11::types::CKO_SECRET_KEY
+        } else {
+            pkcs11::types::CKO_PUBLIC_KEY
+        };
+        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS).with_ck_ulong(&class));
+        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE).with_ck_ulong(&key_type));
+        template
+            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
+        // As for created keys, the label and the policy of the key must not be changed.
+        template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_MODIFIABLE).with_bool(&pkcs11::types::CK_FALSE),
+        );
+        template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_VERIFY)
+                .with_bool(utils::ck_bool(key_attributes.permit_verify)),
+        );
+        template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ENCRYPT)
+                .with_bool(utils::ck_bool(key_attributes.permit_encrypt)),
+        );
+        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()));
+        template.push(
+            CK_ATTRIBUTE::new(pkcs11::types::CKA_ALLOWED_MECHANISMS)
+                .with_bytes(&allowed_mechanisms),
+        );
+
+        if utils::is_secret_key_type(key_type) {
+            template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_VALUE).with_bytes(&op.key_data));
+            template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_DECRYPT)
+                    .with_bool(utils::ck_bool(key_attributes.permit_decrypt)),
+            );
+            template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_SIGN)
+                    .with_bool(utils::ck_bool(key_attributes.permit_sign)),
+            );
+            template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_DERIVE)
+                    .with_bool(utils::ck_bool(key_attributes.permit_derive)),
+            );
+            template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_SENSITIVE)
+                    .with_bool(utils::ck_bool(!key_attributes.permit_export)),
+            );
+            template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_EXTRACTABLE)
+                    .with_bool(utils::ck_bool(key_attributes.permit_export)),
+            );
+        }
+        for (attribute, value) in &public_values {
+            template.push(CK_ATTRIBUTE::new(*attribute).with_bytes(value));
+        }
+
+        // The private key object of a key pair shares the ID of the public one, which is needed
+        // for the public key operations and to read back the key attributes.
+        let mut priv_template: Vec<CK_ATTRIBUTE> = Vec::new();
+        if is_key_pair {
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                    .with_ck_ulong(&pkcs11::types::CKO_PRIVATE_KEY),
+            );
+            priv_template
+                .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE).with_ck_ulong(&key_type));
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_MODIFIABLE)
+                    .with_bool(&pkcs11::types::CK_FALSE),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_SIGN)
+                    .with_bool(utils::ck_bool(key_attributes.permit_sign)),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_DECRYPT)
+                    .with_bool(utils::ck_bool(key_attributes.permit_decrypt)),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_DERIVE)
+                    .with_bool(utils::ck_bool(key_attributes.permit_derive)),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_SENSITIVE)
+                    .with_bool(utils::ck_bool(!key_attributes.permit_export)),
+            );
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_EXTRACTABLE)
+                    .with_bool(utils::ck_bool(key_attributes.permit_export)),
+            );
+            priv_template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+            priv_template
+                .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_LABEL).with_bytes(label.as_bytes()));
+            priv_template.push(
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_ALLOWED_MECHANISMS)
+                    .with_bytes(&allowed_mechanisms),
+            );
+            for (attribute, value) in &private_values {
+                priv_template.push(CK_ATTRIBUTE::new(*attribute).with_bytes(value));
+            }
+        }
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite).or_else(|err| {
+            error!("Error creating a new session: {}.", err);
+            remove_key_id(
+                &key_triple,
+                key_id,
+                &mut *store_handle,
+                &mut local_ids_handle,
+            )?;
+            Err(err)
+        })?;
+
+        info!("Importing key in session {}", session.session_handle());
+
+        let result = session
+            .backend()
+            .create_object(session.session_handle(), &template)
+            .and_then(|key| {
+                if !is_key_pair {
+                    return Ok(());
+                }
+                session
+                    .backend()
+                    .create_object(session.session_handle(), &priv_template)
+                    .map(|_key| ())
+                    .or_else(|e| {
+                        // Do not leave the public key object of a failed import on the token.
+                        if let Err(e) = session
+                            .backend()
+                            .destroy_object(session.session_handle(), key)
+                        {
+                            warn!("Failed to destroy the imported public key object ({}).", e);
+                        }
+                        Err(e)
+                    })
+            });
+        match result {
+            Ok(()) => Ok(ResultImportKey {}),
+            Err(e) => {
+                let status = utils::to_response_status("C_CreateObject", e);
+                remove_key_id(
+                    &key_triple,
+                    key_id,
+                    &mut *store_handle,
+                    &mut local_ids_handle,
+                )?;
+                Err(status)
+            }
+        }
+    }
+
+    fn export_public_key(
+        &self,
+        app_name: ApplicationName,
+        op: OpExportPublicKey,
+    ) -> Result<ResultExportPublicKey> {
+        info!("Pkcs11 Provider - Export Public Key");
+
+        let key_name = op.key_name;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Export, &*store_handle)?;
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        info!("Export public key in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PublicKey)?;
+            info!("Located key for export.");
+
+            match self.get_key_type(&session, key)? {
+                pkcs11::types::CKK_EC => {
+                    let ec_point =
+                        self.get_attribute_bytes(&session, key, pkcs11::types::CKA_EC_POINT)?;
+                    let key_data = utils::ec_point_from_der(&ec_point)?;
+                    Ok(ResultExportPublicKey { key_data })
+                }
+                pkcs11::types::CKK_RSA => {
+                    let modulus =
+                        self.get_attribute_bytes(&session, key, pkcs11::types::CKA_MODULUS)?;
+                    let public_exponent = self.get_attribute_bytes(
+                        &session,
+                        key,
+                        pkcs11::types::CKA_PUBLIC_EXPONENT,
+                    )?;
+                    let key = RsaPublicKey {
+                        modulus: IntegerAsn1(BigInt::from_bytes_be(Sign::Plus, &modulus)),
+                        public_exponent: IntegerAsn1(BigInt::from_bytes_be(
+                            Sign::Plus,
+                            &public_exponent,
+                        )),
+                    };
+                    let key_data = serde_asn1_der::to_vec(&key).or_else(|err| {
+                        error!("Could not serialise key elements: {}.", err);
+                        Err(ResponseStatus::PsaErrorCommunicationFailure)
+                    })?;
+                    Ok(ResultExportPublicKey { key_data })
+                }
+                key_type => {
+                    error!("Public keys of type {} can not be exported.", key_type);
+                    Err(ResponseStatus::PsaErrorNotSupported)
+                }
+            }
+        })
+    }
+
+    fn destroy_key(&self, app_name: ApplicationName, op: OpDestroyKey) -> Result<ResultDestroyKey> {
+        info!("Pkcs11 Provider - Destroy Key");
+
+        let key_name = op.key_name;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("Deleting key pair in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            match self.find_key(&session, key_id, KeyPairType::Any) {
+                Ok(key) => {
+                    match session
+                        .backend()
+                        .destroy_object(session.session_handle(), key)
+                    {
+                        Ok(_) => info!("Private part of the key destroyed successfully."),
+                        Err(e) => return Err(utils::to_response_status("C_DestroyObject", e)),
+                    };
+                }
+                Err(e) => {
+                    error!("Error destroying key: {}", e);
+                    return Err(e);
+                }
+            };
+            Ok(())
+        })?;
+        // The destroyed object must not be found again.
+        self.session_pool.forget_object_handles(key_id);
+
+        // Second key is optional.
+        match self.find_key(&session, key_id, KeyPairType::Any) {
+            Ok(key) => {
+                match session
+                    .backend()
+                    .destroy_object(session.session_handle(), key)
+                {
+                    Ok(_) => info!("Private part of the key destroyed successfully."),
+                    Err(e) => return Err(utils::to_response_status("C_DestroyObject", e)),
+                };
+            }
+            // A second key is optional.
+            Err(ResponseStatus::PsaErrorDoesNotExist) => (),
+            Err(e) => {
+                error!("Error destroying key: {}", e);
+                return Err(e);
+            }
+        };
+        self.session_pool.forget_object_handles(key_id);
+
+        remove_key_id(
+            &key_triple,
+            key_id,
+            &mut *store_handle,
+            &mut local_ids_handle,
+        )?;
+        key_id_managers::remove_grants(&key_triple, &mut *store_handle)?;
+
+        Ok(ResultDestroyKey {})
+    }
+
+    fn asym_sign(&self, app_name: ApplicationName, op: OpAsymSign) -> Result<ResultAsymSign> {
+        info!("Pkcs11 Provider - Asym Sign");
+
+        let key_name = op.key_name;
+        let digest = op.hash;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Sign, &*store_handle)?;
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("Asymmetric sign in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PrivateKey)?;
+            info!("Located signing key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_SIGN)? {
+                error!("The key is not permitted to sign.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let key_type = self.get_key_type(&session, key)?;
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut pss_params) =
+                utils::sign_mechanism(key_type, &allowed_mechanisms, purpose)?;
+            let hash = utils::sign_input(&allowed_mechanisms, digest.clone())?;
+            let mech = match pss_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_PSS_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_PSS_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
+
+            match session
+                .backend()
+                .sign_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Signing operation initialized.");
+                    match session.backend().sign(session.session_handle(), &hash) {
+                        Ok(signature) => Ok(ResultAsymSign { signature }),
+                        Err(e) => Err(utils::to_response_status("C_Sign", e)),
+                    }
+                }
+                Err(e) => Err(utils::to_response_status("C_SignInit", e)),
+            }
+        })
+    }
+
+    fn asym_verify(&self, app_name: ApplicationName, op: OpAsymVerify) -> Result<ResultAsymVerify> {
+        info!("Pkcs11 Provider - Asym Verify");
+
+        let key_name = op.key_name;
+        let digest = op.hash;
+        let signature = op.signature;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Verify, &*store_handle)?;
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("Asymmetric verify in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PublicKey)?;
+            info!("Located public key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_VERIFY)? {
+                error!("The key is not permitted to verify.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let key_type = self.get_key_type(&session, key)?;
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut pss_params) =
+                utils::sign_mechanism(key_type, &allowed_mechanisms, purpose)?;
+            let hash = utils::sign_input(&allowed_mechanisms, digest.clone())?;
+            let mech = match pss_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_PSS_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_PSS_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
+
+            match session
+                .backend()
+                .verify_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Verify operation initialized.");
+                    match session
+                        .backend()
+                        .verify(session.session_handle(), &hash, &signature)
+                    {
+                        Ok(_) => Ok(ResultAsymVerify {}),
+                        Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_INVALID)) => {
+                            info!("Signature verification failed.");
+                            Err(ResponseStatus::PsaErrorInvalidSignature)
+                        }
+                        Err(e) => Err(utils::to_response_status("C_Verify", e)),
+                    }
+                }
+                Err(e) => Err(utils::to_response_status("C_VerifyInit", e)),
+            }
+        })
+    }
+
+    fn asym_encrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymmetricEncrypt,
+    ) -> Result<ResultAsymmetricEncrypt> {
+        info!("Pkcs11 Provider - Asym Encrypt");
+
+        let key_name = op.key_name;
+        let plaintext = op.plaintext;
+        let mut label = op.salt.unwrap_or_default();
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("Asymmetric encrypt in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PublicKey)?;
+            info!("Located encryption key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_ENCRYPT)? {
+                error!("The key is not permitted to encrypt.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut oaep_params) =
+                utils::encrypt_mechanism(&allowed_mechanisms, purpose, &mut label)?;
+            let mech = match oaep_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_OAEP_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_OAEP_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
+
+            match session
+                .backend()
+                .encrypt_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Encryption operation initialized.");
+                    match session
+                        .backend()
+                        .encrypt(session.session_handle(), &plaintext)
+                    {
+                        Ok(ciphertext) => Ok(ResultAsymmetricEncrypt { ciphertext }),
+                        Err(e) => Err(utils::to_response_status("C_Encrypt", e)),
+                    }
+                }
+                Err(pkcs11::errors::Error::Pkcs11(CKR_KEY_FUNCTION_NOT_PERMITTED)) => {
+                    error!("The key is not permitted to encrypt.");
+                    Err(ResponseStatus::PsaErrorNotPermitted)
+                }
+                Err(e) => Err(utils::to_response_status("C_EncryptInit", e)),
+            }
+        })
+    }
+
+    fn asym_decrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymmetricDecrypt,
+    ) -> Result<ResultAsymmetricDecrypt> {
+        info!("Pkcs11 Provider - Asym Decrypt");
+
+        let key_name = op.key_name;
+        let ciphertext = op.ciphertext;
+        let mut label = op.salt.unwrap_or_default();
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("Asymmetric decrypt in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PrivateKey)?;
+            info!("Located decryption key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_DECRYPT)? {
+                error!("The key is not permitted to decrypt.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut oaep_params) =
+                utils::encrypt_mechanism(&allowed_mechanisms, purpose, &mut label)?;
+            let mech = match oaep_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_OAEP_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_OAEP_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
+
+            match session
+                .backend()
+                .decrypt_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Decryption operation initialized.");
+                    match session
+                        .backend()
+                        .decrypt(session.session_handle(), &ciphertext)
+                    {
+                        Ok(plaintext) => Ok(ResultAsymmetricDecrypt { plaintext }),
+                        Err(e) => Err(utils::to_response_status("C_Decrypt", e)),
+                    }
+                }
+                Err(pkcs11::errors::Error::Pkcs11(CKR_KEY_FUNCTION_NOT_PERMITTED)) => {
+                    error!("The key is not permitted to decrypt.");
+                    Err(ResponseStatus::PsaErrorNotPermitted)
+                }
+                Err(e) => Err(utils::to_response_status("C_DecryptInit", e)),
+            }
+        })
+    }
+
+    fn aead_encrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAeadEncrypt,
+    ) -> Result<ResultAeadEncrypt> {
+        info!("Pkcs11 Provider - AEAD Encrypt");
+
+        let key_name = op.key_name;
+        let mut nonce = op.nonce;
+        let mut additional_data = op.additional_data;
+        let plaintext = op.plaintext;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("AEAD encrypt in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::SecretKey)?;
+            info!("Located encryption key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_ENCRYPT)? {
+                error!("The key is not permitted to encrypt.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let mut gcm_params =
+                utils::gcm_params(&allowed_mechanisms, &mut nonce, &mut additional_data)?;
+            let mech = CK_MECHANISM {
+                mechanism: pkcs11::types::CKM_AES_GCM,
+                pParameter: &mut gcm_params as *mut CK_GCM_PARAMS as CK_VOID_PTR,
+                ulParameterLen: std::mem::size_of::<CK_GCM_PARAMS>(),
+            };
+
+            match session
+                .backend()
+                .encrypt_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Encryption operation initialized.");
+                    match session
+                        .backend()
+                        .encrypt(session.session_handle(), &plaintext)
+                    {
+                        Ok(ciphertext) => Ok(ResultAeadEncrypt { ciphertext }),
+                        Err(e) => Err(utils::to_response_status("C_Encrypt", e)),
+                    }
+                }
+                Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_PARAM_INVALID)) => {
+                    error!("Invalid nonce or additional data.");
+                    Err(ResponseStatus::PsaErrorInvalidArgument)
+                }
+                Err(e) => Err(utils::to_response_status("C_EncryptInit", e)),
+            }
+        })
+    }
+
+    fn aead_decrypt(
+        &self,
+        app_name: ApplicationName,
+        op: OpAeadDecrypt,
+    ) -> Result<ResultAeadDecrypt> {
+        info!("Pkcs11 Provider - AEAD Decrypt");
+
+        let key_name = op.key_name;
+        let mut nonce = op.nonce;
+        let mut additional_data = op.additional_data;
+        let ciphertext = op.ciphertext;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("AEAD decrypt in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::SecretKey)?;
+            info!("Located decryption key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_DECRYPT)? {
+                error!("The key is not permitted to decrypt.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let mut gcm_params =
+                utils::gcm_params(&allowed_mechanisms, &mut nonce, &mut additional_data)?;
+            let mech = CK_MECHANISM {
+                mechanism: pkcs11::types::CKM_AES_GCM,
+                pParameter: &mut gcm_params as *mut CK_GCM_PARAMS as CK_VOID_PTR,
+                ulParameterLen: std::mem::size_of::<CK_GCM_PARAMS>(),
+            };
+
+            match session
+                .backend()
+                .decrypt_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Decryption operation initialized.");
+                    match session
+                        .backend()
+                        .decrypt(session.session_handle(), &ciphertext)
+                    {
+                        Ok(plaintext) => Ok(ResultAeadDecrypt { plaintext }),
+                        Err(pkcs11::errors::Error::Pkcs11(CKR_ENCRYPTED_DATA_INVALID)) => {
+                            info!("Authentication of the ciphertext failed.");
+                            Err(ResponseStatus::PsaErrorInvalidSignature)
+                        }
+                        Err(e) => Err(utils::to_response_status("C_Decrypt", e)),
+                    }
+                }
+                Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_PARAM_INVALID)) => {
+                    error!("Invalid nonce or additional data.");
+                    Err(ResponseStatus::PsaErrorInvalidArgument)
+                }
+                Err(e) => Err(utils::to_response_status("C_DecryptInit", e)),
+            }
+        })
+    }
+
+    fn mac_compute(&self, app_name: ApplicationName, op: OpMacCompute) -> Result<ResultMacCompute> {
+        info!("Pkcs11 Provider - MAC Compute");
+
+        let key_name = op.key_name;
+        let input = op.input;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("MAC compute in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::SecretKey)?;
+            info!("Located MAC key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_SIGN)? {
+                error!("The key is not permitted to compute MACs.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let mech = CK_MECHANISM {
+                mechanism: utils::mac_mechanism(&allowed_mechanisms)?,
+                pParameter: std::ptr::null_mut(),
+                ulParameterLen: 0,
+            };
+
+            match session
+                .backend()
+                .sign_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("MAC operation initialized.");
+                    match session.backend().sign(session.session_handle(), &input) {
+                        Ok(mac) => Ok(ResultMacCompute { mac }),
+                        Err(e) => Err(utils::to_response_status("C_Sign", e)),
+                    }
+                }
+                Err(e) => Err(utils::to_response_status("C_SignInit", e)),
+            }
+        })
+    }
+
+    fn mac_verify(&self, app_name: ApplicationName, op: OpMacVerify) -> Result<ResultMacVerify> {
+        info!("Pkcs11 Provider - MAC Verify");
+
+        let key_name = op.key_name;
+        let input = op.input;
+        let mac = op.mac;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_id = get_key_id(&key_triple, &*store_handle)?;
+
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
+        info!("MAC verify in session {}", session.session_handle());
+
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::SecretKey)?;
+            info!("Located MAC key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_VERIFY)? {
+                error!("The key is not permitted to verify MACs.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let mech = CK_MECHANISM {
+                mechanism: utils::mac_mechanism(&allowed_mechanisms)?,
+                pParameter: std::ptr::null_mut(),
+                ulParameterLen: 0,
+            };
+
+            // The MAC is compared by the token, the provider never computes it.
+            match session
+                .backend()
+                .verify_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("MAC verify operation initialized.");
+                    match session
+                        .backend()
+                        .verify(session.session_handle(), &input, &mac)
+                    {
+                        Ok(_) => Ok(ResultMacVerify {}),
+                        Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_INVALID))
+                        | Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_LEN_RANGE)) => {
+                            info!("MAC verification failed.");
+                            Err(ResponseStatus::PsaErrorInvalidSignature)
+                        }
+                        Err(e) => Err(utils::to_response_status("C_Verify", e)),
+                    }
+                }
+                Err(e) => Err(utils::to_response_status("C_VerifyInit", e)),
+            }
+        })
+    }
 
-        template.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
-                .with_ck_ulong(&pkcs11::types::CKO_PUBLIC_KEY),
-        );
-        template.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE).with_ck_ulong(&pkcs11::types::CKK_RSA),
-        );
-        template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_TRUE));
-        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_MODULUS).with_bytes(modulus_object));
-        template.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_PUBLIC_EXPONENT).with_bytes(exponent_object),
-        );
-        template
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_VERIFY).with_bool(&pkcs11::types::CK_TRUE));
-        template.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_ID).with_bytes(&key_id));
+    fn hash_compute(&self, op: OpHashCompute) -> Result<ResultHashCompute> {
+        info!("Pkcs11 Provider - Hash Compute");
 
-        let session = Session::new(self, ReadWriteSession::ReadWrite).or_else(|err| {
-            error!("Error creating a new session: {}.", err);
-            remove_key_id(
-                &key_triple,
-                key_id,
-                &mut *store_handle,
-                &mut local_ids_handle,
-            )?;
-            Err(err)
-        })?;
+        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        info!("Hash compute in session {}", session.session_handle());
 
-        info!(
-            "Importing RSA public key in session {}",
-            session.session_handle()
-        );
+        let hash = self.digest(&session, op.alg, &op.input)?;
+        Ok(ResultHashCompute { hash })
+    }
 
-        match self
-            .backend
-            .create_object(session.session_handle(), &template)
-        {
-            Ok(_key) => Ok(ResultImportKey {}),
-            Err(e) => {
-                error!("Import operation failed with {}", e);
-                remove_key_id(
-                    &key_triple,
-                    key_id,
-                    &mut *store_handle,
-                    &mut local_ids_handle,
-                )?;
-                Err(ResponseStatus::PsaErrorHardwareFailure)
-            }
+    fn hash_compare(&self, op: OpHashCompare) -> Result<ResultHashCompare> {
+        info!("Pkcs11 Provider - Hash Compare");
+
+        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        info!("Hash compare in session {}", session.session_handle());
+
+        let hash = self.digest(&session, op.alg, &op.input)?;
+        if utils::constant_time_eq(&hash, &op.hash) {
+            Ok(ResultHashCompare {})
+        } else {
+            info!("Hash comparison failed.");
+            Err(ResponseStatus::PsaErrorInvalidSignature)
         }
     }
 
-    fn export_public_key(
+    fn asym_sign_message(
         &self,
         app_name: ApplicationName,
-        op: OpExportPublicKey,
-    ) -> Result<ResultExportPublicKey> {
-        info!("Pkcs11 Provider - Export Public Key");
+        op: OpAsymSignMessage,
+    ) -> Result<ResultAsymSignMessage> {
+        info!("Pkcs11 Provider - Asym Sign Message");
 
-        let key_name = op.key_name;
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let message = op.message;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, op.key_name);
         let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Sign, &*store_handle)?;
         let key_id = get_key_id(&key_triple, &*store_handle)?;
 
-        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
         info!(
-            "Export RSA public key in session {}",
+            "Asymmetric sign message in session {}",
             session.session_handle()
         );
 
-        let key = self.find_key(session.session_handle(), key_id, KeyPairType::PublicKey)?;
-        info!("Located key for export.");
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PrivateKey)?;
+            info!("Located signing key.");
 
-        let mut size_attrs: Vec<CK_ATTRIBUTE> = Vec::new();
-        size_attrs.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_MODULUS));
-        size_attrs.push(CK_ATTRIBUTE::new(pkcs11::types::CKA_PUBLIC_EXPONENT));
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_SIGN)? {
+                error!("The key is not permitted to sign.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
+            }
+            let key_type = self.get_key_type(&session, key)?;
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut pss_params) =
+                utils::sign_message_mechanism(key_type, &allowed_mechanisms, purpose)?;
+            let mech = match pss_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_PSS_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_PSS_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
 
-        // Get the length of the attributes to retrieve.
-        let (modulus_len, public_exponent_len) =
-            match self
-                .backend
-                .get_attribute_value(session.session_handle(), key, &mut size_attrs)
+            match session
+                .backend()
+                .sign_init(session.session_handle(), &mech, key)
             {
-                Ok((rv, attrs)) => {
-                    if rv != CKR_OK {
-                        error!("Error when extracting attribute: {}.", rv);
-                        Err(ResponseStatus::PsaErrorCommunicationFailure)
-                    } else {
-                        Ok((attrs[0].ulValueLen, attrs[1].ulValueLen))
+                Ok(_) => {
+                    info!("Signing operation initialized.");
+                    match session.backend().sign(session.session_handle(), &message) {
+                        Ok(signature) => Ok(ResultAsymSignMessage { signature }),
+                        Err(e) => Err(utils::to_response_status("C_Sign", e)),
                     }
                 }
-                Err(e) => {
-                    error!("Failed to read attributes from public key. Error: {}", e);
-                    Err(ResponseStatus::PsaErrorCommunicationFailure)
-                }
-            }?;
-
-        let mut modulus: Vec<pkcs11::types::CK_BYTE> = Vec::new();
-        let mut public_exponent: Vec<pkcs11::types::CK_BYTE> = Vec::new();
-        modulus.resize(modulus_len, 0);
-        public_exponent.resize(public_exponent_len, 0);
-
-        let mut extract_attrs: Vec<CK_ATTRIBUTE> = Vec::new();
-        extract_attrs
-            .push(CK_ATTRIBUTE::new(pkcs11::types::CKA_MODULUS).with_bytes(modulus.as_mut_slice()));
-        extract_attrs.push(
-            CK_ATTRIBUTE::new(pkcs11::types::CKA_PUBLIC_EXPONENT)
-                .with_bytes(public_exponent.as_mut_slice()),
-        );
-
-        match self
-            .backend
-            .get_attribute_value(session.session_handle(), key, &mut extract_attrs)
-        {
-            Ok(res) => {
-                let (rv, attrs) = res;
-                if rv != CKR_OK {
-                    error!("Error when extracting attribute: {}.", rv);
-                    Err(ResponseStatus::PsaErrorCommunicationFailure)
-                } else {
-                    let key = RsaPublicKey {
-                        modulus: IntegerAsn1(BigInt::from_bytes_be(
-                            Sign::Plus,
-                            &attrs[0].get_bytes(),
-                        )),
-                        public_exponent: IntegerAsn1(BigInt::from_bytes_be(
-                            Sign::Plus,
-                            &attrs[1].get_bytes(),
-                        )),
-                    };
-                    let key_data = serde_asn1_der::to_vec(&key).or_else(|err| {
-                        error!("Could not serialise key elements: {}.", err);
-                        Err(ResponseStatus::PsaErrorCommunicationFailure)
-                    })?;
-                    Ok(ResultExportPublicKey { key_data })
+                Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_INVALID)) => {
+                    error!(
+                        "The token does not support hash-and-sign mechanism {}.",
+                        mechanism
+                    );
+                    Err(ResponseStatus::PsaErrorNotSupported)
                 }
+                Err(e) => Err(utils::to_response_status("C_SignInit", e)),
             }
-            Err(e) => {
-                error!("Failed to read attributes from public key. Error: {}", e);
-                Err(ResponseStatus::PsaErrorCommunicationFailure)
-            }
-        }
+        })
     }
 
-    fn destroy_key(&self, app_name: ApplicationName, op: OpDestroyKey) -> Result<ResultDestroyKey> {
-        info!("Pkcs11 Provider - Destroy Key");
+    fn asym_verify_message(
+        &self,
+        app_name: ApplicationName,
+        op: OpAsymVerifyMessage,
+    ) -> Result<ResultAsymVerifyMessage> {
+        info!("Pkcs11 Provider - Asym Verify Message");
 
-        let key_name = op.key_name;
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
-        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
-        let mut local_ids_handle = self.local_ids.write().expect("Local ID lock poisoned");
+        let message = op.message;
+        let signature = op.signature;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, op.key_name);
+        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
+        let key_triple =
+            key_id_managers::resolve_key_triple(&key_triple, KeyUsage::Verify, &*store_handle)?;
         let key_id = get_key_id(&key_triple, &*store_handle)?;
 
         let session = Session::new(self, ReadWriteSession::ReadWrite)?;
         info!(
-            "Deleting RSA keypair in session {}",
+            "Asymmetric verify message in session {}",
             session.session_handle()
         );
 
-        match self.find_key(session.session_handle(), key_id, KeyPairType::Any) {
-            Ok(key) => {
-                match self.backend.destroy_object(session.session_handle(), key) {
-                    Ok(_) => info!("Private part of the key destroyed successfully."),
-                    Err(e) => {
-                        error!("Failed to destroy private part of the key. Error: {}", e);
-                        return Err(ResponseStatus::PsaErrorGenericError);
-                    }
-                };
-            }
-            Err(e) => {
-                error!("Error destroying key: {}", e);
-                return Err(e);
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PublicKey)?;
+            info!("Located public key.");
+
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_VERIFY)? {
+                error!("The key is not permitted to verify.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
             }
-        };
+            let key_type = self.get_key_type(&session, key)?;
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let purpose = self.get_key_purpose(&session, key)?;
+            let (mechanism, mut pss_params) =
+                utils::sign_message_mechanism(key_type, &allowed_mechanisms, purpose)?;
+            let mech = match pss_params.as_mut() {
+                Some(params) => CK_MECHANISM {
+                    mechanism,
+                    pParameter: params as *mut CK_RSA_PKCS_PSS_PARAMS as CK_VOID_PTR,
+                    ulParameterLen: std::mem::size_of::<CK_RSA_PKCS_PSS_PARAMS>(),
+                },
+                None => CK_MECHANISM {
+                    mechanism,
+                    pParameter: std::ptr::null_mut(),
+                    ulParameterLen: 0,
+                },
+            };
 
-        // Second key is optional.
-        match self.find_key(session.session_handle(), key_id, KeyPairType::Any) {
-            Ok(key) => {
-                match self.backend.destroy_object(session.session_handle(), key) {
-                    Ok(_) => info!("Private part of the key destroyed successfully."),
-                    Err(e) => {
-                        error!("Failed to destroy private part of the key. Error: {}", e);
-                        return Err(ResponseStatus::PsaErrorGenericError);
+            match session
+                .backend()
+                .verify_init(session.session_handle(), &mech, key)
+            {
+                Ok(_) => {
+                    info!("Verify operation initialized.");
+                    match session
+                        .backend()
+                        .verify(session.session_handle(), &message, &signature)
+                    {
+                        Ok(_) => Ok(ResultAsymVerifyMessage {}),
+                        Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_INVALID))
+                        | Err(pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_LEN_RANGE)) => {
+                            info!("Signature verification failed.");
+                            Err(ResponseStatus::PsaErrorInvalidSignature)
+                        }
+                        Err(e) => Err(utils::to_response_status("C_Verify", e)),
                     }
-                };
-            }
-            // A second key is optional.
-            Err(ResponseStatus::PsaErrorDoesNotExist) => (),
-            Err(e) => {
-                error!("Error destroying key: {}", e);
-                return Err(e);
+                }
+                Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_INVALID)) => {
+                    error!(
+                        "The token does not support hash-and-sign mechanism {}.",
+                        mechanism
+                    );
+                    Err(ResponseStatus::PsaErrorNotSupported)
+                }
+                Err(e) => Err(utils::to_response_status("C_VerifyInit", e)),
             }
-        };
-
-        remove_key_id(
-            &key_triple,
-            key_id,
-            &mut *store_handle,
-            &mut local_ids_handle,
-        )?;
-
-        Ok(ResultDestroyKey {})
+        })
     }
 
-    fn asym_sign(&self, app_name: ApplicationName, op: OpAsymSign) -> Result<ResultAsymSign> {
-        info!("Pkcs11 Provider - Asym Sign");
+    fn raw_key_agreement(
+        &self,
+        app_name: ApplicationName,
+        op: OpRawKeyAgreement,
+    ) -> Result<ResultRawKeyAgreement> {
+        info!("Pkcs11 Provider - Raw Key Agreement");
 
-        let key_name = op.key_name;
-        let hash = op.hash;
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
+        let mut peer_key = op.peer_key;
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, op.private_key_name);
         let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
         let key_id = get_key_id(&key_triple, &*store_handle)?;
 
-        let mech = pkcs11::types::CK_MECHANISM {
-            mechanism: pkcs11::types::CKM_RSA_PKCS,
-            pParameter: std::ptr::null_mut(),
-            ulParameterLen: 0,
-        };
-
         let session = Session::new(self, ReadWriteSession::ReadWrite)?;
-        info!("Asymmetric sign in session {}", session.session_handle());
+        info!("Raw key agreement in session {}", session.session_handle());
 
-        let key = self.find_key(session.session_handle(), key_id, KeyPairType::PrivateKey)?;
-        info!("Located signing key.");
+        self.with_key_objects(key_id, || {
+            let key = self.find_key(&session, key_id, KeyPairType::PrivateKey)?;
+            info!("Located private key.");
 
-        match self.backend.sign_init(session.session_handle(), &mech, key) {
-            Ok(_) => {
-                info!("Signing operation initialized.");
-                match self.backend.sign(session.session_handle(), &hash) {
-                    Ok(signature) => Ok(ResultAsymSign { signature }),
-                    Err(e) => {
-                        error!("Failed to execute signing operation. Error: {}", e);
-                        Err(ResponseStatus::PsaErrorGenericError)
-                    }
-                }
+            if !self.get_attribute_bool(&session, key, pkcs11::types::CKA_DERIVE)? {
+                error!("The key is not permitted to derive.");
+                return Err(ResponseStatus::PsaErrorNotPermitted);
             }
-            Err(e) => {
-                error!("Failed to initialize signing operation. Error: {}", e);
-                Err(ResponseStatus::PsaErrorGenericError)
+            let allowed_mechanisms = self.get_allowed_mechanisms(&session, key)?;
+            let mut ecdh_params = utils::ecdh_params(&allowed_mechanisms, &mut peer_key)?;
+            let mech = CK_MECHANISM {
+                mechanism: pkcs11::types::CKM_ECDH1_DERIVE,
+                pParameter: &mut ecdh_params as *mut CK_ECDH1_DERIVE_PARAMS as CK_VOID_PTR,
+                ulParameterLen: std::mem::size_of::<CK_ECDH1_DERIVE_PARAMS>(),
+            };
+
+            // The shared secret is derived as a session object which is read and destroyed straight
+            // away.
+            let template = vec![
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_CLASS)
+                    .with_ck_ulong(&pkcs11::types::CKO_SECRET_KEY),
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_KEY_TYPE)
+                    .with_ck_ulong(&pkcs11::types::CKK_GENERIC_SECRET),
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_TOKEN).with_bool(&pkcs11::types::CK_FALSE),
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_SENSITIVE).with_bool(&pkcs11::types::CK_FALSE),
+                CK_ATTRIBUTE::new(pkcs11::types::CKA_EXTRACTABLE)
+                    .with_bool(&pkcs11::types::CK_TRUE),
+            ];
+
+            let secret =
+                match session
+                    .backend()
+                    .derive_key(session.session_handle(), &mech, key, &template)
+                {
+                    Ok(secret) => secret,
+                    Err(pkcs11::errors::Error::Pkcs11(CKR_MECHANISM_PARAM_INVALID)) => {
+                        error!("Invalid peer public key.");
+                        return Err(ResponseStatus::PsaErrorInvalidArgument);
+                    }
+                    Err(e) => return Err(utils::to_response_status("C_DeriveKey", e)),
+                };
+            let shared_secret =
+                self.get_attribute_bytes(&session, secret, pkcs11::types::CKA_VALUE);
+            if let Err(e) = session
+                .backend()
+                .destroy_object(session.session_handle(), secret)
+            {
+                error!("Failed to destroy the shared secret object. Error: {}", e);
             }
+
+            Ok(ResultRawKeyAgreement {
+                shared_secret: shared_secret?,
+            })
+        })
+    }
+
+    fn generate_random(&self, op: OpGenerateRandom) -> Result<ResultGenerateRandom> {
+        info!("Pkcs11 Provider - Generate Random");
+
+        let session = Session::new(self, ReadWriteSession::ReadOnly)?;
+        info!("Generate random in session {}", session.session_handle());
+
+        match session
+            .backend()
+            .generate_random(session.session_handle(), op.size)
+        {
+            Ok(random_bytes) => Ok(ResultGenerateRandom { random_bytes }),
+            Err(e) => Err(utils::to_response_status("C_GenerateRandom", e)),
         }
     }
 
-    fn asym_verify(&self, app_name: ApplicationName, op: OpAsymVerify) -> Result<ResultAsymVerify> {
-        info!("Pkcs11 Provider - Asym Verify");
+    fn grant_key(&self, app_name: ApplicationName, op: OpGrantKey) -> Result<ResultGrantKey> {
+        info!("Pkcs11 Provider - Grant Key");
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        key_id_managers::grant_key(
+            &key_triple,
+            ApplicationName::new(op.grantee),
+            op.permit_sign,
+            op.permit_verify,
+            op.permit_export,
+            &mut *store_handle,
+        )?;
 
-        let key_name = op.key_name;
-        let hash = op.hash;
-        let signature = op.signature;
-        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name);
-        let store_handle = self.key_id_store.read().expect("Key store lock poisoned");
-        let key_id = get_key_id(&key_triple, &*store_handle)?;
+        Ok(ResultGrantKey {})
+    }
 
-        let mech = pkcs11::types::CK_MECHANISM {
-            mechanism: pkcs11::types::CKM_RSA_PKCS,
-            pParameter: std::ptr::null_mut(),
-            ulParameterLen: 0,
-        };
+    fn revoke_key(&self, app_name: ApplicationName, op: OpRevokeKey) -> Result<ResultRevokeKey> {
+        info!("Pkcs11 Provider - Revoke Key");
+        let key_triple = KeyTriple::new(app_name, ProviderID::Pkcs11Provider, op.key_name);
+        let mut store_handle = self.key_id_store.write().expect("Key store lock poisoned");
+        key_id_managers::revoke_key(
+            &key_triple,
+            ApplicationName::new(op.grantee),
+            &mut *store_handle,
+        )?;
 
-        let session = Session::new(self, ReadWriteSession::ReadWrite)?;
-        info!("Asymmetric verify in session {}", session.session_handle());
+        Ok(ResultRevokeKey {})
+    }
+}
 
-        let key = self.find_key(session.session_handle(), key_id, KeyPairType::PublicKey)?;
-        info!("Located public key.");
+// Criteria selecting the token to use, the ones which are set must all match.
+#[derive(Default, Debug)]
+struct TokenMatcher {
+    slot_number: Option<CK_SLOT_ID>,
+    label: Option<String>,
+    serial_number: Option<String>,
+    manufacturer: Option<String>,
+    model: Option<String>,
+}
 
-        match self
-            .backend
-            .verify_init(session.session_handle(), &mech, key)
-        {
-            Ok(_) => {
-                info!("Verify operation initialized.");
-                match self
-                    .backend
-                    .verify(session.session_handle(), &hash, &signature)
-                {
-                    Ok(_) => Ok(ResultAsymVerify {}),
-                    Err(e) => match e {
-                        pkcs11::errors::Error::Pkcs11(CKR_SIGNATURE_INVALID) => {
-                            info!("Signature verification failed.");
-                            Err(ResponseStatus::PsaErrorInvalidSignature)
-                        }
-                        err => {
-                            error!("Failed to execute verify operation. Error: {}", err);
-                            Err(ResponseStatus::PsaErrorGenericError)
-                        }
-                    },
+impl TokenMatcher {
+    fn is_empty(&self) -> bool {
+        self.slot_number.is_none()
+            && self.label.is_none()
+            && self.serial_number.is_none()
+            && self.manufacturer.is_none()
+            && self.model.is_none()
+    }
+
+    fn matches(&self, slot: CK_SLOT_ID, token_info: &CK_TOKEN_INFO) -> bool {
+        let field_matches = |expected: &Option<String>, field: &[u8]| match expected {
+            Some(expected) => *expected == utils::padded_string(field),
+            None => true,
+        };
+        self.slot_number
+            .map_or(true, |slot_number| slot_number == slot)
+            && field_matches(&self.label, &token_info.label)
+            && field_matches(&self.serial_number, &token_info.serialNumber)
+            && field_matches(&self.manufacturer, &token_info.manufacturerID)
+            && field_matches(&self.model, &token_info.model)
+    }
+}
+
+/// Finds the slot of the only token matching the criteria among the slots with a token present.
+/// Returns `None` if no token or more than one match.
+fn find_slot(backend: &Ctx, matcher: &TokenMatcher) -> Option<CK_SLOT_ID> {
+    let slots = match backend.get_slot_list(true) {
+        Ok(slots) => slots,
+        Err(e) => {
+            error!("Failed to list the slots with a token: {}.", e);
+            return None;
+        }
+    };
+
+    let mut matching_slots = Vec::new();
+    for slot in slots {
+        match backend.get_token_info(slot) {
+            Ok(token_info) => {
+                if matcher.matches(slot, &token_info) {
+                    matching_slots.push(slot);
                 }
             }
-            Err(e) => {
-                error!("Failed to initialize signing operation. Error: {}", e);
-                Err(ResponseStatus::PsaErrorGenericError)
-            }
+            Err(e) => warn!(
+                "Failed to read the information of the token in slot {}: {}, ignoring it.",
+                slot, e
+            ),
+        }
+    }
+
+    match matching_slots.as_slice() {
+        [slot] => {
+            info!("Using the token in slot {}.", slot);
+            Some(*slot)
+        }
+        [] => {
+            error!("No token matches {:?}.", matcher);
+            None
+        }
+        _ => {
+            error!(
+                "The tokens in slots {:?} all match {:?}, set more criteria to select one.",
+                matching_slots, matcher
+            );
+            None
         }
     }
 }
 
+// Arguments of `C_Initialize`, allowing the PKCS 11 library to use OS native locking mechanism.
+fn initialize_args() -> CK_C_INITIALIZE_ARGS {
+    let mut args = CK_C_INITIALIZE_ARGS::new();
+    args.CreateMutex = None;
+    args.DestroyMutex = None;
+    args.LockMutex = None;
+    args.UnlockMutex = None;
+    args.flags = CKF_OS_LOCKING_OK;
+    args
+}
+
 #[derive(Default)]
 pub struct Pkcs11ProviderBuilder {
     key_id_store: Option<Arc<RwLock<dyn ManageKeyIDs + Send + Sync>>>,
     pkcs11_library_path: Option<String>,
     slot_number: Option<usize>,
-    user_pin: Option<String>,
+    token_label: Option<String>,
+    token_serial_number: Option<String>,
+    token_manufacturer: Option<String>,
+    token_model: Option<String>,
+    user_pin: Option<PinSource>,
+    min_sessions: Option<usize>,
+    max_sessions: Option<usize>,
+    rebuild_key_id_mappings: Option<bool>,
+    adopted_keys: Vec<(KeyTriple, TokenObject)>,
 }
 
 impl Pkcs11ProviderBuilder {
@@ -914,7 +2651,15 @@ impl Pkcs11ProviderBuilder {
             key_id_store: None,
             pkcs11_library_path: None,
             slot_number: None,
+            token_label: None,
+            token_serial_number: None,
+            token_manufacturer: None,
+            token_model: None,
             user_pin: None,
+            min_sessions: None,
+            max_sessions: None,
+            rebuild_key_id_mappings: None,
+            adopted_keys: Vec::new(),
         }
     }
 
@@ -936,18 +2681,83 @@ impl Pkcs11ProviderBuilder {
         self
     }
 
-    pub fn with_slot_number(mut self, slot_number: usize) -> Pkcs11ProviderBuilder {
-        self.slot_number = Some(slot_number);
+    pub fn with_slot_number(mut self, slot_number: Option<usize>) -> Pkcs11ProviderBuilder {
+        self.slot_number = slot_number;
+
+        self
+    }
+
+    pub fn with_token_label(mut self, token_label: Option<String>) -> Pkcs11ProviderBuilder {
+        self.token_label = token_label;
+
+        self
+    }
+
+    pub fn with_token_serial_number(
+        mut self,
+        token_serial_number: Option<String>,
+    ) -> Pkcs11ProviderBuilder {
+        self.token_serial_number = token_serial_number;
+
+        self
+    }
+
+    pub fn with_token_manufacturer(
+        mut self,
+        token_manufacturer: Option<String>,
+    ) -> Pkcs11ProviderBuilder {
+        self.token_manufacturer = token_manufacturer;
+
+        self
+    }
+
+    pub fn with_token_model(mut self, token_model: Option<String>) -> Pkcs11ProviderBuilder {
+        self.token_model = token_model;
 
         self
     }
 
-    pub fn with_user_pin(mut self, user_pin: Option<String>) -> Pkcs11ProviderBuilder {
+    pub fn with_user_pin(mut self, user_pin: Option<PinSource>) -> Pkcs11ProviderBuilder {
         self.user_pin = user_pin;
 
         self
     }
 
+    pub fn with_min_sessions(mut self, min_sessions: Option<usize>) -> Pkcs11ProviderBuilder {
+        self.min_sessions = min_sessions;
+
+        self
+    }
+
+    pub fn with_max_sessions(mut self, max_sessions: Option<usize>) -> Pkcs11ProviderBuilder {
+        self.max_sessions = max_sessions;
+
+        self
+    }
+
+    pub fn with_rebuild_key_id_mappings(
+        mut self,
+        rebuild_key_id_mappings: Option<bool>,
+    ) -> Pkcs11ProviderBuilder {
+        self.rebuild_key_id_mappings = rebuild_key_id_mappings;
+
+        self
+    }
+
+    pub fn with_adopted_key(
+        mut self,
+        app_name: ApplicationName,
+        key_name: String,
+        object: TokenObject,
+    ) -> Pkcs11ProviderBuilder {
+        self.adopted_keys.push((
+            KeyTriple::new(app_name, ProviderID::Pkcs11Provider, key_name),
+            object,
+        ));
+
+        self
+    }
+
     pub fn build(self) -> Pkcs11Provider {
         let library_path = self
             .pkcs11_library_path
@@ -956,24 +2766,55 @@ impl Pkcs11ProviderBuilder {
             "Building a PKCS 11 provider with library \'{}\'",
             library_path
         );
-        let slot_number = self
-            .slot_number
-            .expect("The slot number of the device is needed to communicate with PKCS 11 library.");
+        let token_matcher = TokenMatcher {
+            slot_number: self.slot_number,
+            label: self.token_label,
+            serial_number: self.token_serial_number,
+            manufacturer: self.token_manufacturer,
+            model: self.token_model,
+        };
+        if token_matcher.is_empty() {
+            panic!("The slot number, or the label, serial number, manufacturer or model of the token, is needed to select the token to use.");
+        }
+        let min_sessions = self.min_sessions.unwrap_or(session::DEFAULT_MIN_SESSIONS);
+        let max_sessions = self.max_sessions.unwrap_or(session::DEFAULT_MAX_SESSIONS);
+        if max_sessions == 0 || min_sessions > max_sessions {
+            panic!(
+                "The minimum number of sessions ({}) can not be more than the maximum ({}), which must be at least 1.",
+                min_sessions, max_sessions
+            );
+        }
         let mut backend = Ctx::new(library_path).unwrap();
-        let mut args = CK_C_INITIALIZE_ARGS::new();
-        // Allow the PKCS 11 library to use OS native locking mechanism.
-        args.CreateMutex = None;
-        args.DestroyMutex = None;
-        args.LockMutex = None;
-        args.UnlockMutex = None;
-        args.flags = CKF_OS_LOCKING_OK;
-        backend.initialize(Some(args)).unwrap();
-        Pkcs11Provider::new(
+        // The service refuses to start if the pin can not be read securely.
+        let user_pin = self.user_pin.map(|pin_source| {
+            pin_source.read().unwrap_or_else(|e| {
+                error!("{}", e);
+                panic!("Failed to read the user pin.")
+            })
+        });
+        backend.initialize(Some(initialize_args())).unwrap();
+        let slot_number = find_slot(&backend, &token_matcher)
+            .expect("Failed to find the token to use, check the token selection criteria.");
+        let pkcs11_provider = Pkcs11Provider::new(
             self.key_id_store.expect("Missing key ID store"),
             backend,
             slot_number,
-            self.user_pin,
+            token_matcher,
+            user_pin,
+            SessionPool::new(min_sessions, max_sessions),
         )
-        .expect("Failed to initialise PKCS 11 Provider")
+        .expect("Failed to initialise PKCS 11 Provider");
+        if self.rebuild_key_id_mappings.unwrap_or(false) {
+            pkcs11_provider
+                .rebuild_key_id_mappings()
+                .expect("Failed to rebuild the key ID mappings from the PKCS 11 token");
+        }
+        for (key_triple, object) in self.adopted_keys {
+            pkcs11_provider
+                .adopt_key(key_triple, &object)
+                .expect("Failed to adopt a key of the PKCS 11 token");
+        }
+
+        pkcs11_provider
     }
 }
@@ -0,0 +1,224 @@
+// Copyright (c) 2020, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Sources of the user PIN of the token.
+//!
+//! The PIN is held in memory which is zeroed when dropped.
+use log::info;
+use std::env;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use zeroize::Zeroizing;
+
+/// Environment variable set by systemd to the directory of the credentials of the service.
+const CREDENTIALS_DIRECTORY: &str = "CREDENTIALS_DIRECTORY";
+
+/// Where to read the user PIN of the token from.
+pub enum PinSource {
+    /// A file only readable and writable by its owner containing the PIN.
+    File(PathBuf),
+    /// An environment variable containing the PIN. It is removed from the environment once read.
+    EnvironmentVariable(String),
+    /// The name of a systemd credential, read from `$CREDENTIALS_DIRECTORY`.
+    SystemdCredential(String),
+    /// A command, with its arguments, printing the PIN on its standard output.
+    Command(Vec<String>),
+}
+
+impl PinSource {
+    /// Reads the PIN. A final line ending is not part of the PIN.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the PIN can not be read or if its source is not secure, for example
+    /// a file that other users can access.
+    pub fn read(&self) -> Result<Zeroizing<String>, String> {
+        let mut pin = match self {
+            PinSource::File(path) => read_pin_file(path)?,
+            PinSource::EnvironmentVariable(name) => {
+                let pin = Zeroizing::new(env::var(name).or_else(|e| {
+                    Err(format!(
+                        "Failed to read the user PIN from environment variable {}: {}",
+                        name, e
+                    ))
+                })?);
+                // Do not pass the PIN on to other processes.
+                env::remove_var(name);
+                pin
+            }
+            PinSource::SystemdCredential(name) => {
+                let directory = env::var_os(CREDENTIALS_DIRECTORY).ok_or_else(|| {
+                    format!(
+                        "${} is not set, the service must be started by systemd with the {} credential.",
+                        CREDENTIALS_DIRECTORY, name
+                    )
+                })?;
+                read_pin_file(&Path::new(&directory).join(name))?
+            }
+            PinSource::Command(command) => run_pin_command(command)?,
+        };
+
+        let len = pin.trim_end_matches(|c| c == '\n' || c == '\r').len();
+        pin.truncate(len);
+        if pin.is_empty() {
+            return Err("The user PIN is empty.".to_string());
+        }
+        Ok(pin)
+    }
+}
+
+fn read_pin_file(path: &Path) -> Result<Zeroizing<String>, String> {
+    let metadata = fs::metadata(path).or_else(|e| {
+        Err(format!(
+            "Failed to read the user PIN file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    // Another user owning the file could change its mode or content.
+    let euid = unsafe { libc::geteuid() };
+    if metadata.uid() != euid {
+        return Err(format!(
+            "The user PIN file {} is owned by user {}, it must be owned by the user running the service ({}).",
+            path.display(),
+            metadata.uid(),
+            euid
+        ));
+    }
+    let mode = metadata.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(format!(
+            "The user PIN file {} can be accessed by other users (mode {:o}), its mode must be 600 or stricter.",
+            path.display(),
+            mode & 0o777
+        ));
+    }
+
+    info!("Reading the user PIN from {}.", path.display());
+    let pin = fs::read_to_string(path).or_else(|e| {
+        Err(format!(
+            "Failed to read the user PIN file {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    Ok(Zeroizing::new(pin))
+}
+
+fn run_pin_command(command: &[String]) -> Result<Zeroizing<String>, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "The user PIN command is empty.".to_string())?;
+
+    info!("Reading the user PIN from the output of {}.", program);
+    let output = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .or_else(|e| {
+            Err(format!(
+                "Failed to run the user PIN command {}: {}",
+                program, e
+            ))
+        })?;
+    let stdout = Zeroizing::new(output.stdout);
+    if !output.status.success() {
+        return Err(format!(
+            "The user PIN command {} failed with {}.",
+            program, output.status
+        ));
+    }
+
+    let pin = std::str::from_utf8(&stdout).or_else(|_| {
+        Err(format!(
+            "The output of the user PIN command {} is not valid UTF-8.",
+            program
+        ))
+    })?;
+    Ok(Zeroizing::new(pin.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    fn pin_file(name: &str, mode: u32) -> PathBuf {
+        let path = env::temp_dir().join(format!("parsec-{}-{}", name, std::process::id()));
+        let mut file = fs::File::create(&path).unwrap();
+        file.write_all(b"123456\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn file_source() {
+        let path = pin_file("pin-file", 0o600);
+        assert_eq!(
+            PinSource::File(path.clone()).read().unwrap().as_str(),
+            "123456"
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn insecure_file_source() {
+        let path = pin_file("insecure-pin-file", 0o644);
+        assert!(PinSource::File(path.clone()).read().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn foreign_file_source() {
+        // Only root can give the file to another user.
+        if unsafe { libc::geteuid() } != 0 {
+            return;
+        }
+        let path = pin_file("foreign-pin-file", 0o600);
+        let c_path = std::ffi::CString::new(path.to_str().unwrap()).unwrap();
+        assert_eq!(unsafe { libc::chown(c_path.as_ptr(), 65534, 65534) }, 0);
+        assert!(PinSource::File(path.clone()).read().is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn environment_variable_source() {
+        let name = "PARSEC_TEST_USER_PIN";
+        env::set_var(name, "654321");
+        assert_eq!(
+            PinSource::EnvironmentVariable(name.to_string())
+                .read()
+                .unwrap()
+                .as_str(),
+            "654321"
+        );
+        assert!(env::var(name).is_err());
+    }
+
+    #[test]
+    fn command_source() {
+        let command = vec!["echo".to_string(), "abcdef".to_string()];
+        assert_eq!(
+            PinSource::Command(command).read().unwrap().as_str(),
+            "abcdef"
+        );
+        assert!(PinSource::Command(vec!["false".to_string()])
+            .read()
+            .is_err());
+        assert!(PinSource::Command(Vec::new()).read().is_err());
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright (c) 2020, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Pool of PKCS 11 sessions shared by the operations of the provider.
+//!
+//! Opening a session and logging in takes several round-trips to the token, so sessions are kept
+//! open between operations. The login state is common to all the sessions of an application: the
+//! user logs in once and stays logged in for as long as one session is open, which the pool
+//! ensures by never closing its last healthy session.
+//!
+//! A session which can not be opened while the token does not answer anymore means that the
+//! token was lost, for example unplugged or restarted. The provider then tries to recover it.
+use super::{utils, Backend, KeyPairType, Pkcs11Provider};
+use log::{error, info, warn};
+use parsec_interface::requests::{ResponseStatus, Result};
+use pkcs11::types::{
+    CKF_RW_SESSION, CKF_SERIAL_SESSION, CKR_USER_ALREADY_LOGGED_IN, CKS_RO_USER_FUNCTIONS,
+    CKS_RW_USER_FUNCTIONS, CKU_USER, CK_OBJECT_HANDLE, CK_SESSION_HANDLE,
+};
+use pkcs11::Ctx;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Condvar, Mutex, RwLockReadGuard};
+
+/// Default number of sessions of each kind opened when the provider starts.
+pub const DEFAULT_MIN_SESSIONS: usize = 1;
+/// Default maximum number of sessions of each kind.
+pub const DEFAULT_MAX_SESSIONS: usize = 8;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ReadWriteSession {
+    ReadOnly,
+    ReadWrite,
+}
+
+// Sessions of one kind: the idle ones and the number of open ones, idle or in use.
+#[derive(Default)]
+struct Sessions {
+    idle: Vec<CK_SESSION_HANDLE>,
+    open: usize,
+}
+
+#[derive(Default)]
+struct PoolState {
+    read_only: Sessions,
+    read_write: Sessions,
+}
+
+impl PoolState {
+    fn sessions(&mut self, read_write: ReadWriteSession) -> &mut Sessions {
+        match read_write {
+            ReadWriteSession::ReadOnly => &mut self.read_only,
+            ReadWriteSession::ReadWrite => &mut self.read_write,
+        }
+    }
+}
+
+/// Bounded pool of open and authenticated sessions, of both read-only and read-write kinds.
+///
+/// Sessions are checked before being handed out: the ones the token does not know anymore, for
+/// example after `CKR_SESSION_HANDLE_INVALID`, are replaced and the user is logged in again if
+/// the token logged it out, for example after `CKR_USER_NOT_LOGGED_IN`.
+pub struct SessionPool {
+    state: Mutex<PoolState>,
+    // Signalled when a session is returned or closed, for the operations waiting for one when
+    // all the sessions of a kind are in use.
+    available: Condvar,
+    // Handles of the key objects already found on the token, by key ID and object type. Handles
+    // are only valid until the library is finalised, so they are forgotten with the sessions.
+    object_handles: Mutex<HashMap<([u8; 4], KeyPairType), CK_OBJECT_HANDLE>>,
+    min_sessions: usize,
+    max_sessions: usize,
+}
+
+impl SessionPool {
+    /// Creates an empty pool keeping between `min_sessions` and `max_sessions` sessions of each
+    /// kind.
+    pub fn new(min_sessions: usize, max_sessions: usize) -> SessionPool {
+        SessionPool {
+            state: Mutex::new(PoolState::default()),
+            available: Condvar::new(),
+            object_handles: Mutex::new(HashMap::new()),
+            min_sessions,
+            max_sessions,
+        }
+    }
+
+    /// Opens the minimum number of sessions of each kind, logging the user in.
+    pub(super) fn fill(&self, provider: &Pkcs11Provider, backend: &Backend) -> Result<()> {
+        if provider.user_pin.is_none() {
+            warn!("The provider has no user pin set, sessions will not be authenticated.");
+        }
+        for read_write in &[ReadWriteSession::ReadOnly, ReadWriteSession::ReadWrite] {
+            let sessions = (0..self.min_sessions)
+                .map(|_| self.take(provider, backend, *read_write))
+                .collect::<Result<Vec<CK_SESSION_HANDLE>>>()?;
+            for session in sessions {
+                self.put_back(*read_write, session);
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets all the sessions, which the PKCS 11 library closed when it was finalised, and the
+    /// object handles found with them. None must be in use.
+    pub fn clear(&self) {
+        let mut state = self.state.lock().expect("Session pool lock poisoned");
+        *state = PoolState::default();
+        self.object_handles
+            .lock()
+            .expect("Object handles lock poisoned")
+            .clear();
+        self.available.notify_all();
+    }
+
+    /// Returns the handle of the object of a key if it was already found.
+    pub(super) fn object_handle(
+        &self,
+        key_id: [u8; 4],
+        key_type: KeyPairType,
+    ) -> Option<CK_OBJECT_HANDLE> {
+        self.object_handles
+            .lock()
+            .expect("Object handles lock poisoned")
+            .get(&(key_id, key_type))
+            .copied()
+    }
+
+    /// Keeps the handle of the object of a key for the next operations using it.
+    pub(super) fn cache_object_handle(
+        &self,
+        key_id: [u8; 4],
+        key_type: KeyPairType,
+        object: CK_OBJECT_HANDLE,
+    ) {
+        self.object_handles
+            .lock()
+            .expect("Object handles lock poisoned")
+            .insert((key_id, key_type), object);
+    }
+
+    /// Checks whether handles of the objects of a key are cached.
+    pub(super) fn has_object_handles(&self, key_id: [u8; 4]) -> bool {
+        self.object_handles
+            .lock()
+            .expect("Object handles lock poisoned")
+            .keys()
+            .any(|(id, _)| *id == key_id)
+    }
+
+    /// Forgets the handles of the objects of a key, once they are destroyed or modified.
+    pub fn forget_object_handles(&self, key_id: [u8; 4]) {
+        self.object_handles
+            .lock()
+            .expect("Object handles lock poisoned")
+            .retain(|(id, _), _| *id != key_id);
+    }
+
+    /// Takes a healthy session of the given kind out of the pool, opening one if none is idle
+    /// and the maximum is not reached, waiting for one to be returned otherwise.
+    fn take(
+        &self,
+        provider: &Pkcs11Provider,
+        backend: &Backend,
+        read_write: ReadWriteSession,
+    ) -> Result<CK_SESSION_HANDLE> {
+        loop {
+            match self.reserve(read_write) {
+                Some(session) => match check_session(provider, &backend.ctx, session) {
+                    Ok(()) => return Ok(session),
+                    Err(_) => {
+                        self.close(&backend.ctx, read_write, session);
+                        continue;
+                    }
+                },
+                None => {
+                    let session = match open_session(backend, read_write) {
+                        Ok(session) => session,
+                        Err(e) => {
+                            self.release(read_write);
+                            return Err(e);
+                        }
+                    };
+                    if let Err(e) = check_session(provider, &backend.ctx, session) {
+                        self.close(&backend.ctx, read_write, session);
+                        return Err(e);
+                    }
+                    return Ok(session);
+                }
+            }
+        }
+    }
+
+    // Pops an idle session of the given kind or, if none is and the maximum is not reached,
+    // reserves the place of a new one to open and returns `None`. Waits for a session to be
+    // returned or closed otherwise.
+    fn reserve(&self, read_write: ReadWriteSession) -> Option<CK_SESSION_HANDLE> {
+        let mut state = self.state.lock().expect("Session pool lock poisoned");
+        loop {
+            let sessions = state.sessions(read_write);
+            if let Some(session) = sessions.idle.pop() {
+                return Some(session);
+            }
+            if sessions.open < self.max_sessions {
+                sessions.open += 1;
+                return None;
+            }
+            state = self
+                .available
+                .wait(state)
+                .expect("Session pool lock poisoned");
+        }
+    }
+
+    /// Returns a session taken out of the pool.
+    fn put_back(&self, read_write: ReadWriteSession, session: CK_SESSION_HANDLE) {
+        let mut state = self.state.lock().expect("Session pool lock poisoned");
+        state.sessions(read_write).idle.push(session);
+        self.available.notify_one();
+    }
+
+    /// Closes a session taken out of the pool which can not be used anymore.
+    fn close(&self, ctx: &Ctx, read_write: ReadWriteSession, session: CK_SESSION_HANDLE) {
+        // Treat this as best effort, the token might already have closed it.
+        if let Err(e) = ctx.close_session(session) {
+            info!("Failed to close session {}: {}.", session, e);
+        }
+        self.release(read_write);
+    }
+
+    // Frees the place of a session which was closed or could not be opened.
+    fn release(&self, read_write: ReadWriteSession) {
+        let mut state = self.state.lock().expect("Session pool lock poisoned");
+        state.sessions(read_write).open -= 1;
+        self.available.notify_all();
+    }
+}
+
+fn open_session(backend: &Backend, read_write: ReadWriteSession) -> Result<CK_SESSION_HANDLE> {
+    let slot_number = backend.slot_number.ok_or_else(|| {
+        error!("The PKCS 11 token is unavailable.");
+        ResponseStatus::PsaErrorCommunicationFailure
+    })?;
+    info!("Opening session on slot {}", slot_number);
+
+    let mut session_flags = CKF_SERIAL_SESSION;
+    if read_write == ReadWriteSession::ReadWrite {
+        session_flags |= CKF_RW_SESSION;
+    }
+
+    backend
+        .ctx
+        .open_session(slot_number, session_flags, None, None)
+        .or_else(|e| Err(utils::to_response_status("C_OpenSession", e)))
+}
+
+// Checks whether the token still answers, after an error getting a session from the pool.
+fn token_present(backend: &Backend) -> bool {
+    match backend.slot_number {
+        Some(slot_number) => match backend.ctx.get_token_info(slot_number) {
+            Ok(_) => true,
+            Err(e) => {
+                warn!(
+                    "Failed to read the information of the token in slot {}: {}.",
+                    slot_number, e
+                );
+                false
+            }
+        },
+        None => false,
+    }
+}
+
+// Checks that the token still knows the session and that the user is logged in, logging in
+// again if needed.
+fn check_session(provider: &Pkcs11Provider, ctx: &Ctx, session: CK_SESSION_HANDLE) -> Result<()> {
+    let session_info = ctx.get_session_info(session).or_else(|e| {
+        warn!("Session {} can not be used anymore: {}.", session, e);
+        Err(ResponseStatus::PsaErrorCommunicationFailure)
+    })?;
+
+    let user_pin = match provider.user_pin.as_ref() {
+        Some(user_pin) => user_pin,
+        None => return Ok(()),
+    };
+    if session_info.state == CKS_RO_USER_FUNCTIONS || session_info.state == CKS_RW_USER_FUNCTIONS {
+        return Ok(());
+    }
+
+    info!("Logging in session {}.", session);
+    match ctx.login(session, CKU_USER, Some(user_pin.as_str())) {
+        Ok(_) | Err(pkcs11::errors::Error::Pkcs11(CKR_USER_ALREADY_LOGGED_IN)) => Ok(()),
+        Err(e) => Err(utils::to_response_status("C_Login", e)),
+    }
+}
+
+/// A session taken out of the pool of the provider, returned to it when dropped.
+///
+/// The session keeps the backend of the provider locked for reading, the PKCS 11 library is not
+/// initialised again while it is in use.
+pub struct Session<'a> {
+    provider: &'a Pkcs11Provider,
+    backend: RwLockReadGuard<'a, Backend>,
+    session_handle: CK_SESSION_HANDLE,
+    read_write: ReadWriteSession,
+}
+
+impl Session<'_> {
+    /// Takes a session out of the pool. If the token was lost, tries once to recover it.
+    pub fn new(provider: &Pkcs11Provider, read_write: ReadWriteSession) -> Result<Session> {
+        let backend = provider.read_backend()?;
+        let (backend, session_handle) =
+            match provider.session_pool.take(provider, &backend, read_write) {
+                Ok(session_handle) => (backend, session_handle),
+                Err(e) => {
+                    if token_present(&backend) {
+                        return Err(e);
+                    }
+                    let generation = backend.generation;
+                    drop(backend);
+                    provider.token_lost(generation);
+                    let backend = provider.read_backend()?;
+                    let session_handle =
+                        provider.session_pool.take(provider, &backend, read_write)?;
+                    (backend, session_handle)
+                }
+            };
+
+        let session = Session {
+            provider,
+            backend,
+            session_handle,
+            read_write,
+        };
+        if provider.key_ids_to_check.swap(false, Ordering::SeqCst)
+            && !provider.verify_key_ids(&session)
+        {
+            provider.key_ids_to_check.store(true, Ordering::SeqCst);
+        }
+        Ok(session)
+    }
+
+    pub fn session_handle(&self) -> CK_SESSION_HANDLE {
+        self.session_handle
+    }
+
+    /// The PKCS 11 library the session was opened with.
+    pub fn backend(&self) -> &Ctx {
+        &self.backend.ctx
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        self.provider
+            .session_pool
+            .put_back(self.read_write, self.session_handle);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{PinSource, Pkcs11ProviderBuilder};
+    use super::*;
+    use crate::key_id_managers::on_disk_manager::OnDiskKeyIDManagerBuilder;
+    use std::env;
+    use std::path::PathBuf;
+    use std::sync::mpsc::{self, RecvTimeoutError};
+    use std::sync::{Arc, RwLock};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn object_handles() {
+        let pool = SessionPool::new(DEFAULT_MIN_SESSIONS, DEFAULT_MAX_SESSIONS);
+        pool.cache_object_handle([1, 2, 3, 4], KeyPairType::PrivateKey, 10);
+        pool.cache_object_handle([1, 2, 3, 4], KeyPairType::PublicKey, 11);
+        pool.cache_object_handle([5, 6, 7, 8], KeyPairType::SecretKey, 12);
+        assert_eq!(
+            pool.object_handle([1, 2, 3, 4], KeyPairType::PrivateKey),
+            Some(10)
+        );
+        assert_eq!(pool.object_handle([1, 2, 3, 4], KeyPairType::Any), None);
+
+        assert!(pool.has_object_handles([1, 2, 3, 4]));
+
+        pool.forget_object_handles([1, 2, 3, 4]);
+        assert!(!pool.has_object_handles([1, 2, 3, 4]));
+        assert_eq!(
+            pool.object_handle([1, 2, 3, 4], KeyPairType::PublicKey),
+            None
+        );
+        assert_eq!(
+            pool.object_handle([5, 6, 7, 8], KeyPairType::SecretKey),
+            Some(12)
+        );
+
+        pool.clear();
+        assert_eq!(
+            pool.object_handle([5, 6, 7, 8], KeyPairType::SecretKey),
+            None
+        );
+    }
+
+    #[test]
+    fn maximum_sessions() {
+        let pool = SessionPool::new(0, 2);
+        assert_eq!(pool.reserve(ReadWriteSession::ReadOnly), None);
+        assert_eq!(pool.reserve(ReadWriteSession::ReadOnly), None);
+        // The sessions of each kind are counted apart.
+        assert_eq!(pool.reserve(ReadWriteSession::ReadWrite), None);
+
+        pool.put_back(ReadWriteSession::ReadOnly, 1);
+        assert_eq!(pool.reserve(ReadWriteSession::ReadOnly), Some(1));
+        pool.release(ReadWriteSession::ReadOnly);
+        assert_eq!(pool.reserve(ReadWriteSession::ReadOnly), None);
+
+        let mut state = pool.state.lock().unwrap();
+        assert_eq!(state.sessions(ReadWriteSession::ReadOnly).open, 2);
+        assert_eq!(state.sessions(ReadWriteSession::ReadWrite).open, 1);
+    }
+
+    #[test]
+    fn wait_for_session() {
+        let pool = Arc::new(SessionPool::new(0, 1));
+        assert_eq!(pool.reserve(ReadWriteSession::ReadWrite), None);
+
+        let (sender, receiver) = mpsc::channel();
+        let waiting_pool = pool.clone();
+        let waiting = thread::spawn(move || {
+            let session = waiting_pool.reserve(ReadWriteSession::ReadWrite);
+            sender.send(()).unwrap();
+            session
+        });
+        // The only session is in use, the other operation waits for it.
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(200)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        pool.put_back(ReadWriteSession::ReadWrite, 7);
+        assert_eq!(waiting.join().unwrap(), Some(7));
+    }
+
+    // Uses the SoftHSM token created by tests/softhsm/setup.sh, only when SOFTHSM2_CONF points to
+    // its configuration.
+    #[test]
+    fn softhsm_sessions() {
+        if env::var_os("SOFTHSM2_CONF").is_none() {
+            return;
+        }
+        let key_id_store = OnDiskKeyIDManagerBuilder::new()
+            .with_mappings_dir_path(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/softhsm/session_mappings"),
+            )
+            .build();
+        let provider = Pkcs11ProviderBuilder::new()
+            .with_key_id_store(Arc::new(RwLock::new(key_id_store)))
+            .with_pkcs11_library_path(String::from("/usr/lib/softhsm/libsofthsm2.so"))
+            .with_token_label(Some(String::from("Parsec Tests")))
+            .with_user_pin(Some(PinSource::Command(vec![
+                String::from("echo"),
+                String::from("123456"),
+            ])))
+            .with_min_sessions(Some(2))
+            .with_max_sessions(Some(3))
+            .build();
+
+        // The minimum number of sessions of each kind is opened with the provider.
+        {
+            let mut state = provider.session_pool.state.lock().unwrap();
+            for read_write in &[ReadWriteSession::ReadOnly, ReadWriteSession::ReadWrite] {
+                let sessions = state.sessions(*read_write);
+                assert_eq!(sessions.idle.len(), 2);
+                assert_eq!(sessions.open, 2);
+            }
+        }
+
+        // The user is logged out of all the sessions, as a token could do, and logged in again
+        // when a session is taken out of the pool.
+        {
+            let session = Session::new(&provider, ReadWriteSession::ReadOnly).unwrap();
+            session.backend().logout(session.session_handle()).unwrap();
+        }
+        let session = Session::new(&provider, ReadWriteSession::ReadOnly).unwrap();
+        let session_info = session
+            .backend()
+            .get_session_info(session.session_handle())
+            .unwrap();
+        assert_eq!(session_info.state, CKS_RO_USER_FUNCTIONS);
+
+        // A cached handle the token does not know is forgotten and the key looked up again.
+        let key_id = [0xde, 0xad, 0xbe, 0xef];
+        provider
+            .session_pool
+            .cache_object_handle(key_id, KeyPairType::PublicKey, 0xdead);
+        let mut attempts = 0;
+        let result = provider.with_key_objects(key_id, || {
+            attempts += 1;
+            let key = provider.find_key(&session, key_id, KeyPairType::PublicKey)?;
+            provider.get_key_type(&session, key)
+        });
+        assert_eq!(result, Err(ResponseStatus::PsaErrorDoesNotExist));
+        assert_eq!(attempts, 2);
+        assert!(!provider.session_pool.has_object_handles(key_id));
+    }
+}
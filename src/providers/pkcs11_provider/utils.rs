@@ -0,0 +1,1728 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Conversions between the PARSEC and PKCS 11 representations of keys, algorithms and errors.
+use crate::authenticators::ApplicationName;
+use crate::key_id_managers::KeyTriple;
+use log::error;
+use parsec_interface::operations::key_attributes::*;
+use parsec_interface::requests::{ProviderID, ResponseStatus, Result};
+use pkcs11::types::{
+    CK_BBOOL, CK_BYTE_PTR, CK_ECDH1_DERIVE_PARAMS, CK_GCM_PARAMS, CK_KEY_TYPE, CK_MECHANISM_TYPE,
+    CK_RSA_PKCS_MGF_TYPE, CK_RSA_PKCS_OAEP_PARAMS, CK_RSA_PKCS_PSS_PARAMS, CK_RV, CK_ULONG,
+    CK_VOID_PTR,
+};
+use std::convert::TryInto;
+use std::mem::size_of;
+
+// DER encodings of the OBJECT IDENTIFIER of the named curves, as expected in CKA_EC_PARAMS.
+// secp256r1: 1.2.840.10045.3.1.7
+const SECP256R1_OID: [u8; 10] = [0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+// secp384r1: 1.3.132.0.34
+const SECP384R1_OID: [u8; 7] = [0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22];
+// secp521r1: 1.3.132.0.35
+const SECP521R1_OID: [u8; 7] = [0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x23];
+
+const DER_INTEGER_TAG: u8 = 0x02;
+const DER_BIT_STRING_TAG: u8 = 0x03;
+const DER_OCTET_STRING_TAG: u8 = 0x04;
+const DER_SEQUENCE_TAG: u8 = 0x30;
+// Context-specific tags of the optional fields of an ECPrivateKey.
+const DER_EC_PARAMETERS_TAG: u8 = 0xa0;
+const DER_EC_PUBLIC_KEY_TAG: u8 = 0xa1;
+
+// Length of the AES-GCM authentication tag, the default of the PSA `PSA_ALG_GCM` algorithm.
+const GCM_TAG_BITS: CK_ULONG = 128;
+
+// First component of the labels of the key objects created by the service.
+const LABEL_PREFIX: &str = "parsec";
+
+/// Returns the DER encoded parameters of the curve to set as `CKA_EC_PARAMS`.
+///
+/// # Errors
+///
+/// Only the NIST P-256, P-384 and P-521 curves are supported.
+pub fn ec_params(ecc_curve: Option<EccCurve>) -> Result<&'static [u8]> {
+    match ecc_curve {
+        Some(EccCurve::Secp256r1) => Ok(&SECP256R1_OID),
+        Some(EccCurve::Secp384r1) => Ok(&SECP384R1_OID),
+        Some(EccCurve::Secp521r1) => Ok(&SECP521R1_OID),
+        Some(curve) => {
+            error!("Curve {:?} is not supported by this provider.", curve);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+        None => {
+            error!("An ECC key type requires a curve to be specified.");
+            Err(ResponseStatus::PsaErrorInvalidArgument)
+        }
+    }
+}
+
+/// Converts back the `CKA_EC_PARAMS` value of a key to the curve it designates.
+pub fn ecc_curve_from_params(ec_params: &[u8]) -> Result<EccCurve> {
+    if ec_params == SECP256R1_OID {
+        Ok(EccCurve::Secp256r1)
+    } else if ec_params == SECP384R1_OID {
+        Ok(EccCurve::Secp384r1)
+    } else if ec_params == SECP521R1_OID {
+        Ok(EccCurve::Secp521r1)
+    } else {
+        error!(
+            "EC parameters {:x?} do not designate a supported curve.",
+            ec_params
+        );
+        Err(ResponseStatus::PsaErrorNotSupported)
+    }
+}
+
+/// Size in bits of the keys on the curve.
+pub fn ecc_curve_bits(ecc_curve: EccCurve) -> u32 {
+    match ecc_curve {
+        EccCurve::Secp384r1 => 384,
+        EccCurve::Secp521r1 => 521,
+        _ => 256,
+    }
+}
+
+/// Checks that a key of the given type can be created with the requested size. The size of an EC
+/// key is the one of its curve, 0 meaning that it is taken from the curve as with PSA.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the size is not valid for the key type.
+pub fn check_key_size(
+    key_type: CK_KEY_TYPE,
+    ecc_curve: Option<EccCurve>,
+    key_size: u32,
+) -> Result<()> {
+    let valid = match key_type {
+        pkcs11::types::CKK_EC => key_size == 0 || ecc_curve.map(ecc_curve_bits) == Some(key_size),
+        pkcs11::types::CKK_AES => key_size == 128 || key_size == 192 || key_size == 256,
+        _ => key_size != 0 && key_size % 8 == 0,
+    };
+    if valid {
+        Ok(())
+    } else {
+        error!(
+            "Key size {} is not valid for key type {}.",
+            key_size, key_type
+        );
+        Err(ResponseStatus::PsaErrorInvalidArgument)
+    }
+}
+
+/// Checks that the data is an uncompressed point, `0x04` followed by the two coordinates, on a
+/// curve whose coordinates are `curve_bytes` long.
+fn is_uncompressed_point(point: &[u8], curve_bytes: usize) -> bool {
+    point.len() == 1 + 2 * curve_bytes && point[0] == 0x04
+}
+
+/// Wraps an uncompressed EC point, in the format used by PSA for public key export, in the DER
+/// OCTET STRING expected as `CKA_EC_POINT`.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the data is not an uncompressed point on
+/// the curve designated by `ec_params`.
+pub fn ec_point_to_der(point: &[u8], ec_params: &[u8]) -> Result<Vec<u8>> {
+    let curve_bytes = (ecc_curve_bits(ecc_curve_from_params(ec_params)?) as usize + 7) / 8;
+    if !is_uncompressed_point(point, curve_bytes) {
+        error!("The EC public key is not an uncompressed point on the curve of the key.");
+        return Err(ResponseStatus::PsaErrorInvalidArgument);
+    }
+
+    // Points on the supported curves are at most 133 bytes long, the length fits in one byte.
+    let mut der = vec![DER_OCTET_STRING_TAG];
+    if point.len() >= 0x80 {
+        der.push(0x81);
+    }
+    der.push(point.len() as u8);
+    der.extend_from_slice(point);
+    Ok(der)
+}
+
+/// Extracts the uncompressed EC point from the DER OCTET STRING stored as `CKA_EC_POINT`.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the data is not a well-formed OCTET
+/// STRING.
+pub fn ec_point_from_der(der: &[u8]) -> Result<Vec<u8>> {
+    let malformed = || {
+        error!("The EC point is not a valid DER OCTET STRING.");
+        ResponseStatus::PsaErrorInvalidArgument
+    };
+    if der.len() < 2 || der[0] != DER_OCTET_STRING_TAG {
+        return Err(malformed());
+    }
+    let (len, content) = match der[1] {
+        len if len < 0x80 => (usize::from(len), &der[2..]),
+        0x81 if der.len() >= 3 => (usize::from(der[2]), &der[3..]),
+        0x82 if der.len() >= 4 => (usize::from(der[2]) << 8 | usize::from(der[3]), &der[4..]),
+        _ => return Err(malformed()),
+    };
+    if content.len() != len {
+        return Err(malformed());
+    }
+    Ok(content.to_vec())
+}
+
+/// Splits the first DER element off `der`, returning its tag, its content and the bytes following
+/// it.
+fn der_split(der: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    if der.len() < 2 {
+        return None;
+    }
+    let (len, header_len) = match der[1] {
+        len if len < 0x80 => (usize::from(len), 2),
+        0x81 if der.len() >= 3 => (usize::from(der[2]), 3),
+        0x82 if der.len() >= 4 => (usize::from(der[2]) << 8 | usize::from(der[3]), 4),
+        _ => return None,
+    };
+    let rest = &der[header_len..];
+    if rest.len() < len {
+        return None;
+    }
+    Some((der[0], &rest[..len], &rest[len..]))
+}
+
+/// Splits the first DER element off `der` if it has the expected tag.
+fn der_expect(der: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    match der_split(der) {
+        Some((found, content, rest)) if found == tag => Some((content, rest)),
+        _ => None,
+    }
+}
+
+/// Returns the content of `der` if it is exactly one DER SEQUENCE.
+fn der_sequence(der: &[u8]) -> Option<&[u8]> {
+    match der_expect(der, DER_SEQUENCE_TAG) {
+        Some((content, rest)) if rest.is_empty() => Some(content),
+        _ => None,
+    }
+}
+
+/// Returns the private key structure wrapped in a PKCS #8 `PrivateKeyInfo`, or the data as is if
+/// it is not wrapped.
+///
+/// A `PrivateKeyInfo` is told apart from the RSA and EC private key structures by its second
+/// field, the `AlgorithmIdentifier` SEQUENCE, where those have an OCTET STRING or INTEGER.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the data is not a DER SEQUENCE starting
+/// with a version INTEGER or if the `PrivateKeyInfo` is malformed.
+pub fn pkcs8_private_key(der: &[u8]) -> Result<&[u8]> {
+    let malformed = || {
+        error!("The private key is not a valid DER structure.");
+        ResponseStatus::PsaErrorInvalidArgument
+    };
+    let content = der_sequence(der).ok_or_else(malformed)?;
+    let (_version, fields) = der_expect(content, DER_INTEGER_TAG).ok_or_else(malformed)?;
+    match der_expect(fields, DER_SEQUENCE_TAG) {
+        Some((_algorithm, fields)) => {
+            let (private_key, _attributes) =
+                der_expect(fields, DER_OCTET_STRING_TAG).ok_or_else(malformed)?;
+            Ok(private_key)
+        }
+        None => Ok(der),
+    }
+}
+
+/// Extracts the private value and the uncompressed public point of a DER encoded `ECPrivateKey`
+/// (RFC 5915) on the curve designated by `ec_params`.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the structure is malformed, if the key is
+/// on another curve or if it does not contain the public key, which is needed to create the
+/// public key object.
+pub fn ec_private_key_from_der(der: &[u8], ec_params: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let malformed = || {
+        error!("The EC private key is not a valid DER ECPrivateKey.");
+        ResponseStatus::PsaErrorInvalidArgument
+    };
+    let curve_bytes = (ecc_curve_bits(ecc_curve_from_params(ec_params)?) as usize + 7) / 8;
+
+    let content = der_sequence(der).ok_or_else(malformed)?;
+    let (version, fields) = der_expect(content, DER_INTEGER_TAG).ok_or_else(malformed)?;
+    if version != [1] {
+        return Err(malformed());
+    }
+    let (private_value, mut fields) =
+        der_expect(fields, DER_OCTET_STRING_TAG).ok_or_else(malformed)?;
+    if private_value.len() != curve_bytes {
+        error!("The EC private value does not match the size of the curve.");
+        return Err(ResponseStatus::PsaErrorInvalidArgument);
+    }
+    if let Some((parameters, rest)) = der_expect(fields, DER_EC_PARAMETERS_TAG) {
+        if parameters != ec_params {
+            error!("The EC private key is not on the curve of the key attributes.");
+            return Err(ResponseStatus::PsaErrorInvalidArgument);
+        }
+        fields = rest;
+    }
+    let public_key = match der_expect(fields, DER_EC_PUBLIC_KEY_TAG) {
+        Some((public_key, _)) => public_key,
+        None => {
+            error!("The EC private key must contain its public key to be imported.");
+            return Err(ResponseStatus::PsaErrorInvalidArgument);
+        }
+    };
+    let (bits, _) = der_expect(public_key, DER_BIT_STRING_TAG).ok_or_else(malformed)?;
+    // The first byte of the BIT STRING is the number of unused bits, none for an EC point.
+    match bits.split_first() {
+        Some((&0, point)) if is_uncompressed_point(point, curve_bytes) => {
+            Ok((private_value.to_vec(), point.to_vec()))
+        }
+        _ => {
+            error!("The public key of the EC private key is not an uncompressed point.");
+            Err(ResponseStatus::PsaErrorInvalidArgument)
+        }
+    }
+}
+
+/// Converts a fixed-length string field of a PKCS 11 structure, such as the label of a token,
+/// padded with blank characters, to a `String`.
+pub fn padded_string(field: &[u8]) -> String {
+    String::from_utf8_lossy(field)
+        .trim_end_matches(|c| c == ' ' || c == '\0')
+        .to_string()
+}
+
+/// Family of algorithms a key is permitted to be used with, recorded in the label of its objects.
+/// PKCS#1 v1.5 signature and encryption keys have the same allowed mechanism, `CKM_RSA_PKCS`, and
+/// are only told apart with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+    Sign,
+    Encrypt,
+    Aead,
+    Mac,
+    KeyAgreement,
+}
+
+impl KeyPurpose {
+    /// Returns the purpose of a key permitted to be used with the algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ResponseStatus::PsaErrorNotSupported` for algorithms keys of this provider can
+    /// not be created for.
+    pub fn of_algorithm(algorithm: &Algorithm) -> Result<KeyPurpose> {
+        match algorithm.inner() {
+            AlgorithmInner::Sign(..) => Ok(KeyPurpose::Sign),
+            AlgorithmInner::AsymmetricEncryption(..) => Ok(KeyPurpose::Encrypt),
+            AlgorithmInner::Aead(..) => Ok(KeyPurpose::Aead),
+            AlgorithmInner::Mac(..) => Ok(KeyPurpose::Mac),
+            AlgorithmInner::KeyAgreement(..) => Ok(KeyPurpose::KeyAgreement),
+            _ => {
+                error!(
+                    "Algorithm {:?} is not supported by this provider.",
+                    algorithm
+                );
+                Err(ResponseStatus::PsaErrorNotSupported)
+            }
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            KeyPurpose::Sign => "sign",
+            KeyPurpose::Encrypt => "encrypt",
+            KeyPurpose::Aead => "aead",
+            KeyPurpose::Mac => "mac",
+            KeyPurpose::KeyAgreement => "key-agreement",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<KeyPurpose> {
+        [
+            KeyPurpose::Sign,
+            KeyPurpose::Encrypt,
+            KeyPurpose::Aead,
+            KeyPurpose::Mac,
+            KeyPurpose::KeyAgreement,
+        ]
+        .iter()
+        .copied()
+        .find(|purpose| purpose.name() == name)
+    }
+}
+
+/// Label (`CKA_LABEL`) of the objects of a key, from which its key triple and purpose can be found
+/// again. The application and key names are base64 encoded as they can contain the separator.
+/// Keys adopted from the token have no purpose recorded.
+pub fn key_label(key_triple: &KeyTriple, purpose: Option<KeyPurpose>) -> String {
+    let mut label = format!(
+        "{}/{}/{}",
+        LABEL_PREFIX,
+        base64::encode_config(
+            key_triple.app_name().get_name().as_bytes(),
+            base64::URL_SAFE
+        ),
+        base64::encode_config(key_triple.key_name().as_bytes(), base64::URL_SAFE)
+    );
+    if let Some(purpose) = purpose {
+        label.push('/');
+        label.push_str(purpose.name());
+    }
+    label
+}
+
+/// Finds the key triple and the purpose of a key from the label of its objects. Returns `None`
+/// if the objects were not created by the service.
+pub fn parse_key_label(label: &[u8]) -> Option<(KeyTriple, Option<KeyPurpose>)> {
+    let label = std::str::from_utf8(label).ok()?;
+    let mut parts = label.split('/');
+    if parts.next()? != LABEL_PREFIX {
+        return None;
+    }
+    let mut decode = || {
+        base64::decode_config(parts.next()?, base64::URL_SAFE)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+    };
+    let app_name = decode()?;
+    let key_name = decode()?;
+    let purpose = match parts.next() {
+        Some(name) => Some(KeyPurpose::from_name(name)?),
+        None => None,
+    };
+    if parts.next().is_some() {
+        return None;
+    }
+
+    Some((
+        KeyTriple::new(
+            ApplicationName::new(app_name),
+            ProviderID::Pkcs11Provider,
+            key_name,
+        ),
+        purpose,
+    ))
+}
+
+/// Checks whether the algorithm is ECDSA, with any hash.
+fn is_ecdsa(algorithm: &Algorithm) -> bool {
+    match algorithm.inner() {
+        AlgorithmInner::Sign(SignAlgorithm::Ecdsa, _) => true,
+        _ => false,
+    }
+}
+
+// Mechanisms used with a given hash algorithm: the digest and MGF1 mechanisms of RSA-PSS and
+// RSA-OAEP, and the hash-and-sign mechanisms of PKCS#1 v1.5, RSA-PSS and ECDSA. The PSS salt length
+// is the length of the digest. The DER encoded DigestInfo prefix is prepended to a digest signed
+// with PKCS#1 v1.5, as `CKM_RSA_PKCS` does not do it.
+struct HashMechanisms {
+    hash: HashAlgorithm,
+    hash_mechanism: CK_MECHANISM_TYPE,
+    mgf: CK_RSA_PKCS_MGF_TYPE,
+    pkcs1v15_mechanism: CK_MECHANISM_TYPE,
+    pss_mechanism: CK_MECHANISM_TYPE,
+    ecdsa_mechanism: CK_MECHANISM_TYPE,
+    salt_len: CK_ULONG,
+    digest_info: &'static [u8],
+}
+
+const HASH_MECHANISMS: [HashMechanisms; 5] = [
+    HashMechanisms {
+        hash: HashAlgorithm::Sha1,
+        hash_mechanism: pkcs11::types::CKM_SHA_1,
+        mgf: pkcs11::types::CKG_MGF1_SHA1,
+        pkcs1v15_mechanism: pkcs11::types::CKM_SHA1_RSA_PKCS,
+        pss_mechanism: pkcs11::types::CKM_SHA1_RSA_PKCS_PSS,
+        ecdsa_mechanism: pkcs11::types::CKM_ECDSA_SHA1,
+        salt_len: 20,
+        digest_info: &[
+            0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04,
+            0x14,
+        ],
+    },
+    HashMechanisms {
+        hash: HashAlgorithm::Sha224,
+        hash_mechanism: pkcs11::types::CKM_SHA224,
+        mgf: pkcs11::types::CKG_MGF1_SHA224,
+        pkcs1v15_mechanism: pkcs11::types::CKM_SHA224_RSA_PKCS,
+        pss_mechanism: pkcs11::types::CKM_SHA224_RSA_PKCS_PSS,
+        ecdsa_mechanism: pkcs11::types::CKM_ECDSA_SHA224,
+        salt_len: 28,
+        digest_info: &[
+            0x30, 0x2d, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x04, 0x05, 0x00, 0x04, 0x1c,
+        ],
+    },
+    HashMechanisms {
+        hash: HashAlgorithm::Sha256,
+        hash_mechanism: pkcs11::types::CKM_SHA256,
+        mgf: pkcs11::types::CKG_MGF1_SHA256,
+        pkcs1v15_mechanism: pkcs11::types::CKM_SHA256_RSA_PKCS,
+        pss_mechanism: pkcs11::types::CKM_SHA256_RSA_PKCS_PSS,
+        ecdsa_mechanism: pkcs11::types::CKM_ECDSA_SHA256,
+        salt_len: 32,
+        digest_info: &[
+            0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x01, 0x05, 0x00, 0x04, 0x20,
+        ],
+    },
+    HashMechanisms {
+        hash: HashAlgorithm::Sha384,
+        hash_mechanism: pkcs11::types::CKM_SHA384,
+        mgf: pkcs11::types::CKG_MGF1_SHA384,
+        pkcs1v15_mechanism: pkcs11::types::CKM_SHA384_RSA_PKCS,
+        pss_mechanism: pkcs11::types::CKM_SHA384_RSA_PKCS_PSS,
+        ecdsa_mechanism: pkcs11::types::CKM_ECDSA_SHA384,
+        salt_len: 48,
+        digest_info: &[
+            0x30, 0x41, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x02, 0x05, 0x00, 0x04, 0x30,
+        ],
+    },
+    HashMechanisms {
+        hash: HashAlgorithm::Sha512,
+        hash_mechanism: pkcs11::types::CKM_SHA512,
+        mgf: pkcs11::types::CKG_MGF1_SHA512,
+        pkcs1v15_mechanism: pkcs11::types::CKM_SHA512_RSA_PKCS,
+        pss_mechanism: pkcs11::types::CKM_SHA512_RSA_PKCS_PSS,
+        ecdsa_mechanism: pkcs11::types::CKM_ECDSA_SHA512,
+        salt_len: 64,
+        digest_info: &[
+            0x30, 0x51, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02,
+            0x03, 0x05, 0x00, 0x04, 0x40,
+        ],
+    },
+];
+
+// Mechanisms used for HMAC with a given hash algorithm.
+const HMAC_MECHANISMS: [(HashAlgorithm, CK_MECHANISM_TYPE); 5] = [
+    (HashAlgorithm::Sha1, pkcs11::types::CKM_SHA_1_HMAC),
+    (HashAlgorithm::Sha224, pkcs11::types::CKM_SHA224_HMAC),
+    (HashAlgorithm::Sha256, pkcs11::types::CKM_SHA256_HMAC),
+    (HashAlgorithm::Sha384, pkcs11::types::CKM_SHA384_HMAC),
+    (HashAlgorithm::Sha512, pkcs11::types::CKM_SHA512_HMAC),
+];
+
+/// Checks whether keys of the given type are stored as a single secret key object.
+pub fn is_secret_key_type(key_type: CK_KEY_TYPE) -> bool {
+    key_type == pkcs11::types::CKK_AES || key_type == pkcs11::types::CKK_GENERIC_SECRET
+}
+
+/// Returns the mechanisms that a key of the given type used with the algorithm is allowed to use,
+/// to be stored as `CKA_ALLOWED_MECHANISMS`. For RSA-PSS, the hash-and-sign mechanism is added to
+/// record the hash algorithm of the key, as for PKCS#1 v1.5 and ECDSA with a hash, and the digest
+/// mechanism for RSA-OAEP.
+///
+/// # Errors
+///
+/// Only PKCS#1 v1.5 signatures without hashing, RSA-PSS, PKCS#1 v1.5 encryption and RSA-OAEP with
+/// the SHA-1 and SHA-2 hashes are supported for RSA keys, ECDSA and raw ECDH for EC keys, AES-GCM
+/// and AES-CMAC for AES keys and HMAC with the SHA-1 and SHA-2 hashes for generic secret keys.
+pub fn allowed_mechanisms(
+    key_type: CK_KEY_TYPE,
+    algorithm: &Algorithm,
+) -> Result<Vec<CK_MECHANISM_TYPE>> {
+    if key_type == pkcs11::types::CKK_EC && is_ecdsa(algorithm) {
+        // The hash-and-sign mechanism records the hash algorithm, when there is one for it.
+        return Ok(
+            match HASH_MECHANISMS.iter().find(|hash_mechanisms| {
+                *algorithm == Algorithm::sign(SignAlgorithm::Ecdsa, Some(hash_mechanisms.hash))
+            }) {
+                Some(hash_mechanisms) => {
+                    vec![pkcs11::types::CKM_ECDSA, hash_mechanisms.ecdsa_mechanism]
+                }
+                None => vec![pkcs11::types::CKM_ECDSA],
+            },
+        );
+    }
+    if key_type == pkcs11::types::CKK_EC
+        && *algorithm == Algorithm::key_agreement(KeyAgreementAlgorithm::Ecdh, None, None)
+    {
+        return Ok(vec![pkcs11::types::CKM_ECDH1_DERIVE]);
+    }
+    if key_type == pkcs11::types::CKK_AES && *algorithm == Algorithm::aead(AeadAlgorithm::Gcm) {
+        return Ok(vec![pkcs11::types::CKM_AES_GCM]);
+    }
+    if key_type == pkcs11::types::CKK_AES && *algorithm == Algorithm::mac(MacAlgorithm::Cmac, None)
+    {
+        return Ok(vec![pkcs11::types::CKM_AES_CMAC]);
+    }
+    if key_type == pkcs11::types::CKK_GENERIC_SECRET {
+        for (hash, mechanism) in HMAC_MECHANISMS.iter() {
+            if *algorithm == Algorithm::mac(MacAlgorithm::Hmac, Some(*hash)) {
+                return Ok(vec![*mechanism]);
+            }
+        }
+    }
+    if key_type != pkcs11::types::CKK_RSA {
+        error!(
+            "Algorithm {:?} can not be used with key type {}.",
+            algorithm, key_type
+        );
+        return Err(ResponseStatus::PsaErrorNotSupported);
+    }
+    if *algorithm == Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None)
+        || *algorithm
+            == Algorithm::asymmetric_encryption(
+                AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt,
+                None,
+            )
+    {
+        return Ok(vec![pkcs11::types::CKM_RSA_PKCS]);
+    }
+    for hash_mechanisms in HASH_MECHANISMS.iter() {
+        if *algorithm == Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, Some(hash_mechanisms.hash))
+        {
+            return Ok(vec![
+                pkcs11::types::CKM_RSA_PKCS,
+                hash_mechanisms.pkcs1v15_mechanism,
+            ]);
+        }
+        if *algorithm == Algorithm::sign(SignAlgorithm::RsaPss, Some(hash_mechanisms.hash)) {
+            return Ok(vec![
+                pkcs11::types::CKM_RSA_PKCS_PSS,
+                hash_mechanisms.pss_mechanism,
+            ]);
+        }
+        if *algorithm
+            == Algorithm::asymmetric_encryption(
+                AsymmetricEncryptionAlgorithm::RsaOaep,
+                Some(hash_mechanisms.hash),
+            )
+        {
+            return Ok(vec![
+                pkcs11::types::CKM_RSA_PKCS_OAEP,
+                hash_mechanisms.hash_mechanism,
+            ]);
+        }
+    }
+    error!(
+        "Algorithm {:?} is not supported by this provider.",
+        algorithm
+    );
+    Err(ResponseStatus::PsaErrorNotSupported)
+}
+
+/// Converts back the allowed mechanisms of a key to the algorithm it was created for. Keys
+/// without allowed mechanisms are assumed to be PKCS#1 v1.5 RSA keys or ECDSA keys. As PKCS#1
+/// v1.5 signature and encryption share the same mechanism, the purpose recorded in the label of
+/// the key tells which one an RSA key is used for. Keys without a purpose are signing keys.
+pub fn algorithm_from_mechanisms(
+    key_type: CK_KEY_TYPE,
+    mechanisms: &[CK_MECHANISM_TYPE],
+    purpose: Option<KeyPurpose>,
+) -> Result<Algorithm> {
+    match key_type {
+        pkcs11::types::CKK_EC => {
+            if mechanisms.contains(&pkcs11::types::CKM_ECDH1_DERIVE) {
+                Ok(Algorithm::key_agreement(
+                    KeyAgreementAlgorithm::Ecdh,
+                    None,
+                    None,
+                ))
+            } else {
+                Ok(Algorithm::sign(
+                    SignAlgorithm::Ecdsa,
+                    ecdsa_hash(mechanisms).map(|hash_mechanisms| hash_mechanisms.hash),
+                ))
+            }
+        }
+        pkcs11::types::CKK_AES => {
+            if mechanisms.contains(&pkcs11::types::CKM_AES_CMAC) {
+                Ok(Algorithm::mac(MacAlgorithm::Cmac, None))
+            } else {
+                Ok(Algorithm::aead(AeadAlgorithm::Gcm))
+            }
+        }
+        pkcs11::types::CKK_GENERIC_SECRET => HMAC_MECHANISMS
+            .iter()
+            .find(|(_, mechanism)| mechanisms.contains(mechanism))
+            .map(|(hash, _)| Algorithm::mac(MacAlgorithm::Hmac, Some(*hash)))
+            .ok_or_else(|| {
+                error!("Generic secret keys are only recognised for HMAC.");
+                ResponseStatus::PsaErrorNotSupported
+            }),
+        pkcs11::types::CKK_RSA => {
+            if let Some(hash_mechanisms) = pss_hash(mechanisms) {
+                Ok(Algorithm::sign(
+                    SignAlgorithm::RsaPss,
+                    Some(hash_mechanisms.hash),
+                ))
+            } else if let Some(hash_mechanisms) = oaep_hash(mechanisms) {
+                Ok(Algorithm::asymmetric_encryption(
+                    AsymmetricEncryptionAlgorithm::RsaOaep,
+                    Some(hash_mechanisms.hash),
+                ))
+            } else if let Some(hash_mechanisms) = pkcs1v15_hash(mechanisms) {
+                Ok(Algorithm::sign(
+                    SignAlgorithm::RsaPkcs1v15Sign,
+                    Some(hash_mechanisms.hash),
+                ))
+            } else if purpose == Some(KeyPurpose::Encrypt) {
+                Ok(Algorithm::asymmetric_encryption(
+                    AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt,
+                    None,
+                ))
+            } else {
+                Ok(Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None))
+            }
+        }
+        _ => {
+            error!("Key type {} has no native equivalent.", key_type);
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+fn pss_hash(mechanisms: &[CK_MECHANISM_TYPE]) -> Option<&'static HashMechanisms> {
+    HASH_MECHANISMS
+        .iter()
+        .find(|hash_mechanisms| mechanisms.contains(&hash_mechanisms.pss_mechanism))
+}
+
+fn pkcs1v15_hash(mechanisms: &[CK_MECHANISM_TYPE]) -> Option<&'static HashMechanisms> {
+    HASH_MECHANISMS
+        .iter()
+        .find(|hash_mechanisms| mechanisms.contains(&hash_mechanisms.pkcs1v15_mechanism))
+}
+
+fn ecdsa_hash(mechanisms: &[CK_MECHANISM_TYPE]) -> Option<&'static HashMechanisms> {
+    HASH_MECHANISMS
+        .iter()
+        .find(|hash_mechanisms| mechanisms.contains(&hash_mechanisms.ecdsa_mechanism))
+}
+
+fn pss_params(hash_mechanisms: &HashMechanisms) -> CK_RSA_PKCS_PSS_PARAMS {
+    CK_RSA_PKCS_PSS_PARAMS {
+        hashAlg: hash_mechanisms.hash_mechanism,
+        mgf: hash_mechanisms.mgf,
+        sLen: hash_mechanisms.salt_len,
+    }
+}
+
+fn oaep_hash(mechanisms: &[CK_MECHANISM_TYPE]) -> Option<&'static HashMechanisms> {
+    if !mechanisms.contains(&pkcs11::types::CKM_RSA_PKCS_OAEP) {
+        return None;
+    }
+    HASH_MECHANISMS
+        .iter()
+        .find(|hash_mechanisms| mechanisms.contains(&hash_mechanisms.hash_mechanism))
+}
+
+/// Serialises a list of mechanisms to the value of `CKA_ALLOWED_MECHANISMS`.
+pub fn mechanisms_to_bytes(mechanisms: &[CK_MECHANISM_TYPE]) -> Vec<u8> {
+    mechanisms
+        .iter()
+        .flat_map(|mechanism| mechanism.to_ne_bytes().to_vec())
+        .collect()
+}
+
+/// Parses the value of `CKA_ALLOWED_MECHANISMS` to a list of mechanisms.
+pub fn mechanisms_from_bytes(bytes: &[u8]) -> Vec<CK_MECHANISM_TYPE> {
+    bytes
+        .chunks_exact(size_of::<CK_MECHANISM_TYPE>())
+        .map(|chunk| CK_MECHANISM_TYPE::from_ne_bytes(chunk.try_into().unwrap()))
+        .collect()
+}
+
+/// Returns the PKCS 11 mechanism, and its parameters if any, to sign or verify with a key of the
+/// given type, allowed mechanisms and purpose.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for signing, as PSA
+/// does for a key whose permitted algorithm is not a signature algorithm. The check does not rely
+/// on the token enforcing `CKA_ALLOWED_MECHANISMS`.
+pub fn sign_mechanism(
+    key_type: CK_KEY_TYPE,
+    mechanisms: &[CK_MECHANISM_TYPE],
+    purpose: Option<KeyPurpose>,
+) -> Result<(CK_MECHANISM_TYPE, Option<CK_RSA_PKCS_PSS_PARAMS>)> {
+    // Keys without a purpose were created before it was recorded, for signing.
+    if purpose.map_or(false, |purpose| purpose != KeyPurpose::Sign) {
+        error!("The key was not created for signing.");
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    match key_type {
+        pkcs11::types::CKK_RSA => {
+            if let Some(hash_mechanisms) = pss_hash(mechanisms) {
+                return Ok((
+                    pkcs11::types::CKM_RSA_PKCS_PSS,
+                    Some(pss_params(hash_mechanisms)),
+                ));
+            }
+            // PKCS#1 v1.5 signature and encryption keys share the `CKM_RSA_PKCS` mechanism, they
+            // are told apart by their purpose checked above. Keys without allowed mechanisms have
+            // no restriction.
+            if mechanisms.is_empty()
+                || mechanisms == [pkcs11::types::CKM_RSA_PKCS]
+                || pkcs1v15_hash(mechanisms).is_some()
+            {
+                return Ok((pkcs11::types::CKM_RSA_PKCS, None));
+            }
+        }
+        pkcs11::types::CKK_EC => {
+            if mechanisms.is_empty() || mechanisms.contains(&pkcs11::types::CKM_ECDSA) {
+                return Ok((pkcs11::types::CKM_ECDSA, None));
+            }
+        }
+        _ => {
+            error!("Key type {} can not be used for signing.", key_type);
+            return Err(ResponseStatus::PsaErrorNotSupported);
+        }
+    }
+    error!("The key was not created for signing.");
+    Err(ResponseStatus::PsaErrorNotPermitted)
+}
+
+/// Returns the data to sign or verify with `CKM_RSA_PKCS` for the given digest. For PKCS#1 v1.5
+/// keys with a hash algorithm, the digest is wrapped in a DigestInfo structure. Other keys sign
+/// the digest as it is.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorInvalidArgument` if the length of the digest does not match
+/// the hash algorithm of the key.
+pub fn sign_input(mechanisms: &[CK_MECHANISM_TYPE], digest: Vec<u8>) -> Result<Vec<u8>> {
+    // The salt of RSA-PSS is as long as the digest.
+    if let Some(hash_mechanisms) = pss_hash(mechanisms).or_else(|| ecdsa_hash(mechanisms)) {
+        if digest.len() != hash_mechanisms.salt_len {
+            error!("The digest length does not match the hash algorithm of the key.");
+            return Err(ResponseStatus::PsaErrorInvalidArgument);
+        }
+        return Ok(digest);
+    }
+    match pkcs1v15_hash(mechanisms) {
+        Some(hash_mechanisms) => {
+            // The last byte of the prefix is the length of the digest.
+            if Some(&(digest.len() as u8)) != hash_mechanisms.digest_info.last()
+                || digest.len() > u8::max_value() as usize
+            {
+                error!("The digest length does not match the hash algorithm of the key.");
+                return Err(ResponseStatus::PsaErrorInvalidArgument);
+            }
+            let mut input = hash_mechanisms.digest_info.to_vec();
+            input.extend_from_slice(&digest);
+            Ok(input)
+        }
+        None => Ok(digest),
+    }
+}
+
+/// Returns the PKCS 11 hash-and-sign mechanism, and its parameters if any, to sign or verify a
+/// message with a key of the given type and allowed mechanisms.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for signing and
+/// `ResponseStatus::PsaErrorInvalidArgument` if it was not created with a hash algorithm to hash
+/// messages with.
+pub fn sign_message_mechanism(
+    key_type: CK_KEY_TYPE,
+    mechanisms: &[CK_MECHANISM_TYPE],
+    purpose: Option<KeyPurpose>,
+) -> Result<(CK_MECHANISM_TYPE, Option<CK_RSA_PKCS_PSS_PARAMS>)> {
+    // The key must be permitted to sign in the first place.
+    sign_mechanism(key_type, mechanisms, purpose)?;
+    let mechanism = match key_type {
+        pkcs11::types::CKK_RSA => {
+            if let Some(hash_mechanisms) = pss_hash(mechanisms) {
+                return Ok((
+                    hash_mechanisms.pss_mechanism,
+                    Some(pss_params(hash_mechanisms)),
+                ));
+            }
+            pkcs1v15_hash(mechanisms).map(|hash_mechanisms| hash_mechanisms.pkcs1v15_mechanism)
+        }
+        pkcs11::types::CKK_EC => {
+            ecdsa_hash(mechanisms).map(|hash_mechanisms| hash_mechanisms.ecdsa_mechanism)
+        }
+        _ => {
+            error!("Key type {} can not be used for signing.", key_type);
+            return Err(ResponseStatus::PsaErrorNotSupported);
+        }
+    };
+    match mechanism {
+        Some(mechanism) => Ok((mechanism, None)),
+        None => {
+            error!("The key was not created with a hash algorithm to hash messages with.");
+            Err(ResponseStatus::PsaErrorInvalidArgument)
+        }
+    }
+}
+
+/// Returns the PKCS 11 mechanism, and its parameters if any, to encrypt or decrypt with an RSA
+/// key with the given allowed mechanisms and purpose. The parameters of RSA-OAEP point to the
+/// label which must outlive them.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for encryption.
+pub fn encrypt_mechanism(
+    mechanisms: &[CK_MECHANISM_TYPE],
+    purpose: Option<KeyPurpose>,
+    label: &mut [u8],
+) -> Result<(CK_MECHANISM_TYPE, Option<CK_RSA_PKCS_OAEP_PARAMS>)> {
+    if purpose.map_or(false, |purpose| purpose != KeyPurpose::Encrypt) {
+        error!("The key was not created for encryption.");
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    if let Some(hash_mechanisms) = oaep_hash(mechanisms) {
+        Ok((
+            pkcs11::types::CKM_RSA_PKCS_OAEP,
+            Some(CK_RSA_PKCS_OAEP_PARAMS {
+                hashAlg: hash_mechanisms.hash_mechanism,
+                mgf: hash_mechanisms.mgf,
+                source: pkcs11::types::CKZ_DATA_SPECIFIED,
+                pSourceData: if label.is_empty() {
+                    std::ptr::null_mut()
+                } else {
+                    label.as_mut_ptr() as CK_VOID_PTR
+                },
+                ulSourceDataLen: label.len(),
+            }),
+        ))
+    } else if mechanisms == [pkcs11::types::CKM_RSA_PKCS] && purpose == Some(KeyPurpose::Encrypt) {
+        // Without a purpose, this is a PKCS#1 v1.5 signature key.
+        Ok((pkcs11::types::CKM_RSA_PKCS, None))
+    } else {
+        error!("The key was not created for encryption.");
+        Err(ResponseStatus::PsaErrorNotPermitted)
+    }
+}
+
+/// Returns the parameters of the `CKM_AES_GCM` mechanism for the given nonce and additional data,
+/// which must outlive them. The tag is appended to the ciphertext, as for PSA AEAD operations.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for AES-GCM.
+pub fn gcm_params(
+    mechanisms: &[CK_MECHANISM_TYPE],
+    nonce: &mut [u8],
+    additional_data: &mut [u8],
+) -> Result<CK_GCM_PARAMS> {
+    if mechanisms != [pkcs11::types::CKM_AES_GCM] {
+        error!("The key was not created for AES-GCM.");
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    Ok(CK_GCM_PARAMS {
+        pIv: nonce.as_mut_ptr() as CK_BYTE_PTR,
+        ulIvLen: nonce.len(),
+        ulIvBits: nonce.len() * 8,
+        pAAD: if additional_data.is_empty() {
+            std::ptr::null_mut()
+        } else {
+            additional_data.as_mut_ptr() as CK_BYTE_PTR
+        },
+        ulAADLen: additional_data.len(),
+        ulTagBits: GCM_TAG_BITS,
+    })
+}
+
+/// Returns the parameters of the `CKM_ECDH1_DERIVE` mechanism for a raw key agreement with the
+/// given peer public key, which must outlive them. The peer key is an uncompressed EC point.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for ECDH.
+pub fn ecdh_params(
+    mechanisms: &[CK_MECHANISM_TYPE],
+    peer_key: &mut [u8],
+) -> Result<CK_ECDH1_DERIVE_PARAMS> {
+    if mechanisms != [pkcs11::types::CKM_ECDH1_DERIVE] {
+        error!("The key was not created for ECDH.");
+        return Err(ResponseStatus::PsaErrorNotPermitted);
+    }
+    Ok(CK_ECDH1_DERIVE_PARAMS {
+        kdf: pkcs11::types::CKD_NULL,
+        ulSharedDataLen: 0,
+        pSharedData: std::ptr::null_mut(),
+        ulPublicDataLen: peer_key.len(),
+        pPublicData: peer_key.as_mut_ptr() as CK_BYTE_PTR,
+    })
+}
+
+/// Returns the PKCS 11 mechanism to compute or verify a MAC with a key with the given allowed
+/// mechanisms.
+///
+/// # Errors
+///
+/// Returns `ResponseStatus::PsaErrorNotPermitted` if the key was not created for HMAC or CMAC.
+pub fn mac_mechanism(mechanisms: &[CK_MECHANISM_TYPE]) -> Result<CK_MECHANISM_TYPE> {
+    match mechanisms {
+        [pkcs11::types::CKM_AES_CMAC] => Ok(pkcs11::types::CKM_AES_CMAC),
+        [mechanism]
+            if HMAC_MECHANISMS
+                .iter()
+                .any(|(_, hmac_mechanism)| hmac_mechanism == mechanism) =>
+        {
+            Ok(*mechanism)
+        }
+        _ => {
+            error!("The key was not created for MAC operations.");
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        }
+    }
+}
+
+/// Returns the PKCS 11 digest mechanism of a hash algorithm.
+///
+/// # Errors
+///
+/// The SHA-3 and truncated SHA-512 hashes have no PKCS 11 mechanism and are not supported.
+pub fn digest_mechanism(hash: HashAlgorithm) -> Result<CK_MECHANISM_TYPE> {
+    match hash {
+        HashAlgorithm::Md2 => Ok(pkcs11::types::CKM_MD2),
+        HashAlgorithm::Md5 => Ok(pkcs11::types::CKM_MD5),
+        HashAlgorithm::Ripemd160 => Ok(pkcs11::types::CKM_RIPEMD160),
+        HashAlgorithm::Sha1 => Ok(pkcs11::types::CKM_SHA_1),
+        HashAlgorithm::Sha224 => Ok(pkcs11::types::CKM_SHA224),
+        HashAlgorithm::Sha256 => Ok(pkcs11::types::CKM_SHA256),
+        HashAlgorithm::Sha384 => Ok(pkcs11::types::CKM_SHA384),
+        HashAlgorithm::Sha512 => Ok(pkcs11::types::CKM_SHA512),
+        _ => {
+            error!(
+                "Hash algorithm {:?} is not supported by this provider.",
+                hash
+            );
+            Err(ResponseStatus::PsaErrorNotSupported)
+        }
+    }
+}
+
+/// Compares two byte strings in a time which only depends on their length.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Converts a boolean to the PKCS 11 value to use in a template.
+pub fn ck_bool(value: bool) -> &'static CK_BBOOL {
+    if value {
+        &pkcs11::types::CK_TRUE
+    } else {
+        &pkcs11::types::CK_FALSE
+    }
+}
+
+/// Converts a PKCS 11 return value into its name and the response status to send back.
+///
+/// `PsaErrorCommunicationFailure` means that the token could not be reached, for example because
+/// it was removed: the request can be retried later, once the provider recovered the token, and
+/// the provider fails with the same status in between. `AuthenticationError` means that the
+/// service could not log in to the token, the user PIN in its configuration must be fixed; it is
+/// kept apart from `PsaErrorNotPermitted`, the key policy of the token forbidding the operation.
+/// `PsaErrorInsufficientMemory` and `PsaErrorBadState` can also be transient. The other statuses
+/// mean that the request will fail again as it is.
+pub fn rv_to_response_status(rv: CK_RV) -> (&'static str, ResponseStatus) {
+    macro_rules! ckr_table {
+        ($($code:ident => $status:ident,)*) => {
+            match rv {
+                $(pkcs11::types::$code => (stringify!($code), ResponseStatus::$status),)*
+                rv if rv >= pkcs11::types::CKR_VENDOR_DEFINED => {
+                    ("CKR_VENDOR_DEFINED", ResponseStatus::PsaErrorGenericError)
+                }
+                _ => ("unknown CKR value", ResponseStatus::PsaErrorGenericError),
+            }
+        };
+    }
+
+    ckr_table! {
+        CKR_OK => Success,
+        CKR_CANCEL => PsaErrorGenericError,
+        CKR_HOST_MEMORY => PsaErrorInsufficientMemory,
+        CKR_SLOT_ID_INVALID => PsaErrorCommunicationFailure,
+        CKR_GENERAL_ERROR => PsaErrorHardwareFailure,
+        CKR_FUNCTION_FAILED => PsaErrorHardwareFailure,
+        CKR_ARGUMENTS_BAD => PsaErrorInvalidArgument,
+        CKR_NO_EVENT => PsaErrorBadState,
+        CKR_NEED_TO_CREATE_THREADS => PsaErrorGenericError,
+        CKR_CANT_LOCK => PsaErrorGenericError,
+        CKR_ATTRIBUTE_READ_ONLY => PsaErrorNotPermitted,
+        CKR_ATTRIBUTE_SENSITIVE => PsaErrorNotPermitted,
+        CKR_ATTRIBUTE_TYPE_INVALID => PsaErrorNotSupported,
+        CKR_ATTRIBUTE_VALUE_INVALID => PsaErrorInvalidArgument,
+        CKR_ACTION_PROHIBITED => PsaErrorNotPermitted,
+        CKR_DATA_INVALID => PsaErrorInvalidArgument,
+        CKR_DATA_LEN_RANGE => PsaErrorInvalidArgument,
+        CKR_DEVICE_ERROR => PsaErrorHardwareFailure,
+        CKR_DEVICE_MEMORY => PsaErrorInsufficientStorage,
+        CKR_DEVICE_REMOVED => PsaErrorCommunicationFailure,
+        CKR_ENCRYPTED_DATA_INVALID => PsaErrorInvalidPadding,
+        CKR_ENCRYPTED_DATA_LEN_RANGE => PsaErrorInvalidArgument,
+        CKR_FUNCTION_CANCELED => PsaErrorGenericError,
+        CKR_FUNCTION_NOT_PARALLEL => PsaErrorNotSupported,
+        CKR_FUNCTION_NOT_SUPPORTED => PsaErrorNotSupported,
+        CKR_KEY_HANDLE_INVALID => PsaErrorDoesNotExist,
+        CKR_KEY_SIZE_RANGE => PsaErrorNotSupported,
+        CKR_KEY_TYPE_INCONSISTENT => PsaErrorInvalidArgument,
+        CKR_KEY_NOT_NEEDED => PsaErrorInvalidArgument,
+        CKR_KEY_CHANGED => PsaErrorBadState,
+        CKR_KEY_NEEDED => PsaErrorBadState,
+        CKR_KEY_INDIGESTIBLE => PsaErrorNotSupported,
+        CKR_KEY_FUNCTION_NOT_PERMITTED => PsaErrorNotPermitted,
+        CKR_KEY_NOT_WRAPPABLE => PsaErrorNotPermitted,
+        CKR_KEY_UNEXTRACTABLE => PsaErrorNotPermitted,
+        CKR_MECHANISM_INVALID => PsaErrorNotSupported,
+        CKR_MECHANISM_PARAM_INVALID => PsaErrorInvalidArgument,
+        CKR_OBJECT_HANDLE_INVALID => PsaErrorDoesNotExist,
+        CKR_OPERATION_ACTIVE => PsaErrorBadState,
+        CKR_OPERATION_NOT_INITIALIZED => PsaErrorBadState,
+        CKR_PIN_INCORRECT => AuthenticationError,
+        CKR_PIN_INVALID => AuthenticationError,
+        CKR_PIN_LEN_RANGE => AuthenticationError,
+        CKR_PIN_EXPIRED => AuthenticationError,
+        CKR_PIN_LOCKED => AuthenticationError,
+        CKR_SESSION_CLOSED => PsaErrorCommunicationFailure,
+        CKR_SESSION_COUNT => PsaErrorInsufficientMemory,
+        CKR_SESSION_HANDLE_INVALID => PsaErrorCommunicationFailure,
+        CKR_SESSION_PARALLEL_NOT_SUPPORTED => PsaErrorNotSupported,
+        CKR_SESSION_READ_ONLY => PsaErrorNotPermitted,
+        CKR_SESSION_EXISTS => PsaErrorBadState,
+        CKR_SESSION_READ_ONLY_EXISTS => PsaErrorBadState,
+        CKR_SESSION_READ_WRITE_SO_EXISTS => PsaErrorBadState,
+        CKR_SIGNATURE_INVALID => PsaErrorInvalidSignature,
+        CKR_SIGNATURE_LEN_RANGE => PsaErrorInvalidSignature,
+        CKR_TEMPLATE_INCOMPLETE => PsaErrorInvalidArgument,
+        CKR_TEMPLATE_INCONSISTENT => PsaErrorInvalidArgument,
+        CKR_TOKEN_NOT_PRESENT => PsaErrorCommunicationFailure,
+        CKR_TOKEN_NOT_RECOGNIZED => PsaErrorCommunicationFailure,
+        CKR_TOKEN_WRITE_PROTECTED => PsaErrorNotPermitted,
+        CKR_UNWRAPPING_KEY_HANDLE_INVALID => PsaErrorDoesNotExist,
+        CKR_UNWRAPPING_KEY_SIZE_RANGE => PsaErrorNotSupported,
+        CKR_UNWRAPPING_KEY_TYPE_INCONSISTENT => PsaErrorInvalidArgument,
+        CKR_USER_ALREADY_LOGGED_IN => PsaErrorBadState,
+        CKR_USER_NOT_LOGGED_IN => AuthenticationError,
+        CKR_USER_PIN_NOT_INITIALIZED => AuthenticationError,
+        CKR_USER_TYPE_INVALID => PsaErrorInvalidArgument,
+        CKR_USER_ANOTHER_ALREADY_LOGGED_IN => PsaErrorBadState,
+        CKR_USER_TOO_MANY_TYPES => PsaErrorBadState,
+        CKR_WRAPPED_KEY_INVALID => PsaErrorInvalidArgument,
+        CKR_WRAPPED_KEY_LEN_RANGE => PsaErrorInvalidArgument,
+        CKR_WRAPPING_KEY_HANDLE_INVALID => PsaErrorDoesNotExist,
+        CKR_WRAPPING_KEY_SIZE_RANGE => PsaErrorNotSupported,
+        CKR_WRAPPING_KEY_TYPE_INCONSISTENT => PsaErrorInvalidArgument,
+        CKR_RANDOM_SEED_NOT_SUPPORTED => PsaErrorNotSupported,
+        CKR_RANDOM_NO_RNG => PsaErrorInsufficientEntropy,
+        CKR_DOMAIN_PARAMS_INVALID => PsaErrorInvalidArgument,
+        CKR_CURVE_NOT_SUPPORTED => PsaErrorNotSupported,
+        CKR_BUFFER_TOO_SMALL => PsaErrorBufferTooSmall,
+        CKR_SAVED_STATE_INVALID => PsaErrorBadState,
+        CKR_INFORMATION_SENSITIVE => PsaErrorNotPermitted,
+        CKR_STATE_UNSAVEABLE => PsaErrorNotSupported,
+        CKR_CRYPTOKI_NOT_INITIALIZED => PsaErrorCommunicationFailure,
+        CKR_CRYPTOKI_ALREADY_INITIALIZED => PsaErrorBadState,
+        CKR_MUTEX_BAD => PsaErrorGenericError,
+        CKR_MUTEX_NOT_LOCKED => PsaErrorGenericError,
+        CKR_NEW_PIN_MODE => PsaErrorNotPermitted,
+        CKR_NEXT_OTP => PsaErrorNotPermitted,
+        CKR_EXCEEDED_MAX_ITERATIONS => PsaErrorInvalidArgument,
+        CKR_FIPS_SELF_TEST_FAILED => PsaErrorHardwareFailure,
+        CKR_LIBRARY_LOAD_FAILED => PsaErrorHardwareFailure,
+        CKR_PIN_TOO_WEAK => PsaErrorNotPermitted,
+        CKR_PUBLIC_KEY_INVALID => PsaErrorInvalidArgument,
+        CKR_FUNCTION_REJECTED => PsaErrorNotPermitted,
+    }
+}
+
+/// Converts an error of the PKCS 11 library into the response status to send back, logging the
+/// operation which failed and the original return value.
+pub fn to_response_status(operation: &str, error: pkcs11::errors::Error) -> ResponseStatus {
+    match error {
+        pkcs11::errors::Error::Pkcs11(rv) => {
+            let (name, status) = rv_to_response_status(rv);
+            error!(
+                "{} failed with {} ({:#x}), returning {}.",
+                operation, name, rv, status
+            );
+            status
+        }
+        // The library could not be called, for example because it is not initialised anymore.
+        error => {
+            error!("{} failed: {}.", operation, error);
+            ResponseStatus::PsaErrorCommunicationFailure
+        }
+    }
+}
+
+/// Checks a return value which the PKCS 11 library does not turn into an error, such as the one
+/// of `C_GetAttributeValue`.
+pub fn check_rv(operation: &str, rv: CK_RV) -> Result<()> {
+    if rv == pkcs11::types::CKR_OK {
+        Ok(())
+    } else {
+        Err(to_response_status(
+            operation,
+            pkcs11::errors::Error::Pkcs11(rv),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ec_params_round_trip() {
+        for curve in &[
+            EccCurve::Secp256r1,
+            EccCurve::Secp384r1,
+            EccCurve::Secp521r1,
+        ] {
+            let params = ec_params(Some(*curve)).unwrap();
+            assert_eq!(ecc_curve_from_params(params).unwrap(), *curve);
+        }
+        assert_eq!(
+            ec_params(None).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+    }
+
+    #[test]
+    fn key_label_round_trip() {
+        let key_triple = KeyTriple::new(
+            ApplicationName::new("app/name".to_string()),
+            ProviderID::Pkcs11Provider,
+            "key name ?".to_string(),
+        );
+        let label = key_label(&key_triple, Some(KeyPurpose::Encrypt));
+        assert!(label.starts_with("parsec/"));
+        assert_eq!(
+            parse_key_label(label.as_bytes()).unwrap(),
+            (key_triple.clone(), Some(KeyPurpose::Encrypt))
+        );
+        // Adopted keys have no purpose.
+        let adopted_label = key_label(&key_triple, None);
+        assert_eq!(
+            parse_key_label(adopted_label.as_bytes()).unwrap(),
+            (key_triple, None)
+        );
+
+        assert!(parse_key_label(b"my key").is_none());
+        assert!(parse_key_label(b"parsec/YXBw").is_none());
+        assert!(parse_key_label(format!("{}/sign", label).as_bytes()).is_none());
+        assert!(parse_key_label(format!("{}/unknown", adopted_label).as_bytes()).is_none());
+    }
+
+    #[test]
+    fn ec_point_round_trip() {
+        // Uncompressed points on P-256 (short form length) and P-521 (long form length).
+        for (size, ec_params) in &[(65, &SECP256R1_OID[..]), (133, &SECP521R1_OID[..])] {
+            let mut point = vec![0xab; *size];
+            point[0] = 0x04;
+            let der = ec_point_to_der(&point, ec_params).unwrap();
+            assert_eq!(ec_point_from_der(&der).unwrap(), point);
+        }
+    }
+
+    #[test]
+    fn invalid_ec_points() {
+        let mut point = vec![0xab; 65];
+        // Compressed point.
+        point[0] = 0x02;
+        assert!(ec_point_to_der(&point, &SECP256R1_OID).is_err());
+        point[0] = 0x04;
+        // Point on another curve.
+        assert!(ec_point_to_der(&point, &SECP384R1_OID).is_err());
+        assert!(ec_point_to_der(&[0x04; 0x10001], &SECP256R1_OID).is_err());
+        assert!(ec_point_to_der(&[], &SECP256R1_OID).is_err());
+    }
+
+    #[test]
+    fn rsa_pss_mechanisms() {
+        let alg = Algorithm::sign(SignAlgorithm::RsaPss, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &alg).unwrap();
+        let mechanisms = mechanisms_from_bytes(&mechanisms_to_bytes(&mechanisms));
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_RSA, &mechanisms, Some(KeyPurpose::Sign))
+                .unwrap(),
+            alg
+        );
+
+        let (mechanism, params) =
+            sign_mechanism(pkcs11::types::CKK_RSA, &mechanisms, Some(KeyPurpose::Sign)).unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_RSA_PKCS_PSS);
+        let params = params.unwrap();
+        assert_eq!(params.hashAlg, pkcs11::types::CKM_SHA256);
+        assert_eq!(params.mgf, pkcs11::types::CKG_MGF1_SHA256);
+        assert_eq!(params.sLen, 32);
+    }
+
+    #[test]
+    fn rsa_pkcs1v15_mechanisms() {
+        // Keys created before allowed mechanisms were set have none.
+        let (mechanism, params) = sign_mechanism(pkcs11::types::CKK_RSA, &[], None).unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_RSA_PKCS);
+        assert!(params.is_none());
+        assert_eq!(
+            allowed_mechanisms(
+                pkcs11::types::CKK_RSA,
+                &Algorithm::sign(SignAlgorithm::RsaPss, None)
+            )
+            .unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+    }
+
+    #[test]
+    fn rsa_oaep_mechanisms() {
+        let alg = Algorithm::asymmetric_encryption(
+            AsymmetricEncryptionAlgorithm::RsaOaep,
+            Some(HashAlgorithm::Sha1),
+        );
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &alg).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(
+                pkcs11::types::CKK_RSA,
+                &mechanisms,
+                Some(KeyPurpose::Encrypt)
+            )
+            .unwrap(),
+            alg
+        );
+
+        let mut label = b"label".to_vec();
+        let (mechanism, params) =
+            encrypt_mechanism(&mechanisms, Some(KeyPurpose::Encrypt), &mut label).unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_RSA_PKCS_OAEP);
+        let params = params.unwrap();
+        assert_eq!(params.hashAlg, pkcs11::types::CKM_SHA_1);
+        assert_eq!(params.mgf, pkcs11::types::CKG_MGF1_SHA1);
+        assert_eq!(params.ulSourceDataLen, 5);
+    }
+
+    #[test]
+    fn sign_keys_can_not_encrypt() {
+        let alg = Algorithm::sign(SignAlgorithm::RsaPss, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &alg).unwrap();
+        assert_eq!(
+            encrypt_mechanism(&mechanisms, Some(KeyPurpose::Sign), &mut []).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+        // Keys created before allowed mechanisms were set are signing keys.
+        assert_eq!(
+            encrypt_mechanism(&[], None, &mut []).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn rsa_pkcs1v15_sign_and_crypt_keys() {
+        let sign = Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, None);
+        let crypt =
+            Algorithm::asymmetric_encryption(AsymmetricEncryptionAlgorithm::RsaPkcs1v15Crypt, None);
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &sign).unwrap();
+        assert_eq!(
+            allowed_mechanisms(pkcs11::types::CKK_RSA, &crypt).unwrap(),
+            mechanisms
+        );
+
+        // Only the purpose tells the two keys apart.
+        let sign_purpose = KeyPurpose::of_algorithm(&sign).unwrap();
+        let crypt_purpose = KeyPurpose::of_algorithm(&crypt).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_RSA, &mechanisms, Some(sign_purpose))
+                .unwrap(),
+            sign
+        );
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_RSA, &mechanisms, Some(crypt_purpose))
+                .unwrap(),
+            crypt
+        );
+        assert!(sign_mechanism(pkcs11::types::CKK_RSA, &mechanisms, Some(sign_purpose)).is_ok());
+        assert_eq!(
+            sign_mechanism(pkcs11::types::CKK_RSA, &mechanisms, Some(crypt_purpose)).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+        let (mechanism, params) =
+            encrypt_mechanism(&mechanisms, Some(crypt_purpose), &mut []).unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_RSA_PKCS);
+        assert!(params.is_none());
+        assert_eq!(
+            encrypt_mechanism(&mechanisms, Some(sign_purpose), &mut []).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+        assert_eq!(
+            encrypt_mechanism(&mechanisms, None, &mut []).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn aes_gcm_mechanisms() {
+        let alg = Algorithm::aead(AeadAlgorithm::Gcm);
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_AES, &alg).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_AES, &mechanisms, Some(KeyPurpose::Aead))
+                .unwrap(),
+            alg
+        );
+
+        let mut nonce = vec![0; 12];
+        let params = gcm_params(&mechanisms, &mut nonce, &mut []).unwrap();
+        assert_eq!(params.ulIvBits, 96);
+        assert_eq!(params.ulTagBits, 128);
+        assert!(params.pAAD.is_null());
+
+        assert_eq!(
+            allowed_mechanisms(pkcs11::types::CKK_AES, &Algorithm::aead(AeadAlgorithm::Ccm))
+                .unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+        assert_eq!(
+            gcm_params(&[pkcs11::types::CKM_RSA_PKCS], &mut nonce, &mut []).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn mac_mechanisms() {
+        let alg = Algorithm::mac(MacAlgorithm::Hmac, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_GENERIC_SECRET, &alg).unwrap();
+        assert_eq!(mechanisms, vec![pkcs11::types::CKM_SHA256_HMAC]);
+        assert_eq!(
+            algorithm_from_mechanisms(
+                pkcs11::types::CKK_GENERIC_SECRET,
+                &mechanisms,
+                Some(KeyPurpose::Mac)
+            )
+            .unwrap(),
+            alg
+        );
+        assert_eq!(
+            mac_mechanism(&mechanisms).unwrap(),
+            pkcs11::types::CKM_SHA256_HMAC
+        );
+
+        let alg = Algorithm::mac(MacAlgorithm::Cmac, None);
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_AES, &alg).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_AES, &mechanisms, Some(KeyPurpose::Mac))
+                .unwrap(),
+            alg
+        );
+        assert_eq!(
+            mac_mechanism(&mechanisms).unwrap(),
+            pkcs11::types::CKM_AES_CMAC
+        );
+
+        assert_eq!(
+            mac_mechanism(&[pkcs11::types::CKM_AES_GCM]).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn digest_mechanisms() {
+        assert_eq!(
+            digest_mechanism(HashAlgorithm::Sha256).unwrap(),
+            pkcs11::types::CKM_SHA256
+        );
+        assert_eq!(
+            digest_mechanism(HashAlgorithm::Sha3256).unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+    }
+
+    #[test]
+    fn constant_time_comparison() {
+        assert!(constant_time_eq(&[1, 2, 3], &[1, 2, 3]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2, 4]));
+        assert!(!constant_time_eq(&[1, 2, 3], &[1, 2]));
+        assert!(constant_time_eq(&[], &[]));
+    }
+
+    #[test]
+    fn sign_message_mechanisms() {
+        let alg = Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &alg).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_RSA, &mechanisms, Some(KeyPurpose::Sign))
+                .unwrap(),
+            alg
+        );
+        let (mechanism, params) =
+            sign_message_mechanism(pkcs11::types::CKK_RSA, &mechanisms, Some(KeyPurpose::Sign))
+                .unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_SHA256_RSA_PKCS);
+        assert!(params.is_none());
+
+        let alg = Algorithm::sign(SignAlgorithm::Ecdsa, Some(HashAlgorithm::Sha384));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_EC, &alg).unwrap();
+        assert_eq!(
+            algorithm_from_mechanisms(pkcs11::types::CKK_EC, &mechanisms, Some(KeyPurpose::Sign))
+                .unwrap(),
+            alg
+        );
+        let (mechanism, _) =
+            sign_message_mechanism(pkcs11::types::CKK_EC, &mechanisms, Some(KeyPurpose::Sign))
+                .unwrap();
+        assert_eq!(mechanism, pkcs11::types::CKM_ECDSA_SHA384);
+
+        assert_eq!(
+            sign_message_mechanism(
+                pkcs11::types::CKK_RSA,
+                &[pkcs11::types::CKM_RSA_PKCS],
+                Some(KeyPurpose::Sign)
+            )
+            .unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+    }
+
+    #[test]
+    fn pkcs1v15_digest_info() {
+        let alg = Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &alg).unwrap();
+        let input = sign_input(&mechanisms, vec![0xab; 32]).unwrap();
+        assert_eq!(input.len(), 19 + 32);
+        assert_eq!(&input[..2], &[0x30, 0x31]);
+        assert_eq!(
+            sign_input(&mechanisms, vec![0xab; 20]).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+
+        // Keys without a hash algorithm sign the digest as it is.
+        let input = sign_input(&[pkcs11::types::CKM_RSA_PKCS], vec![0xab; 20]).unwrap();
+        assert_eq!(input, vec![0xab; 20]);
+    }
+
+    #[test]
+    fn ecdh_mechanisms() {
+        let alg = Algorithm::key_agreement(KeyAgreementAlgorithm::Ecdh, None, None);
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_EC, &alg).unwrap();
+        assert_eq!(mechanisms, vec![pkcs11::types::CKM_ECDH1_DERIVE]);
+        assert_eq!(
+            algorithm_from_mechanisms(
+                pkcs11::types::CKK_EC,
+                &mechanisms,
+                Some(KeyPurpose::KeyAgreement)
+            )
+            .unwrap(),
+            alg
+        );
+
+        let mut peer_key = vec![0x04; 65];
+        let params = ecdh_params(&mechanisms, &mut peer_key).unwrap();
+        assert_eq!(params.kdf, pkcs11::types::CKD_NULL);
+        assert_eq!(params.ulPublicDataLen, 65);
+        assert_eq!(
+            ecdh_params(&[pkcs11::types::CKM_ECDSA], &mut peer_key).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+
+        // The shared secret can only be derived with the token as it is.
+        let alg = Algorithm::key_agreement(
+            KeyAgreementAlgorithm::Ecdh,
+            Some(KeyDerivationAlgorithm::Hkdf),
+            Some(HashAlgorithm::Sha256),
+        );
+        assert_eq!(
+            allowed_mechanisms(pkcs11::types::CKK_EC, &alg).unwrap_err(),
+            ResponseStatus::PsaErrorNotSupported
+        );
+    }
+
+    #[test]
+    fn malformed_ec_point() {
+        assert!(ec_point_from_der(&[]).is_err());
+        assert!(ec_point_from_der(&[0x03, 0x01, 0x00]).is_err());
+        assert!(ec_point_from_der(&[0x04, 0x05, 0x04, 0x01]).is_err());
+        assert!(ec_point_from_der(&[0x04, 0x81]).is_err());
+    }
+
+    #[test]
+    fn padded_strings() {
+        let mut label = [b' '; 32];
+        label[..8].copy_from_slice(b"Parsec 1");
+        assert_eq!(padded_string(&label), "Parsec 1");
+        assert_eq!(padded_string(b"0123abcd\0\0\0\0"), "0123abcd");
+        assert_eq!(padded_string(&[b' '; 16]), "");
+    }
+
+    #[test]
+    fn key_sizes() {
+        assert!(check_key_size(pkcs11::types::CKK_RSA, None, 2048).is_ok());
+        assert!(check_key_size(pkcs11::types::CKK_RSA, None, 0).is_err());
+        assert!(check_key_size(pkcs11::types::CKK_AES, None, 192).is_ok());
+        assert!(check_key_size(pkcs11::types::CKK_AES, None, 512).is_err());
+        assert!(check_key_size(pkcs11::types::CKK_GENERIC_SECRET, None, 260).is_err());
+        assert!(check_key_size(pkcs11::types::CKK_EC, Some(EccCurve::Secp384r1), 0).is_ok());
+        assert!(check_key_size(pkcs11::types::CKK_EC, Some(EccCurve::Secp384r1), 384).is_ok());
+        assert!(check_key_size(pkcs11::types::CKK_EC, Some(EccCurve::Secp384r1), 256).is_err());
+    }
+
+    #[test]
+    fn sign_not_permitted() {
+        let oaep = Algorithm::asymmetric_encryption(
+            AsymmetricEncryptionAlgorithm::RsaOaep,
+            Some(HashAlgorithm::Sha256),
+        );
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &oaep).unwrap();
+        assert_eq!(
+            sign_mechanism(pkcs11::types::CKK_RSA, &mechanisms, None).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+        assert_eq!(
+            sign_message_mechanism(pkcs11::types::CKK_RSA, &mechanisms, None).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+
+        let ecdh = Algorithm::key_agreement(KeyAgreementAlgorithm::Ecdh, None, None);
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_EC, &ecdh).unwrap();
+        assert_eq!(
+            sign_mechanism(pkcs11::types::CKK_EC, &mechanisms, None).unwrap_err(),
+            ResponseStatus::PsaErrorNotPermitted
+        );
+    }
+
+    #[test]
+    fn sign_input_length() {
+        let ecdsa = Algorithm::sign(SignAlgorithm::Ecdsa, Some(HashAlgorithm::Sha384));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_EC, &ecdsa).unwrap();
+        assert_eq!(sign_input(&mechanisms, vec![0; 48]).unwrap(), vec![0; 48]);
+        assert!(sign_input(&mechanisms, vec![0; 32]).is_err());
+
+        let pss = Algorithm::sign(SignAlgorithm::RsaPss, Some(HashAlgorithm::Sha256));
+        let mechanisms = allowed_mechanisms(pkcs11::types::CKK_RSA, &pss).unwrap();
+        assert!(sign_input(&mechanisms, vec![0; 32]).is_ok());
+        assert!(sign_input(&mechanisms, vec![0; 20]).is_err());
+    }
+
+    // Builds a DER element of at most 255 bytes of content.
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut der = vec![tag];
+        if content.len() >= 0x80 {
+            der.push(0x81);
+        }
+        der.push(content.len() as u8);
+        der.extend_from_slice(content);
+        der
+    }
+
+    fn ec_private_key(parameters: bool, public_key: bool) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let private_value = vec![0x11; 32];
+        let mut point = vec![0x22; 65];
+        point[0] = 0x04;
+
+        let mut content = der(DER_INTEGER_TAG, &[1]);
+        content.extend(der(DER_OCTET_STRING_TAG, &private_value));
+        if parameters {
+            content.extend(der(DER_EC_PARAMETERS_TAG, &SECP256R1_OID));
+        }
+        if public_key {
+            let mut bits = vec![0];
+            bits.extend_from_slice(&point);
+            content.extend(der(DER_EC_PUBLIC_KEY_TAG, &der(DER_BIT_STRING_TAG, &bits)));
+        }
+        (der(DER_SEQUENCE_TAG, &content), private_value, point)
+    }
+
+    #[test]
+    fn ec_private_key_import() {
+        let (key, private_value, point) = ec_private_key(true, true);
+        assert_eq!(
+            ec_private_key_from_der(pkcs8_private_key(&key).unwrap(), &SECP256R1_OID).unwrap(),
+            (private_value, point)
+        );
+        assert_eq!(
+            ec_private_key_from_der(&key, &SECP384R1_OID).unwrap_err(),
+            ResponseStatus::PsaErrorInvalidArgument
+        );
+
+        // The same key without its parameters, wrapped in a PKCS #8 PrivateKeyInfo.
+        let (key, private_value, point) = ec_private_key(false, true);
+        let ec_public_key_oid = [0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+        let mut algorithm = ec_public_key_oid.to_vec();
+        algorithm.extend_from_slice(&SECP256R1_OID);
+        let mut content = der(DER_INTEGER_TAG, &[0]);
+        content.extend(der(DER_SEQUENCE_TAG, &algorithm));
+        content.extend(der(DER_OCTET_STRING_TAG, &key));
+        let pkcs8 = der(DER_SEQUENCE_TAG, &content);
+        assert_eq!(pkcs8_private_key(&pkcs8).unwrap(), &key[..]);
+        assert_eq!(
+            ec_private_key_from_der(pkcs8_private_key(&pkcs8).unwrap(), &SECP256R1_OID).unwrap(),
+            (private_value, point)
+        );
+
+        let (key, _, _) = ec_private_key(true, false);
+        assert!(ec_private_key_from_der(&key, &SECP256R1_OID).is_err());
+    }
+
+    #[test]
+    fn malformed_private_key() {
+        let (key, _, _) = ec_private_key(true, true);
+        assert!(pkcs8_private_key(&[]).is_err());
+        assert!(pkcs8_private_key(&key[..key.len() - 1]).is_err());
+        assert!(pkcs8_private_key(&der(DER_OCTET_STRING_TAG, &[0])).is_err());
+        assert!(ec_private_key_from_der(&key[..key.len() - 1], &SECP256R1_OID).is_err());
+
+        let mut trailing = key.clone();
+        trailing.push(0);
+        assert!(pkcs8_private_key(&trailing).is_err());
+        assert!(ec_private_key_from_der(&trailing, &SECP256R1_OID).is_err());
+    }
+
+    #[test]
+    fn return_values() {
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_PIN_INCORRECT),
+            ("CKR_PIN_INCORRECT", ResponseStatus::AuthenticationError)
+        );
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_KEY_FUNCTION_NOT_PERMITTED),
+            (
+                "CKR_KEY_FUNCTION_NOT_PERMITTED",
+                ResponseStatus::PsaErrorNotPermitted
+            )
+        );
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_SIGNATURE_LEN_RANGE),
+            (
+                "CKR_SIGNATURE_LEN_RANGE",
+                ResponseStatus::PsaErrorInvalidSignature
+            )
+        );
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_DEVICE_REMOVED),
+            (
+                "CKR_DEVICE_REMOVED",
+                ResponseStatus::PsaErrorCommunicationFailure
+            )
+        );
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_TOKEN_NOT_PRESENT),
+            (
+                "CKR_TOKEN_NOT_PRESENT",
+                ResponseStatus::PsaErrorCommunicationFailure
+            )
+        );
+        assert_eq!(
+            rv_to_response_status(pkcs11::types::CKR_VENDOR_DEFINED + 1),
+            ("CKR_VENDOR_DEFINED", ResponseStatus::PsaErrorGenericError)
+        );
+        assert!(check_rv("C_GetAttributeValue", pkcs11::types::CKR_OK).is_ok());
+        assert_eq!(
+            check_rv(
+                "C_GetAttributeValue",
+                pkcs11::types::CKR_ATTRIBUTE_SENSITIVE
+            ),
+            Err(ResponseStatus::PsaErrorNotPermitted)
+        );
+    }
+}
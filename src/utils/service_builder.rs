@@ -13,8 +13,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 use crate::authenticators::simple_authenticator::SimpleAuthenticator;
+use crate::authenticators::unix_peer_credentials_authenticator::UnixPeerCredentialsAuthenticator;
+use crate::authenticators::{
+    ApplicationName, Authenticate, AuthenticatorConfig, AuthenticatorType, NoAuthPolicy,
+};
+use crate::authorizers::policy_file_authorizer::PolicyFileAuthorizer;
+use crate::authorizers::{AuthorizationConfig, Authorize};
 use crate::back::{
-    backend_handler::{BackEndHandler, BackEndHandlerBuilder},
+    backend_handler::{BackEndHandler, BackEndHandlerBuilder, DEFAULT_MAX_RANDOM_SIZE},
     dispatcher::DispatcherBuilder,
 };
 use crate::front::listener::{ListenerConfig, ListenerType};
@@ -25,10 +31,12 @@ use crate::front::{
 use crate::key_id_managers::on_disk_manager::{OnDiskKeyIDManagerBuilder, DEFAULT_MAPPINGS_PATH};
 use crate::key_id_managers::{KeyIdManagerConfig, KeyIdManagerType, ManageKeyIDs};
 use crate::providers::{
-    core_provider::CoreProviderBuilder, mbed_provider::MbedProviderBuilder,
-    pkcs11_provider::Pkcs11ProviderBuilder, Provide, ProviderConfig, ProviderType,
+    core_provider::CoreProviderBuilder,
+    mbed_provider::MbedProviderBuilder,
+    pkcs11_provider::{PinSource, Pkcs11ProviderBuilder, TokenObject},
+    AdoptKeyConfig, Provide, ProviderConfig, ProviderType,
 };
-use log::{info, LevelFilter};
+use log::{info, warn, LevelFilter};
 use parsec_interface::operations_protobuf::ProtobufConverter;
 use parsec_interface::requests::AuthType;
 use parsec_interface::requests::{BodyType, ProviderID};
@@ -44,7 +52,9 @@ const VERSION_MINOR: u8 = 0;
 const VERSION_MAJOR: u8 = 1;
 
 type KeyIdManager = Arc<RwLock<dyn ManageKeyIDs + Send + Sync>>;
-type Provider = Box<dyn Provide + Send + Sync>;
+type Provider = Arc<dyn Provide + Send + Sync>;
+type Authenticator = Box<dyn Authenticate + Send + Sync>;
+type Authorizer = Arc<dyn Authorize + Send + Sync>;
 
 #[derive(Deserialize, Debug)]
 pub struct CoreSettings {
@@ -52,12 +62,17 @@ pub struct CoreSettings {
     pub idle_listener_sleep_duration: Option<u64>,
     pub log_level: Option<LevelFilter>,
     pub log_timestamp: Option<bool>,
+    pub no_auth_policy: Option<NoAuthPolicy>,
+    pub max_random_size: Option<usize>,
+    pub random_provider: Option<ProviderType>,
 }
 
 #[derive(Deserialize, Debug)]
 pub struct ServiceConfig {
     pub core_settings: CoreSettings,
     pub listener: ListenerConfig,
+    pub authenticator: Option<Vec<AuthenticatorConfig>>,
+    pub authorization: Option<AuthorizationConfig>,
     pub key_manager: Vec<KeyIdManagerConfig>,
     pub provider: Vec<ProviderConfig>,
 }
@@ -70,18 +85,26 @@ impl ServiceBuilder {
 
         let providers = build_providers(&config.provider, key_id_managers);
 
-        let backend_handlers = build_backend_handlers(providers);
+        let authorizer = config.authorization.as_ref().map(build_authorizer);
+
+        let backend_handlers = build_backend_handlers(providers, authorizer, &config.core_settings);
 
         let dispatcher = DispatcherBuilder::new()
             .with_backends(backend_handlers)
             .build();
 
-        let simple_authenticator = Box::from(SimpleAuthenticator {});
-
-        FrontEndHandlerBuilder::new()
+        let mut front_end_handler_builder = FrontEndHandlerBuilder::new()
             .with_dispatcher(dispatcher)
-            .with_authenticator(AuthType::Simple, simple_authenticator)
-            .build()
+            .with_no_auth_policy(config.core_settings.no_auth_policy.unwrap_or_default());
+
+        for (auth_type, authenticator) in
+            build_authenticators(config.authenticator.as_ref().map(Vec::as_slice))
+        {
+            front_end_handler_builder =
+                front_end_handler_builder.with_authenticator(auth_type, authenticator);
+        }
+
+        front_end_handler_builder.build()
     }
 
     pub fn start_listener(config: &ListenerConfig) -> Box<dyn Listen> {
@@ -105,33 +128,56 @@ impl ServiceBuilder {
 
 fn build_backend_handlers(
     mut providers: HashMap<ProviderID, Provider>,
+    authorizer: Option<Authorizer>,
+    core_settings: &CoreSettings,
 ) -> HashMap<ProviderID, BackEndHandler> {
     let mut map = HashMap::new();
+    let max_random_size = core_settings
+        .max_random_size
+        .unwrap_or(DEFAULT_MAX_RANDOM_SIZE);
 
     let mut core_provider_builder =
         CoreProviderBuilder::new().with_version(VERSION_MINOR, VERSION_MAJOR);
 
+    if let Some(random_provider) = &core_settings.random_provider {
+        let provider = providers
+            .get(&random_provider.to_provider_id())
+            .unwrap_or_else(|| {
+                panic!(
+                    "The random provider {:?} is not configured.",
+                    random_provider
+                )
+            });
+        core_provider_builder = core_provider_builder.with_random_provider(provider.clone());
+    }
+
     for (provider_id, provider) in providers.drain() {
-        core_provider_builder = core_provider_builder.with_provider_info(provider.describe());
+        core_provider_builder = core_provider_builder
+            .with_provider_info(provider.describe())
+            .with_key_provider(provider.clone());
 
-        let backend_handler = BackEndHandlerBuilder::new()
+        let mut backend_handler_builder = BackEndHandlerBuilder::new()
             .with_provider(provider)
             .with_converter(Box::from(ProtobufConverter {}))
             .with_provider_id(provider_id)
             .with_content_type(BodyType::Protobuf)
             .with_accept_type(BodyType::Protobuf)
             .with_version(VERSION_MINOR, VERSION_MAJOR)
-            .build();
-        map.insert(provider_id, backend_handler);
+            .with_max_random_size(max_random_size);
+        if let Some(authorizer) = &authorizer {
+            backend_handler_builder = backend_handler_builder.with_authorizer(authorizer.clone());
+        }
+        map.insert(provider_id, backend_handler_builder.build());
     }
 
     let core_provider_backend = BackEndHandlerBuilder::new()
-        .with_provider(Box::from(core_provider_builder.build()))
+        .with_provider(Arc::new(core_provider_builder.build()))
         .with_converter(Box::from(ProtobufConverter {}))
         .with_provider_id(ProviderID::CoreProvider)
         .with_content_type(BodyType::Protobuf)
         .with_accept_type(BodyType::Protobuf)
         .with_version(VERSION_MINOR, VERSION_MAJOR)
+        .with_max_random_size(max_random_size)
         .build();
 
     map.insert(ProviderID::CoreProvider, core_provider_backend);
@@ -166,7 +212,7 @@ fn get_provider(config: &ProviderConfig, key_id_manager: KeyIdManager) -> Provid
     match config.provider_type {
         ProviderType::MbedProvider => {
             info!("Creating a Mbed Crypto Provider.");
-            Box::from(
+            Arc::new(
                 MbedProviderBuilder::new()
                     .with_key_id_store(key_id_manager)
                     .build(),
@@ -174,22 +220,133 @@ fn get_provider(config: &ProviderConfig, key_id_manager: KeyIdManager) -> Provid
         }
         ProviderType::Pkcs11Provider => {
             info!("Creating a PKCS 11 Provider.");
-            Box::from(
+            let mut pkcs11_provider_builder =
                 Pkcs11ProviderBuilder::new()
-                .with_key_id_store(key_id_manager)
-                .with_pkcs11_library_path(config.library_path.clone().expect(
+                    .with_key_id_store(key_id_manager)
+                    .with_pkcs11_library_path(config.library_path.clone().expect(
                         "The PKCS 11 provider needs a library path in the configuration file.",
-                        ))
-                .with_slot_number(config.slot_number.expect(
-                        "The slot number of the device is needed to communicate with PKCS 11 library."
-                        ))
-                .with_user_pin(config.user_pin.clone())
-                .build()
-                )
+                    ))
+                    .with_slot_number(config.slot_number)
+                    .with_token_label(config.token_label.clone())
+                    .with_token_serial_number(config.token_serial_number.clone())
+                    .with_token_manufacturer(config.token_manufacturer.clone())
+                    .with_token_model(config.token_model.clone())
+                    .with_user_pin(get_pin_source(config))
+                    .with_min_sessions(config.min_sessions)
+                    .with_max_sessions(config.max_sessions)
+                    .with_rebuild_key_id_mappings(config.rebuild_key_id_mappings);
+            for adopt_key in config.adopt_key.iter().flatten() {
+                pkcs11_provider_builder = pkcs11_provider_builder.with_adopted_key(
+                    ApplicationName::new(adopt_key.app_name.clone()),
+                    adopt_key.key_name.clone(),
+                    get_token_object(adopt_key),
+                );
+            }
+            Arc::new(pkcs11_provider_builder.build())
+        }
+    }
+}
+
+fn get_token_object(config: &AdoptKeyConfig) -> TokenObject {
+    match (&config.object_label, &config.object_id) {
+        (Some(label), None) => TokenObject::Label(label.clone()),
+        (None, Some(id)) => TokenObject::Id(id.clone()),
+        _ => panic!(
+            "Exactly one of object_label and object_id must be set to adopt key \"{}\" of application \"{}\".",
+            config.key_name, config.app_name
+        ),
+    }
+}
+
+fn get_pin_source(config: &ProviderConfig) -> Option<PinSource> {
+    // The configuration file is usually readable by all users.
+    if config.user_pin.is_some() {
+        panic!("The user pin can not be written in the configuration file, set one of user_pin_file, user_pin_env_var, user_pin_credential or user_pin_command instead of user_pin.");
+    }
+    let mut pin_sources = Vec::new();
+    if let Some(path) = &config.user_pin_file {
+        pin_sources.push(PinSource::File(PathBuf::from(path)));
+    }
+    if let Some(name) = &config.user_pin_env_var {
+        pin_sources.push(PinSource::EnvironmentVariable(name.clone()));
+    }
+    if let Some(name) = &config.user_pin_credential {
+        pin_sources.push(PinSource::SystemdCredential(name.clone()));
+    }
+    if let Some(command) = &config.user_pin_command {
+        pin_sources.push(PinSource::Command(command.clone()));
+    }
+    if pin_sources.len() > 1 {
+        panic!("Only one of user_pin_file, user_pin_env_var, user_pin_credential and user_pin_command can be set.");
+    }
+
+    pin_sources.pop()
+}
+
+fn build_authenticators(configs: Option<&[AuthenticatorConfig]>) -> Vec<(AuthType, Authenticator)> {
+    let configs = match configs {
+        Some(configs) => configs,
+        None => {
+            // Configuration files written before authenticators could be selected do not have
+            // the section, the service used to only have the Simple authenticator.
+            warn!("No authenticator is configured, enabling the Simple authenticator.");
+            let config = AuthenticatorConfig {
+                authenticator_type: AuthenticatorType::Simple,
+                allowed_uids: None,
+            };
+            return vec![(AuthType::Simple, get_authenticator(&config))];
+        }
+    };
+    if configs.is_empty() {
+        panic!("At least one authenticator needs to be configured.");
+    }
+
+    let mut authenticators: Vec<(AuthType, Authenticator)> = Vec::new();
+    for config in configs {
+        let auth_type = config.authenticator_type.to_auth_type();
+        if authenticators
+            .iter()
+            .any(|(existing_type, _)| *existing_type == auth_type)
+        {
+            panic!(
+                "Authenticator {:?} is configured more than once.",
+                config.authenticator_type
+            );
+        }
+        authenticators.push((auth_type, get_authenticator(config)));
+    }
+
+    authenticators
+}
+
+fn get_authenticator(config: &AuthenticatorConfig) -> Authenticator {
+    match config.authenticator_type {
+        AuthenticatorType::Simple => {
+            warn!("Creating a Simple Authenticator. It offers no security and should not be used in production.");
+            Box::from(SimpleAuthenticator {})
+        }
+        AuthenticatorType::UnixPeerCredentials => {
+            info!("Creating a Unix Peer Credentials Authenticator.");
+            Box::from(UnixPeerCredentialsAuthenticator::new(
+                config.allowed_uids.clone(),
+            ))
         }
     }
 }
 
+fn build_authorizer(config: &AuthorizationConfig) -> Authorizer {
+    info!(
+        "Creating an authorizer with the policy file \'{}\'.",
+        config.policy_file_path
+    );
+    let policy = ::std::fs::read_to_string(&config.policy_file_path)
+        .expect("Failed to read the authorization policy file");
+    let authorizer = PolicyFileAuthorizer::from_policy(&policy)
+        .unwrap_or_else(|err| panic!("Failed to parse the authorization policy file ({})", err));
+
+    Arc::new(authorizer)
+}
+
 fn build_key_id_managers(configs: &[KeyIdManagerConfig]) -> HashMap<String, KeyIdManager> {
     let mut map = HashMap::new();
     for config in configs {
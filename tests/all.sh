@@ -18,7 +18,7 @@
 # ------------------------------------------------------------------------------
 
 # This script executes static checks, unit and integration tests for the PARSEC
-# service.
+# service. The PKCS 11 provider tests need SoftHSM (softhsm2-util) to be installed.
 #
 # Usage: ./tests/all.sh
 
@@ -49,6 +49,24 @@ cargo test --test normal || exit 1
 
 kill $SERVER_PID
 
+#############################
+# PKCS 11 Integration tests #
+#############################
+# The service runs from target/softhsm, with the configuration using the token.
+./tests/softhsm/setup.sh || exit 1
+export SOFTHSM2_CONF=$(pwd)/target/softhsm/softhsm2.conf
+cp tests/softhsm/config.toml target/softhsm/config.toml || exit 1
+(cd target/softhsm && PARSEC_USER_PIN=123456 RUST_BACKTRACE=1 RUST_LOG=info exec ../debug/parsec) &
+SERVER_PID=$!
+
+cargo test --test pkcs11 || exit 1
+# The session pool tests use the token directly.
+cargo test --lib pkcs11_provider || exit 1
+# Removes and recreates the token, after the other tests.
+cargo test --test pkcs11-recovery || exit 1
+
+kill $SERVER_PID
+
 #################################
 # Persistence Integration tests #
 #################################
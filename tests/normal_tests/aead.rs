@@ -0,0 +1,113 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{
+        NativeOperation, NativeResult, OpAeadDecrypt, OpAeadEncrypt, OpCreateKey,
+    };
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const NONCE: [u8; 12] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    ];
+    const ADDITIONAL_DATA: [u8; 8] = [0x41, 0x44, 0x44, 0x49, 0x54, 0x49, 0x4f, 0x4e];
+    const PLAINTEXT: [u8; 16] = [
+        0x50, 0x61, 0x72, 0x73, 0x65, 0x63, 0x20, 0x70, 0x6c, 0x61, 0x69, 0x6e, 0x74, 0x65, 0x78,
+        0x74,
+    ];
+
+    fn create_aes_gcm_key(client: &mut TestClient, key_name: String) -> Result<()> {
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: KeyAttributes {
+                    key_type: KeyType::Aes,
+                    ecc_curve: None,
+                    algorithm: Algorithm::aead(AeadAlgorithm::Gcm),
+                    key_size: 128,
+                    permit_sign: false,
+                    permit_verify: false,
+                    permit_export: false,
+                    permit_derive: false,
+                    permit_encrypt: true,
+                    permit_decrypt: true,
+                },
+            }))
+            .map(|_| ())
+    }
+
+    fn encrypt(client: &mut TestClient, key_name: String, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::AeadEncrypt(OpAeadEncrypt {
+            key_name,
+            nonce: NONCE.to_vec(),
+            additional_data: ADDITIONAL_DATA.to_vec(),
+            plaintext,
+        }))? {
+            NativeResult::AeadEncrypt(result) => Ok(result.ciphertext),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    fn decrypt(client: &mut TestClient, key_name: String, ciphertext: Vec<u8>) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::AeadDecrypt(OpAeadDecrypt {
+            key_name,
+            nonce: NONCE.to_vec(),
+            additional_data: ADDITIONAL_DATA.to_vec(),
+            ciphertext,
+        }))? {
+            NativeResult::AeadDecrypt(result) => Ok(result.plaintext),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    #[test]
+    fn aead_encrypt_no_key() {
+        let key_name = String::from("aead_encrypt_no_key");
+        let mut client = TestClient::new();
+        let status =
+            encrypt(&mut client, key_name, PLAINTEXT.to_vec()).expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn aead_encrypt_and_decrypt() -> Result<()> {
+        let key_name = String::from("aead_encrypt_and_decrypt");
+        let mut client = TestClient::new();
+
+        create_aes_gcm_key(&mut client, key_name.clone())?;
+        let ciphertext = encrypt(&mut client, key_name.clone(), PLAINTEXT.to_vec())?;
+        // The ciphertext is followed by the authentication tag.
+        assert_eq!(ciphertext.len(), PLAINTEXT.len() + 16);
+        let plaintext = decrypt(&mut client, key_name.clone(), ciphertext)?;
+        assert_eq!(plaintext, PLAINTEXT.to_vec());
+        client.destroy_key(key_name)
+    }
+
+    #[test]
+    fn aead_decrypt_tampered_ciphertext() -> Result<()> {
+        let key_name = String::from("aead_decrypt_tampered_ciphertext");
+        let mut client = TestClient::new();
+
+        create_aes_gcm_key(&mut client, key_name.clone())?;
+        let mut ciphertext = encrypt(&mut client, key_name.clone(), PLAINTEXT.to_vec())?;
+        ciphertext[0] ^= 0xff;
+        let status = decrypt(&mut client, key_name.clone(), ciphertext)
+            .expect_err("Decryption should fail.");
+        assert_eq!(status, ResponseStatus::PsaErrorInvalidSignature);
+        client.destroy_key(key_name)
+    }
+}
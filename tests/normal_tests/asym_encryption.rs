@@ -0,0 +1,104 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{
+        NativeOperation, NativeResult, OpAsymmetricDecrypt, OpAsymmetricEncrypt, OpCreateKey,
+    };
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const PLAINTEXT: [u8; 16] = [
+        0x50, 0x61, 0x72, 0x73, 0x65, 0x63, 0x20, 0x70, 0x6c, 0x61, 0x69, 0x6e, 0x74, 0x65, 0x78,
+        0x74,
+    ];
+
+    fn create_rsa_encryption_key(client: &mut TestClient, key_name: String) -> Result<()> {
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: KeyAttributes {
+                    key_type: KeyType::RsaKeypair,
+                    ecc_curve: None,
+                    algorithm: Algorithm::asymmetric_encryption(
+                        AsymmetricEncryptionAlgorithm::RsaOaep,
+                        Some(HashAlgorithm::Sha256),
+                    ),
+                    key_size: 1024,
+                    permit_sign: false,
+                    permit_verify: false,
+                    permit_export: false,
+                    permit_derive: false,
+                    permit_encrypt: true,
+                    permit_decrypt: true,
+                },
+            }))
+            .map(|_| ())
+    }
+
+    fn encrypt(client: &mut TestClient, key_name: String, plaintext: Vec<u8>) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::AsymmetricEncrypt(OpAsymmetricEncrypt {
+            key_name,
+            plaintext,
+            salt: None,
+        }))? {
+            NativeResult::AsymmetricEncrypt(result) => Ok(result.ciphertext),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    fn decrypt(client: &mut TestClient, key_name: String, ciphertext: Vec<u8>) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::AsymmetricDecrypt(OpAsymmetricDecrypt {
+            key_name,
+            ciphertext,
+            salt: None,
+        }))? {
+            NativeResult::AsymmetricDecrypt(result) => Ok(result.plaintext),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    #[test]
+    fn asym_encrypt_no_key() {
+        let key_name = String::from("asym_encrypt_no_key");
+        let mut client = TestClient::new();
+        let status =
+            encrypt(&mut client, key_name, PLAINTEXT.to_vec()).expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn asym_decrypt_no_key() {
+        let key_name = String::from("asym_decrypt_no_key");
+        let mut client = TestClient::new();
+        let status = decrypt(&mut client, key_name, vec![0xDE, 0xAD, 0xBE, 0xEF])
+            .expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn asym_encrypt_and_decrypt() -> Result<()> {
+        let key_name = String::from("asym_encrypt_and_decrypt");
+        let mut client = TestClient::new();
+
+        create_rsa_encryption_key(&mut client, key_name.clone())?;
+        let ciphertext = encrypt(&mut client, key_name.clone(), PLAINTEXT.to_vec())?;
+        assert_ne!(ciphertext, PLAINTEXT.to_vec());
+        let plaintext = decrypt(&mut client, key_name.clone(), ciphertext)?;
+        assert_eq!(plaintext, PLAINTEXT.to_vec());
+        client.destroy_key(key_name)
+    }
+}
@@ -0,0 +1,66 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::{NativeOperation, OpGrantKey, OpRevokeKey};
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const HASH: [u8; 32] = [
+        0x69, 0x3E, 0xDB, 0x1B, 0x22, 0x79, 0x03, 0xF4, 0xC0, 0xBF, 0xD6, 0x91, 0x76, 0x37, 0x84,
+        0xA2, 0x94, 0x8E, 0x92, 0x50, 0x35, 0xC2, 0x8C, 0x5C, 0x3C, 0xCA, 0xFE, 0x18, 0xE8, 0x81,
+        0x37, 0x78,
+    ];
+
+    #[test]
+    fn grantee_uses_key() -> Result<()> {
+        let key_name = String::from("grantee_uses_key");
+        let mut client = TestClient::new();
+        let owner = String::from("first_client").into_bytes();
+        let grantee = String::from("second_client");
+
+        client.set_auth(owner.clone());
+        client.create_rsa_sign_key(key_name.clone())?;
+        let public_key = client.export_public_key(key_name.clone())?;
+        let _ = client.send_operation(NativeOperation::GrantKey(OpGrantKey {
+            key_name: key_name.clone(),
+            grantee: grantee.clone(),
+            permit_sign: true,
+            permit_verify: true,
+            permit_export: true,
+        }))?;
+
+        // The grantee refers to the key of the owner with the same name.
+        client.set_auth(grantee.clone().into_bytes());
+        let signature = client.sign(key_name.clone(), HASH.to_vec())?;
+        client.verify(key_name.clone(), HASH.to_vec(), signature)?;
+        assert_eq!(client.export_public_key(key_name.clone())?, public_key);
+
+        client.set_auth(owner.clone());
+        let _ = client.send_operation(NativeOperation::RevokeKey(OpRevokeKey {
+            key_name: key_name.clone(),
+            grantee: grantee.clone(),
+        }))?;
+
+        client.set_auth(grantee.into_bytes());
+        let status = client
+            .sign(key_name.clone(), HASH.to_vec())
+            .expect_err("Signing after the revocation should have failed");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+
+        client.set_auth(owner);
+        client.destroy_key(key_name)
+    }
+}
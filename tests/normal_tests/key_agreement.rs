@@ -0,0 +1,196 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{
+        NativeOperation, NativeResult, OpAeadDecrypt, OpAeadEncrypt, OpCreateKey,
+        OpKeyAgreementDeriveKey, OpRawKeyAgreement,
+    };
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const SALT: [u8; 8] = [0x53, 0x41, 0x4c, 0x54, 0x53, 0x41, 0x4c, 0x54];
+    const INFO: [u8; 4] = [0x49, 0x4e, 0x46, 0x4f];
+    const NONCE: [u8; 12] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    ];
+    const PLAINTEXT: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+
+    fn create_ecdh_key(
+        client: &mut TestClient,
+        key_name: String,
+        derivation: Option<KeyDerivationAlgorithm>,
+    ) -> Result<()> {
+        let hash = derivation.map(|_| HashAlgorithm::Sha256);
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: KeyAttributes {
+                    key_type: KeyType::EccKeypair,
+                    ecc_curve: Some(EccCurve::Secp256r1),
+                    algorithm: Algorithm::key_agreement(
+                        KeyAgreementAlgorithm::Ecdh,
+                        derivation,
+                        hash,
+                    ),
+                    key_size: 256,
+                    permit_sign: false,
+                    permit_verify: false,
+                    permit_export: false,
+                    permit_derive: true,
+                    permit_encrypt: false,
+                    permit_decrypt: false,
+                },
+            }))
+            .map(|_| ())
+    }
+
+    fn raw_key_agreement(
+        client: &mut TestClient,
+        private_key_name: String,
+        peer_key: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::RawKeyAgreement(OpRawKeyAgreement {
+            private_key_name,
+            peer_key,
+        }))? {
+            NativeResult::RawKeyAgreement(result) => Ok(result.shared_secret),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    fn key_agreement_derive_aes_key(
+        client: &mut TestClient,
+        private_key_name: String,
+        peer_key: Vec<u8>,
+        key_name: String,
+    ) -> Result<()> {
+        client
+            .send_operation(NativeOperation::KeyAgreementDeriveKey(
+                OpKeyAgreementDeriveKey {
+                    private_key_name,
+                    peer_key,
+                    salt: SALT.to_vec(),
+                    info: INFO.to_vec(),
+                    key_name,
+                    key_attributes: KeyAttributes {
+                        key_type: KeyType::Aes,
+                        ecc_curve: None,
+                        algorithm: Algorithm::aead(AeadAlgorithm::Gcm),
+                        key_size: 128,
+                        permit_sign: false,
+                        permit_verify: false,
+                        permit_export: false,
+                        permit_derive: false,
+                        permit_encrypt: true,
+                        permit_decrypt: true,
+                    },
+                },
+            ))
+            .map(|_| ())
+    }
+
+    #[test]
+    fn raw_key_agreement_no_key() {
+        let key_name = String::from("raw_key_agreement_no_key");
+        let mut client = TestClient::new();
+        let status = raw_key_agreement(&mut client, key_name, vec![0x04; 65])
+            .expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn raw_key_agreement_same_secret() -> Result<()> {
+        let first_key_name = String::from("raw_key_agreement_same_secret_first");
+        let second_key_name = String::from("raw_key_agreement_same_secret_second");
+        let mut client = TestClient::new();
+
+        create_ecdh_key(&mut client, first_key_name.clone(), None)?;
+        create_ecdh_key(&mut client, second_key_name.clone(), None)?;
+        let first_public_key = client.export_public_key(first_key_name.clone())?;
+        let second_public_key = client.export_public_key(second_key_name.clone())?;
+
+        let first_secret =
+            raw_key_agreement(&mut client, first_key_name.clone(), second_public_key)?;
+        let second_secret =
+            raw_key_agreement(&mut client, second_key_name.clone(), first_public_key)?;
+        assert_eq!(first_secret.len(), 32);
+        assert_eq!(first_secret, second_secret);
+
+        client.destroy_key(second_key_name)?;
+        client.destroy_key(first_key_name)
+    }
+
+    #[test]
+    fn key_agreement_derive_same_key() -> Result<()> {
+        let first_key_name = String::from("key_agreement_derive_same_key_first");
+        let second_key_name = String::from("key_agreement_derive_same_key_second");
+        let first_aes_key_name = String::from("key_agreement_derive_same_key_first_aes");
+        let second_aes_key_name = String::from("key_agreement_derive_same_key_second_aes");
+        let mut client = TestClient::new();
+
+        create_ecdh_key(
+            &mut client,
+            first_key_name.clone(),
+            Some(KeyDerivationAlgorithm::Hkdf),
+        )?;
+        create_ecdh_key(
+            &mut client,
+            second_key_name.clone(),
+            Some(KeyDerivationAlgorithm::Hkdf),
+        )?;
+        let first_public_key = client.export_public_key(first_key_name.clone())?;
+        let second_public_key = client.export_public_key(second_key_name.clone())?;
+        key_agreement_derive_aes_key(
+            &mut client,
+            first_key_name.clone(),
+            second_public_key,
+            first_aes_key_name.clone(),
+        )?;
+        key_agreement_derive_aes_key(
+            &mut client,
+            second_key_name.clone(),
+            first_public_key,
+            second_aes_key_name.clone(),
+        )?;
+
+        // What is encrypted with one derived key can be decrypted with the other.
+        let ciphertext =
+            match client.send_operation(NativeOperation::AeadEncrypt(OpAeadEncrypt {
+                key_name: first_aes_key_name.clone(),
+                nonce: NONCE.to_vec(),
+                additional_data: Vec::new(),
+                plaintext: PLAINTEXT.to_vec(),
+            }))? {
+                NativeResult::AeadEncrypt(result) => result.ciphertext,
+                _ => panic!("Wrong result type."),
+            };
+        match client.send_operation(NativeOperation::AeadDecrypt(OpAeadDecrypt {
+            key_name: second_aes_key_name.clone(),
+            nonce: NONCE.to_vec(),
+            additional_data: Vec::new(),
+            ciphertext,
+        }))? {
+            NativeResult::AeadDecrypt(result) => assert_eq!(result.plaintext, PLAINTEXT.to_vec()),
+            _ => panic!("Wrong result type."),
+        };
+
+        client.destroy_key(second_aes_key_name)?;
+        client.destroy_key(first_aes_key_name)?;
+        client.destroy_key(second_key_name)?;
+        client.destroy_key(first_key_name)
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{
+        NativeOperation, NativeResult, OpAeadDecrypt, OpAeadEncrypt, OpCreateKey,
+        OpKeyDerivationOutputBytes, OpKeyDerivationOutputKey,
+    };
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const SALT: [u8; 8] = [0x53, 0x41, 0x4c, 0x54, 0x53, 0x41, 0x4c, 0x54];
+    const INFO: [u8; 4] = [0x49, 0x4e, 0x46, 0x4f];
+    const NONCE: [u8; 12] = [
+        0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b,
+    ];
+    const PLAINTEXT: [u8; 4] = [0xDE, 0xAD, 0xBE, 0xEF];
+
+    fn create_hkdf_key(client: &mut TestClient, key_name: String) -> Result<()> {
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: KeyAttributes {
+                    key_type: KeyType::Derive,
+                    ecc_curve: None,
+                    algorithm: Algorithm::key_derivation(
+                        KeyDerivationAlgorithm::Hkdf,
+                        Some(HashAlgorithm::Sha256),
+                    ),
+                    key_size: 256,
+                    permit_sign: false,
+                    permit_verify: false,
+                    permit_export: false,
+                    permit_derive: true,
+                    permit_encrypt: false,
+                    permit_decrypt: false,
+                },
+            }))
+            .map(|_| ())
+    }
+
+    fn output_bytes(
+        client: &mut TestClient,
+        base_key_name: String,
+        info: Vec<u8>,
+    ) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::KeyDerivationOutputBytes(
+            OpKeyDerivationOutputBytes {
+                base_key_name,
+                salt: SALT.to_vec(),
+                info,
+                length: 32,
+            },
+        ))? {
+            NativeResult::KeyDerivationOutputBytes(result) => Ok(result.output),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    fn output_aes_key(
+        client: &mut TestClient,
+        base_key_name: String,
+        key_name: String,
+    ) -> Result<()> {
+        client
+            .send_operation(NativeOperation::KeyDerivationOutputKey(
+                OpKeyDerivationOutputKey {
+                    base_key_name,
+                    salt: SALT.to_vec(),
+                    info: INFO.to_vec(),
+                    key_name,
+                    key_attributes: KeyAttributes {
+                        key_type: KeyType::Aes,
+                        ecc_curve: None,
+                        algorithm: Algorithm::aead(AeadAlgorithm::Gcm),
+                        key_size: 128,
+                        permit_sign: false,
+                        permit_verify: false,
+                        permit_export: false,
+                        permit_derive: false,
+                        permit_encrypt: true,
+                        permit_decrypt: true,
+                    },
+                },
+            ))
+            .map(|_| ())
+    }
+
+    #[test]
+    fn key_derivation_no_key() {
+        let key_name = String::from("key_derivation_no_key");
+        let mut client = TestClient::new();
+        let status =
+            output_bytes(&mut client, key_name, INFO.to_vec()).expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn key_derivation_output_bytes() -> Result<()> {
+        let key_name = String::from("key_derivation_output_bytes");
+        let mut client = TestClient::new();
+
+        create_hkdf_key(&mut client, key_name.clone())?;
+        let output = output_bytes(&mut client, key_name.clone(), INFO.to_vec())?;
+        assert_eq!(output.len(), 32);
+        // The derivation is deterministic and depends on the info.
+        assert_eq!(
+            output_bytes(&mut client, key_name.clone(), INFO.to_vec())?,
+            output
+        );
+        assert_ne!(
+            output_bytes(&mut client, key_name.clone(), Vec::new())?,
+            output
+        );
+        client.destroy_key(key_name)
+    }
+
+    #[test]
+    fn key_derivation_output_key() -> Result<()> {
+        let key_name = String::from("key_derivation_output_key");
+        let first_aes_key_name = String::from("key_derivation_output_key_first_aes");
+        let second_aes_key_name = String::from("key_derivation_output_key_second_aes");
+        let mut client = TestClient::new();
+
+        create_hkdf_key(&mut client, key_name.clone())?;
+        output_aes_key(&mut client, key_name.clone(), first_aes_key_name.clone())?;
+        output_aes_key(&mut client, key_name.clone(), second_aes_key_name.clone())?;
+
+        // Keys derived from the same inputs are the same.
+        let ciphertext =
+            match client.send_operation(NativeOperation::AeadEncrypt(OpAeadEncrypt {
+                key_name: first_aes_key_name.clone(),
+                nonce: NONCE.to_vec(),
+                additional_data: Vec::new(),
+                plaintext: PLAINTEXT.to_vec(),
+            }))? {
+                NativeResult::AeadEncrypt(result) => result.ciphertext,
+                _ => panic!("Wrong result type."),
+            };
+        match client.send_operation(NativeOperation::AeadDecrypt(OpAeadDecrypt {
+            key_name: second_aes_key_name.clone(),
+            nonce: NONCE.to_vec(),
+            additional_data: Vec::new(),
+            ciphertext,
+        }))? {
+            NativeResult::AeadDecrypt(result) => assert_eq!(result.plaintext, PLAINTEXT.to_vec()),
+            _ => panic!("Wrong result type."),
+        };
+
+        client.destroy_key(second_aes_key_name)?;
+        client.destroy_key(first_aes_key_name)?;
+        client.destroy_key(key_name)
+    }
+}
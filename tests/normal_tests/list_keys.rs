@@ -0,0 +1,61 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::{KeyInfo, NativeOperation, NativeResult, OpListKeys};
+    use parsec_interface::requests::{ProviderID, Result};
+
+    fn list_keys(client: &mut TestClient, provider: ProviderID) -> Result<Vec<KeyInfo>> {
+        client.set_provider(Some(provider));
+        let result = client.send_operation(NativeOperation::ListKeys(OpListKeys {}));
+        client.set_provider(None);
+        match result? {
+            NativeResult::ListKeys(result) => Ok(result.keys),
+            _ => panic!("Unexpected result for a ListKeys operation."),
+        }
+    }
+
+    #[test]
+    fn list_own_keys() -> Result<()> {
+        let key_name = String::from("list_own_keys");
+        let mut client = TestClient::new();
+
+        // Applications only see their own keys.
+        client.set_auth(String::from("list_keys_other_client").into_bytes());
+        client.create_rsa_sign_key(key_name.clone())?;
+        client.set_auth(String::from("list_keys_client").into_bytes());
+        assert!(list_keys(&mut client, ProviderID::MbedProvider)?.is_empty());
+
+        client.create_rsa_sign_key(key_name.clone())?;
+        let keys = list_keys(&mut client, ProviderID::MbedProvider)?;
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].name, key_name);
+        assert_eq!(keys[0].provider_id, ProviderID::MbedProvider);
+        assert!(keys[0].attributes.permit_sign);
+
+        // The Core Provider lists the keys of all the providers.
+        let keys = list_keys(&mut client, ProviderID::CoreProvider)?;
+        assert!(keys
+            .iter()
+            .any(|key| key.name == key_name && key.provider_id == ProviderID::MbedProvider));
+
+        client.destroy_key(key_name.clone())?;
+        assert!(list_keys(&mut client, ProviderID::MbedProvider)?.is_empty());
+
+        client.set_auth(String::from("list_keys_other_client").into_bytes());
+        client.destroy_key(key_name)
+    }
+}
@@ -0,0 +1,108 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{
+        NativeOperation, NativeResult, OpCreateKey, OpMacCompute, OpMacVerify,
+    };
+    use parsec_interface::requests::{ResponseStatus, Result};
+
+    const INPUT: [u8; 16] = [
+        0x50, 0x61, 0x72, 0x73, 0x65, 0x63, 0x20, 0x6d, 0x61, 0x63, 0x20, 0x69, 0x6e, 0x70, 0x75,
+        0x74,
+    ];
+
+    fn create_hmac_key(client: &mut TestClient, key_name: String) -> Result<()> {
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: KeyAttributes {
+                    key_type: KeyType::Hmac,
+                    ecc_curve: None,
+                    algorithm: Algorithm::mac(MacAlgorithm::Hmac, Some(HashAlgorithm::Sha256)),
+                    key_size: 256,
+                    permit_sign: true,
+                    permit_verify: true,
+                    permit_export: false,
+                    permit_derive: false,
+                    permit_encrypt: false,
+                    permit_decrypt: false,
+                },
+            }))
+            .map(|_| ())
+    }
+
+    fn mac_compute(client: &mut TestClient, key_name: String, input: Vec<u8>) -> Result<Vec<u8>> {
+        match client.send_operation(NativeOperation::MacCompute(OpMacCompute {
+            key_name,
+            input,
+        }))? {
+            NativeResult::MacCompute(result) => Ok(result.mac),
+            _ => panic!("Wrong result type."),
+        }
+    }
+
+    fn mac_verify(
+        client: &mut TestClient,
+        key_name: String,
+        input: Vec<u8>,
+        mac: Vec<u8>,
+    ) -> Result<()> {
+        client
+            .send_operation(NativeOperation::MacVerify(OpMacVerify {
+                key_name,
+                input,
+                mac,
+            }))
+            .map(|_| ())
+    }
+
+    #[test]
+    fn mac_compute_no_key() {
+        let key_name = String::from("mac_compute_no_key");
+        let mut client = TestClient::new();
+        let status =
+            mac_compute(&mut client, key_name, INPUT.to_vec()).expect_err("Key should not exist.");
+        assert_eq!(status, ResponseStatus::KeyDoesNotExist);
+    }
+
+    #[test]
+    fn mac_compute_and_verify() -> Result<()> {
+        let key_name = String::from("mac_compute_and_verify");
+        let mut client = TestClient::new();
+
+        create_hmac_key(&mut client, key_name.clone())?;
+        let mac = mac_compute(&mut client, key_name.clone(), INPUT.to_vec())?;
+        assert_eq!(mac.len(), 32);
+        mac_verify(&mut client, key_name.clone(), INPUT.to_vec(), mac)?;
+        client.destroy_key(key_name)
+    }
+
+    #[test]
+    fn mac_verify_fail() -> Result<()> {
+        let key_name = String::from("mac_verify_fail");
+        let mut client = TestClient::new();
+
+        create_hmac_key(&mut client, key_name.clone())?;
+        let mut mac = mac_compute(&mut client, key_name.clone(), INPUT.to_vec())?;
+        mac[0] ^= 0xff;
+        let status = mac_verify(&mut client, key_name.clone(), INPUT.to_vec(), mac)
+            .expect_err("Verification should fail.");
+        assert_eq!(status, ResponseStatus::PsaErrorInvalidSignature);
+        client.destroy_key(key_name)
+    }
+}
@@ -12,11 +12,18 @@
 // WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+mod aead;
+mod asym_encryption;
 mod asym_sign_verify;
 mod auth;
 mod basic;
 mod create_destroy_key;
 mod describe_assets;
 mod export_public_key;
+mod grants;
 mod import_key;
+mod key_agreement;
+mod key_derivation;
+mod list_keys;
+mod mac;
 mod ping;
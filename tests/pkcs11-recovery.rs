@@ -0,0 +1,82 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+// This test removes the SoftHSM token used by the service and creates it again, checking that the
+// PKCS 11 provider is unavailable in between and recovers the token afterwards. It must run alone,
+// after the pkcs11 tests, with the service using the configuration in tests/softhsm/config.toml.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::requests::{ProviderID, ResponseStatus, Result};
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process::Command;
+    use std::thread;
+    use std::time::Duration;
+
+    const HASH: [u8; 32] = [
+        0x69, 0x3E, 0xDB, 0x1B, 0x22, 0x79, 0x03, 0xF4, 0xC0, 0xBF, 0xD6, 0x91, 0x76, 0x37, 0x84,
+        0xA2, 0x94, 0x8E, 0x92, 0x50, 0x35, 0xC2, 0x8C, 0x5C, 0x3C, 0xCA, 0xFE, 0x18, 0xE8, 0x81,
+        0x37, 0x78,
+    ];
+
+    // Longer than the minimum time between two recovery attempts of the provider.
+    const RECOVERY_WAIT: Duration = Duration::from_secs(2);
+    // Number of requests sent before giving up waiting for the provider to notice the loss.
+    const MAX_ATTEMPTS: usize = 10;
+
+    fn manifest_path(path: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(path)
+    }
+
+    #[test]
+    fn token_removed_and_recreated() -> Result<()> {
+        let mut client = TestClient::new();
+        client.set_provider(Some(ProviderID::Pkcs11Provider));
+
+        let key_name = String::from("token_removed_and_recreated");
+        client.create_rsa_sign_key(key_name.clone())?;
+        let _ = client.sign(key_name.clone(), HASH.to_vec())?;
+
+        fs::remove_dir_all(manifest_path("target/softhsm/tokens"))
+            .expect("Failed to remove the SoftHSM token directory");
+
+        let mut status = None;
+        for _ in 0..MAX_ATTEMPTS {
+            match client.sign(key_name.clone(), HASH.to_vec()) {
+                Err(ResponseStatus::PsaErrorCommunicationFailure) => {
+                    status = Some(ResponseStatus::PsaErrorCommunicationFailure);
+                    break;
+                }
+                Err(e) => status = Some(e),
+                Ok(_) => (),
+            }
+            thread::sleep(RECOVERY_WAIT);
+        }
+        assert_eq!(status, Some(ResponseStatus::PsaErrorCommunicationFailure));
+
+        let setup = Command::new(manifest_path("tests/softhsm/setup.sh"))
+            .status()
+            .expect("Failed to run the SoftHSM setup script");
+        assert!(setup.success());
+        thread::sleep(RECOVERY_WAIT);
+
+        // The recreated token is empty, the key created before is gone with the old one.
+        let key_name = String::from("token_removed_and_recreated_again");
+        client.create_rsa_sign_key(key_name.clone())?;
+        let signature = client.sign(key_name.clone(), HASH.to_vec())?;
+        client.verify(key_name, HASH.to_vec(), signature)
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::operations::{NativeOperation, OpCreateKey, OpImportKey};
+    use parsec_interface::requests::{ProviderID, ResponseStatus, Result};
+
+    const HASH: [u8; 32] = [
+        0x69, 0x3E, 0xDB, 0x1B, 0x22, 0x79, 0x03, 0xF4, 0xC0, 0xBF, 0xD6, 0x91, 0x76, 0x37, 0x84,
+        0xA2, 0x94, 0x8E, 0x92, 0x50, 0x35, 0xC2, 0x8C, 0x5C, 0x3C, 0xCA, 0xFE, 0x18, 0xE8, 0x81,
+        0x37, 0x78,
+    ];
+
+    fn ecdsa_key_attributes(key_type: KeyType) -> KeyAttributes {
+        KeyAttributes {
+            key_type,
+            ecc_curve: Some(EccCurve::Secp256r1),
+            algorithm: Algorithm::sign(SignAlgorithm::Ecdsa, Some(HashAlgorithm::Sha256)),
+            key_size: 256,
+            permit_sign: true,
+            permit_verify: true,
+            permit_export: false,
+            permit_derive: false,
+            permit_encrypt: false,
+            permit_decrypt: false,
+        }
+    }
+
+    // The key attributes of the TestClient helpers do not have a curve, the operations are sent
+    // directly.
+    fn create_ecdsa_key(client: &mut TestClient, key_name: String) -> Result<()> {
+        client
+            .send_operation(NativeOperation::CreateKey(OpCreateKey {
+                key_name,
+                key_attributes: ecdsa_key_attributes(KeyType::EccKeypair),
+            }))
+            .map(|_| ())
+    }
+
+    fn import_ecdsa_public_key(
+        client: &mut TestClient,
+        key_name: String,
+        key_data: Vec<u8>,
+    ) -> Result<()> {
+        client
+            .send_operation(NativeOperation::ImportKey(OpImportKey {
+                key_name,
+                key_attributes: ecdsa_key_attributes(KeyType::EccPublicKey),
+                key_data,
+            }))
+            .map(|_| ())
+    }
+
+    #[test]
+    fn ecdsa_sign_and_verify() -> Result<()> {
+        let key_name = String::from("ecdsa_sign_and_verify");
+        let mut client = TestClient::new();
+        client.set_provider(Some(ProviderID::Pkcs11Provider));
+
+        create_ecdsa_key(&mut client, key_name.clone())?;
+        let signature = client.sign(key_name.clone(), HASH.to_vec())?;
+        client.verify(key_name.clone(), HASH.to_vec(), signature)?;
+        client.destroy_key(key_name)
+    }
+
+    #[test]
+    fn ecdsa_export_and_import_public_key() -> Result<()> {
+        let key_name = String::from("ecdsa_export_and_import_public_key");
+        let public_key_name = String::from("ecdsa_export_and_import_public_key_public");
+        let mut client = TestClient::new();
+        client.set_provider(Some(ProviderID::Pkcs11Provider));
+
+        create_ecdsa_key(&mut client, key_name.clone())?;
+        let signature = client.sign(key_name.clone(), HASH.to_vec())?;
+        let public_key = client.export_public_key(key_name.clone())?;
+        // The PSA format of an EC public key is the uncompressed point.
+        assert_eq!(public_key.len(), 65);
+        assert_eq!(public_key[0], 0x04);
+
+        import_ecdsa_public_key(&mut client, public_key_name.clone(), public_key)?;
+        client.verify(public_key_name.clone(), HASH.to_vec(), signature)?;
+        client.destroy_key(public_key_name)?;
+        client.destroy_key(key_name)
+    }
+
+    #[test]
+    fn ecdsa_import_invalid_public_key() {
+        let key_name = String::from("ecdsa_import_invalid_public_key");
+        let mut client = TestClient::new();
+        client.set_provider(Some(ProviderID::Pkcs11Provider));
+
+        // A compressed point.
+        let mut key_data = vec![0xab; 33];
+        key_data[0] = 0x02;
+        let status = import_ecdsa_public_key(&mut client, key_name.clone(), key_data)
+            .expect_err("Compressed points should be rejected");
+        assert_eq!(status, ResponseStatus::PsaErrorInvalidArgument);
+
+        // A point on another curve.
+        let mut key_data = vec![0xab; 97];
+        key_data[0] = 0x04;
+        let status = import_ecdsa_public_key(&mut client, key_name, key_data)
+            .expect_err("Points on another curve should be rejected");
+        assert_eq!(status, ResponseStatus::PsaErrorInvalidArgument);
+    }
+}
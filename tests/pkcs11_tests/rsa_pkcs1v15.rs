@@ -0,0 +1,61 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use parsec_client_test::TestClient;
+    use parsec_interface::operations::key_attributes::*;
+    use parsec_interface::requests::{ProviderID, Result};
+
+    const HASH: [u8; 32] = [
+        0x69, 0x3E, 0xDB, 0x1B, 0x22, 0x79, 0x03, 0xF4, 0xC0, 0xBF, 0xD6, 0x91, 0x76, 0x37, 0x84,
+        0xA2, 0x94, 0x8E, 0x92, 0x50, 0x35, 0xC2, 0x8C, 0x5C, 0x3C, 0xCA, 0xFE, 0x18, 0xE8, 0x81,
+        0x37, 0x78,
+    ];
+
+    fn rsa_pkcs1v15_sign_and_cross_verify(signer: ProviderID, verifier: ProviderID) -> Result<()> {
+        let key_name = format!(
+            "rsa_pkcs1v15_cross_verify_{}_{}",
+            signer as u8, verifier as u8
+        );
+        let algorithm =
+            Algorithm::sign(SignAlgorithm::RsaPkcs1v15Sign, Some(HashAlgorithm::Sha256));
+        let mut client = TestClient::new();
+
+        client.set_provider(Some(signer));
+        client.create_key(key_name.clone(), KeyType::RsaKeypair, algorithm.clone())?;
+        let signature = client.sign(key_name.clone(), HASH.to_vec())?;
+        let public_key = client.export_public_key(key_name.clone())?;
+
+        client.set_provider(Some(verifier));
+        client.import_key(
+            key_name.clone(),
+            KeyType::RsaPublicKey,
+            algorithm,
+            public_key,
+        )?;
+        client.verify(key_name, HASH.to_vec(), signature)
+    }
+
+    // The SoftHSM configuration has both the Mbed and the PKCS 11 providers.
+    #[test]
+    fn rsa_pkcs1v15_mbed_sign_pkcs11_verify() -> Result<()> {
+        rsa_pkcs1v15_sign_and_cross_verify(ProviderID::MbedProvider, ProviderID::Pkcs11Provider)
+    }
+
+    #[test]
+    fn rsa_pkcs1v15_pkcs11_sign_mbed_verify() -> Result<()> {
+        rsa_pkcs1v15_sign_and_cross_verify(ProviderID::Pkcs11Provider, ProviderID::MbedProvider)
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright (c) 2019, Arm Limited, All Rights Reserved
+// SPDX-License-Identifier: Apache-2.0
+//
+// Licensed under the Apache License, Version 2.0 (the "License"); you may
+// not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//          http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+// WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+#[cfg(test)]
+mod tests {
+    use log::info;
+    use parsec_client_test::TestClient;
+    use parsec_interface::requests::{ProviderID, Result};
+    use std::thread;
+    use std::time::Instant;
+
+    const HASH: [u8; 32] = [
+        0x69, 0x3E, 0xDB, 0x1B, 0x22, 0x79, 0x03, 0xF4, 0xC0, 0xBF, 0xD6, 0x91, 0x76, 0x37, 0x84,
+        0xA2, 0x94, 0x8E, 0x92, 0x50, 0x35, 0xC2, 0x8C, 0x5C, 0x3C, 0xCA, 0xFE, 0x18, 0xE8, 0x81,
+        0x37, 0x78,
+    ];
+    const SIGNATURES_PER_THREAD: usize = 100;
+
+    // Measures the signatures per second of the PKCS 11 provider with concurrent clients, which
+    // share the sessions of the pool. Run with RUST_LOG=info and --nocapture to see the result.
+    #[test]
+    fn concurrent_signatures() -> Result<()> {
+        let _ = env_logger::try_init();
+        let key_name = String::from("concurrent_signatures");
+        let mut client = TestClient::new();
+        client.set_provider(Some(ProviderID::Pkcs11Provider));
+        client.create_rsa_sign_key(key_name.clone())?;
+
+        let threads = num_cpus::get();
+        let start = Instant::now();
+        let signers: Vec<_> = (0..threads)
+            .map(|_| {
+                let key_name = key_name.clone();
+                thread::spawn(move || -> Result<()> {
+                    let mut client = TestClient::new();
+                    client.set_provider(Some(ProviderID::Pkcs11Provider));
+                    for _ in 0..SIGNATURES_PER_THREAD {
+                        let _ = client.sign(key_name.clone(), HASH.to_vec())?;
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+        for signer in signers {
+            signer.join().expect("Signing thread panicked")?;
+        }
+        let elapsed = start.elapsed();
+
+        let signatures = threads * SIGNATURES_PER_THREAD;
+        info!(
+            "{} signatures from {} threads in {:?}: {:.1} signatures per second.",
+            signatures,
+            threads,
+            elapsed,
+            signatures as f64 / elapsed.as_secs_f64()
+        );
+        client.destroy_key(key_name)
+    }
+}
@@ -0,0 +1,34 @@
+#!/usr/bin/env bash
+
+# ------------------------------------------------------------------------------
+# Copyright (c) 2019, Arm Limited, All Rights Reserved
+# SPDX-License-Identifier: Apache-2.0
+#
+# Licensed under the Apache License, Version 2.0 (the "License"); you may
+# not use this file except in compliance with the License.
+# You may obtain a copy of the License at
+#
+#          http://www.apache.org/licenses/LICENSE-2.0
+#
+# Unless required by applicable law or agreed to in writing, software
+# distributed under the License is distributed on an "AS IS" BASIS, WITHOUT
+# WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+# See the License for the specific language governing permissions and
+# limitations under the License.
+# ------------------------------------------------------------------------------
+
+# This script creates the SoftHSM token used by the PKCS 11 provider tests, erasing the previous
+# one. The SoftHSM configuration and the token are written under target/softhsm, SOFTHSM2_CONF
+# must point to the configuration for the service and the tests to find the token.
+#
+# Usage: ./tests/softhsm/setup.sh
+#        export SOFTHSM2_CONF=$PWD/target/softhsm/softhsm2.conf
+
+SOFTHSM_DIR=$(cd "$(dirname "$0")/../.." && pwd)/target/softhsm
+
+rm -rf "$SOFTHSM_DIR/tokens" || exit 1
+mkdir -p "$SOFTHSM_DIR/tokens" || exit 1
+echo "directories.tokendir = $SOFTHSM_DIR/tokens" > "$SOFTHSM_DIR/softhsm2.conf" || exit 1
+
+SOFTHSM2_CONF="$SOFTHSM_DIR/softhsm2.conf" softhsm2-util --init-token --free \
+    --label "Parsec Tests" --pin 123456 --so-pin 123456 || exit 1